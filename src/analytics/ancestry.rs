@@ -0,0 +1,55 @@
+//! onboarding family tree, from the AncestorOf edges of the Ancestry resource
+use anyhow::{Context, Result};
+use libra_types::exports::AccountAddress;
+use neo4rs::Graph;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Descendant {
+    pub address: String,
+    /// generations below the root, a direct child has depth 1
+    pub depth: u64,
+    /// the account which onboarded this one, None if it is not in the graph
+    pub parent: Option<String>,
+}
+
+/// the subtree of all accounts onboarded by `address`, directly or through
+/// its descendants, ordered by depth.
+/// Every account has an edge from each of its ancestors, so no path
+/// traversal is needed.
+pub async fn get_descendants(
+    pool: &Graph,
+    address: AccountAddress,
+    max_depth: Option<u64>,
+) -> Result<Vec<Descendant>> {
+    let depth_filter = match max_depth {
+        Some(d) => format!("WHERE r.depth <= {}", d),
+        None => "".to_owned(),
+    };
+    let cypher_string = format!(
+        r#"
+MATCH (:Account {{address: "{}"}})-[r:AncestorOf]->(d:Account)
+{depth_filter}
+OPTIONAL MATCH (p:Account)-[:AncestorOf {{depth: 1}}]->(d)
+RETURN d.address AS address, r.depth AS depth, p.address AS parent
+ORDER BY depth, address
+"#,
+        address.to_hex_literal()
+    );
+
+    let cypher_query = neo4rs::query(&cypher_string);
+    let mut res = pool
+        .execute(cypher_query)
+        .await
+        .context("execute query error")?;
+
+    let mut descendants = vec![];
+    while let Some(row) = res.next().await? {
+        descendants.push(Descendant {
+            address: row.get("address").context("no address field")?,
+            depth: row.get("depth").context("no depth field")?,
+            parent: row.get::<Option<String>>("parent").unwrap_or(None),
+        });
+    }
+    Ok(descendants)
+}
@@ -0,0 +1,276 @@
+//! check that the account states before the V5 -> V6 hard fork
+//! were carried over, after the coin rebase.
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use diem_types::account_address::AccountAddress;
+use log::info;
+use neo4rs::Graph;
+use serde::Serialize;
+
+use crate::{
+    scan::FrameworkVersion, schema_account_state::WarehouseAccState, util::LEGACY_REBASE_MULTIPLIER,
+};
+
+/// the default difference in coins tolerated between the rebased V5 balance and the V6 balance
+pub static DEFAULT_TOLERANCE: f64 = 0.01;
+
+/// The last V5 state of an account, and its first V6 state
+#[derive(Debug, Clone, Serialize)]
+pub struct ContinuityLink {
+    pub address: AccountAddress,
+    pub v5_version: u64,
+    pub v5_epoch: u64,
+    pub v6_version: u64,
+    pub v6_epoch: u64,
+    /// V6 for the genesis state, V7 for the state archives after the fork
+    pub v6_framework_version: FrameworkVersion,
+    pub v5_balance: f64,
+    /// the V5 balance after the rebase
+    pub expected_balance: f64,
+    pub v6_balance: f64,
+    /// v6_balance - expected_balance
+    pub delta: f64,
+    pub balance_ok: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ContinuityIssue {
+    /// the account had a balance in V5, but is not found in V6
+    MissingInV6 {
+        address: AccountAddress,
+        v5_balance: f64,
+    },
+    /// the V6 balance is not the rebased V5 balance
+    BalanceMismatch {
+        address: AccountAddress,
+        expected: f64,
+        found: f64,
+    },
+    /// only one side is a slow wallet
+    SlowWalletMismatch {
+        address: AccountAddress,
+        v5_slow_wallet: bool,
+        v6_slow_wallet: bool,
+    },
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ForkContinuityReport {
+    pub v5_accounts: u64,
+    pub v6_accounts: u64,
+    /// accounts created after the fork
+    pub only_in_v6: u64,
+    pub links: Vec<ContinuityLink>,
+    pub issues: Vec<ContinuityIssue>,
+}
+
+/// keep the latest (or earliest) state of each address
+fn by_address(
+    states: &[WarehouseAccState],
+    latest: bool,
+) -> BTreeMap<AccountAddress, &WarehouseAccState> {
+    let mut map: BTreeMap<AccountAddress, &WarehouseAccState> = BTreeMap::new();
+    for s in states {
+        let replace = match map.get(&s.address) {
+            Some(prev) if latest => s.time.version > prev.time.version,
+            Some(prev) => s.time.version < prev.time.version,
+            None => true,
+        };
+        if replace {
+            map.insert(s.address, s);
+        }
+    }
+    map
+}
+
+/// compare the last V5 state with the first V6 state of each address.
+/// V5 balances are multiplied by the rebase, and compared within `tolerance` coins.
+pub fn compare_fork(
+    v5: &[WarehouseAccState],
+    v6: &[WarehouseAccState],
+    tolerance: f64,
+) -> ForkContinuityReport {
+    let last_v5 = by_address(v5, true);
+    let first_v6 = by_address(v6, false);
+
+    let mut report = ForkContinuityReport {
+        v5_accounts: last_v5.len() as u64,
+        v6_accounts: first_v6.len() as u64,
+        only_in_v6: first_v6.keys().filter(|a| !last_v5.contains_key(a)).count() as u64,
+        ..Default::default()
+    };
+
+    for (address, before) in last_v5 {
+        let Some(after) = first_v6.get(&address) else {
+            if before.balance > 0.0 {
+                report.issues.push(ContinuityIssue::MissingInV6 {
+                    address,
+                    v5_balance: before.balance,
+                });
+            }
+            continue;
+        };
+
+        let expected_balance = before.balance * LEGACY_REBASE_MULTIPLIER as f64;
+        let delta = after.balance - expected_balance;
+        let balance_ok = delta.abs() <= tolerance;
+
+        if !balance_ok {
+            report.issues.push(ContinuityIssue::BalanceMismatch {
+                address,
+                expected: expected_balance,
+                found: after.balance,
+            });
+        }
+        if before.slow_wallet_acc != after.slow_wallet_acc {
+            report.issues.push(ContinuityIssue::SlowWalletMismatch {
+                address,
+                v5_slow_wallet: before.slow_wallet_acc,
+                v6_slow_wallet: after.slow_wallet_acc,
+            });
+        }
+
+        report.links.push(ContinuityLink {
+            address,
+            v5_version: before.time.version,
+            v5_epoch: before.time.epoch,
+            v6_version: after.time.version,
+            v6_epoch: after.time.epoch,
+            v6_framework_version: after.time.framework_version.clone(),
+            v5_balance: before.balance,
+            expected_balance,
+            v6_balance: after.balance,
+            delta,
+            balance_ok,
+        });
+    }
+
+    info!(
+        "fork continuity, linked: {}, issues: {}",
+        report.links.len(),
+        report.issues.len()
+    );
+    report
+}
+
+impl ContinuityLink {
+    pub fn to_cypher_object_template(&self) -> String {
+        format!(
+            r#"{{address: "{}", v5_version: {}, v5_epoch: {}, v6_version: {}, v6_epoch: {}, v6_framework_version: "{}", expected_balance: {}, delta: {}, balance_ok: {}}}"#,
+            self.address.to_hex_literal(),
+            self.v5_version,
+            self.v5_epoch,
+            self.v6_version,
+            self.v6_epoch,
+            self.v6_framework_version,
+            self.expected_balance,
+            self.delta,
+            self.balance_ok,
+        )
+    }
+
+    pub fn to_cypher_map(list: &[Self]) -> String {
+        let mut list_literal = "".to_owned();
+        for el in list {
+            let s = el.to_cypher_object_template();
+            list_literal.push_str(&s);
+            list_literal.push(',');
+        }
+        list_literal.pop(); // need to drop last comma ","
+        format!("[{}]", list_literal)
+    }
+}
+
+/// link the V5 and V6 snapshots in the graph.
+/// Both snapshots must already be loaded, links whose snapshots
+/// are not found are skipped.
+/// Returns the count of links merged.
+pub async fn persist_continuity_links(
+    pool: &Graph,
+    links: &[ContinuityLink],
+    batch_size: usize,
+) -> Result<u64> {
+    let mut merged = 0;
+    for c in links.chunks(batch_size) {
+        let list_str = ContinuityLink::to_cypher_map(c);
+        let cypher_string = format!(
+            r#"
+WITH {list_str} AS links
+UNWIND links AS l
+MATCH (before:Snapshot {{address: l.address, epoch: l.v5_epoch, version: l.v5_version, framework_version: "V5"}})
+MATCH (after:Snapshot {{address: l.address, epoch: l.v6_epoch, version: l.v6_version, framework_version: l.v6_framework_version}})
+MERGE (before)-[r:CONTINUES]->(after)
+SET
+  r.expected_balance = l.expected_balance,
+  r.balance_delta = l.delta,
+  r.balance_ok = l.balance_ok
+RETURN COUNT(r) AS merged_links
+"#
+        );
+
+        let cypher_query = neo4rs::query(&cypher_string);
+        let mut res = pool
+            .execute(cypher_query)
+            .await
+            .context("execute query error")?;
+
+        let row = res.next().await?.context("no row returned")?;
+        let n: u64 = row.get("merged_links").context("no merged_links field")?;
+        merged += n;
+    }
+    info!("continuity links merged: {}", merged);
+    Ok(merged)
+}
+
+#[test]
+fn test_compare_fork() {
+    let state = |addr: AccountAddress, version: u64, balance: f64| {
+        let mut s = WarehouseAccState::new(addr);
+        s.time.version = version;
+        s.balance = balance;
+        s
+    };
+    let a = AccountAddress::from_hex_literal("0xa").unwrap();
+    let b = AccountAddress::from_hex_literal("0xb").unwrap();
+    let c = AccountAddress::from_hex_literal("0xc").unwrap();
+    let d = AccountAddress::from_hex_literal("0xd").unwrap();
+
+    let v5 = vec![
+        state(a, 1, 1.0),
+        // the last state is the one compared
+        state(a, 2, 2.0),
+        state(b, 2, 1.0),
+        state(c, 2, 3.0),
+    ];
+    let v6 = vec![
+        state(a, 0, 70.0),
+        state(b, 0, 10.0),
+        state(d, 0, 1.0),
+        // not the first state
+        state(b, 100, 35.0),
+    ];
+
+    let report = compare_fork(&v5, &v6, DEFAULT_TOLERANCE);
+    assert!(report.v5_accounts == 3);
+    assert!(report.only_in_v6 == 1);
+    assert!(report.links.len() == 2);
+    assert!(
+        report
+            .links
+            .iter()
+            .find(|l| l.address == a)
+            .unwrap()
+            .balance_ok
+    );
+    assert!(report.issues.len() == 2);
+    assert!(report.issues.contains(&ContinuityIssue::BalanceMismatch {
+        address: b,
+        expected: 35.0,
+        found: 10.0
+    }));
+    assert!(report.issues.contains(&ContinuityIssue::MissingInV6 {
+        address: c,
+        v5_balance: 3.0
+    }));
+}
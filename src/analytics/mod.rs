@@ -1,4 +1,8 @@
+pub mod ancestry;
 pub mod enrich_account_funding;
 pub mod enrich_rms;
 pub mod exchange_stats;
+pub mod fork_continuity;
 pub mod offline_matching;
+pub mod reconcile;
+pub mod slow_wallet_compliance;
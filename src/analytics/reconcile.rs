@@ -0,0 +1,325 @@
+//! reconcile the balance changes between two snapshots with the
+//! transactions loaded in that version range.
+//! An unexplained delta is either rewards, fees and burns, or a sign of
+//! missing archives and transaction types which were not decoded.
+use std::{fs::File, io::Write, path::Path};
+
+use anyhow::{Context, Result};
+use log::info;
+use neo4rs::Graph;
+use serde::Serialize;
+
+use crate::{scan::FrameworkVersion, util::LEGACY_REBASE_MULTIPLIER};
+
+/// the default difference in coins tolerated between the balance change and the net of transactions
+pub static DEFAULT_TOLERANCE: f64 = 0.01;
+
+/// The balances of one account at both snapshots, and the coins it
+/// sent and received in between
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceChange {
+    pub address: String,
+    /// None if the account has no state at the start version
+    pub start_balance: Option<f64>,
+    pub end_balance: f64,
+    pub tx_in: f64,
+    pub tx_out: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Discrepancy {
+    pub address: String,
+    pub framework_version: FrameworkVersion,
+    pub start_version: u64,
+    pub end_version: u64,
+    pub start_balance: f64,
+    pub end_balance: f64,
+    /// end_balance - start_balance
+    pub balance_delta: f64,
+    /// tx_in - tx_out
+    pub tx_net: f64,
+    /// balance_delta - tx_net
+    pub unexplained: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReconcileReport {
+    pub framework_version: FrameworkVersion,
+    pub start_version: u64,
+    pub end_version: u64,
+    pub accounts: u64,
+    /// sum of all the unexplained deltas
+    pub total_unexplained: f64,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+/// compare each balance change with the net of transactions, and keep
+/// the accounts where the difference is more than `tolerance` coins.
+/// Largest differences first.
+pub fn find_discrepancies(
+    changes: &[BalanceChange],
+    framework_version: &FrameworkVersion,
+    start_version: u64,
+    end_version: u64,
+    tolerance: f64,
+) -> ReconcileReport {
+    let mut report = ReconcileReport {
+        framework_version: framework_version.clone(),
+        start_version,
+        end_version,
+        accounts: changes.len() as u64,
+        ..Default::default()
+    };
+
+    for c in changes {
+        // accounts created in the range start at zero
+        let start_balance = c.start_balance.unwrap_or(0.0);
+        let balance_delta = c.end_balance - start_balance;
+        let tx_net = c.tx_in - c.tx_out;
+        let unexplained = balance_delta - tx_net;
+
+        if unexplained.abs() > tolerance {
+            report.total_unexplained += unexplained;
+            report.discrepancies.push(Discrepancy {
+                address: c.address.clone(),
+                framework_version: framework_version.clone(),
+                start_version,
+                end_version,
+                start_balance,
+                end_balance: c.end_balance,
+                balance_delta,
+                tx_net,
+                unexplained,
+            });
+        }
+    }
+
+    report
+        .discrepancies
+        .sort_by(|a, b| b.unexplained.abs().total_cmp(&a.unexplained.abs()));
+
+    info!(
+        "reconciled accounts: {}, discrepancies: {}",
+        report.accounts,
+        report.discrepancies.len()
+    );
+    report
+}
+
+/// the balance change of every account with a snapshot at `end_version`,
+/// with the coins of the transactions after `start_version` up to and including `end_version`.
+/// Versions restart after the V5 hard fork, so snapshots and transactions
+/// are only matched within one framework version.
+/// V5 transaction coins are rebased, but V5 balances are not, so the
+/// coins are divided by the rebase to compare them.
+pub async fn query_balance_changes(
+    pool: &Graph,
+    framework_version: &FrameworkVersion,
+    start_version: u64,
+    end_version: u64,
+) -> Result<Vec<BalanceChange>> {
+    let coin_divisor = match framework_version {
+        FrameworkVersion::V5 => LEGACY_REBASE_MULTIPLIER,
+        _ => 1,
+    };
+    let cypher_string = format!(
+        r#"
+MATCH (a:Account)-[:State]->(after:Snapshot {{version: {end_version}, framework_version: "{framework_version}"}})
+OPTIONAL MATCH (a)-[:State]->(before:Snapshot {{version: {start_version}, framework_version: "{framework_version}"}})
+CALL {{
+  WITH a
+  OPTIONAL MATCH ()-[t:Tx {{framework_version: "{framework_version}"}}]->(a)
+  WHERE t.version > {start_version} AND t.version <= {end_version} AND t.coins IS NOT NULL
+  RETURN toFloat(sum(t.coins)) / {coin_divisor} AS tx_in
+}}
+CALL {{
+  WITH a
+  OPTIONAL MATCH (a)-[t:Tx {{framework_version: "{framework_version}"}}]->()
+  WHERE t.version > {start_version} AND t.version <= {end_version} AND t.coins IS NOT NULL
+  RETURN toFloat(sum(t.coins)) / {coin_divisor} AS tx_out
+}}
+RETURN
+  a.address AS address,
+  before.balance AS start_balance,
+  after.balance AS end_balance,
+  tx_in,
+  tx_out
+"#
+    );
+
+    let cypher_query = neo4rs::query(&cypher_string);
+    let mut res = pool
+        .execute(cypher_query)
+        .await
+        .context("execute query error")?;
+
+    let mut changes = vec![];
+    while let Some(row) = res.next().await? {
+        changes.push(BalanceChange {
+            address: row.get("address").context("no address field")?,
+            start_balance: row.get::<Option<f64>>("start_balance").unwrap_or(None),
+            end_balance: row.get("end_balance").context("no end_balance field")?,
+            tx_in: row.get("tx_in").context("no tx_in field")?,
+            tx_out: row.get("tx_out").context("no tx_out field")?,
+        });
+    }
+    Ok(changes)
+}
+
+impl Discrepancy {
+    pub fn to_cypher_object_template(&self) -> String {
+        format!(
+            r#"{{address: "{}", framework_version: "{}", start_version: {}, end_version: {}, start_balance: {}, end_balance: {}, balance_delta: {}, tx_net: {}, unexplained: {}}}"#,
+            self.address,
+            self.framework_version,
+            self.start_version,
+            self.end_version,
+            self.start_balance,
+            self.end_balance,
+            self.balance_delta,
+            self.tx_net,
+            self.unexplained,
+        )
+    }
+
+    pub fn to_cypher_map(list: &[Self]) -> String {
+        let mut list_literal = "".to_owned();
+        for el in list {
+            let s = el.to_cypher_object_template();
+            list_literal.push_str(&s);
+            list_literal.push(',');
+        }
+        list_literal.pop(); // need to drop last comma ","
+        format!("[{}]", list_literal)
+    }
+}
+
+/// merge a Discrepancy node for each account, keyed by the framework version
+/// and version range.
+/// Returns the count of discrepancies merged.
+pub async fn persist_discrepancies(
+    pool: &Graph,
+    discrepancies: &[Discrepancy],
+    batch_size: usize,
+) -> Result<u64> {
+    let mut merged = 0;
+    for c in discrepancies.chunks(batch_size) {
+        let list_str = Discrepancy::to_cypher_map(c);
+        let cypher_string = format!(
+            r#"
+WITH {list_str} AS discrepancies
+UNWIND discrepancies AS d
+MATCH (a:Account {{address: d.address}})
+MERGE (dis:Discrepancy {{address: d.address, framework_version: d.framework_version, start_version: d.start_version, end_version: d.end_version}})
+SET
+  dis.start_balance = d.start_balance,
+  dis.end_balance = d.end_balance,
+  dis.balance_delta = d.balance_delta,
+  dis.tx_net = d.tx_net,
+  dis.unexplained = d.unexplained
+MERGE (a)-[:HasDiscrepancy]->(dis)
+RETURN COUNT(dis) AS merged_discrepancies
+"#
+        );
+
+        let cypher_query = neo4rs::query(&cypher_string);
+        let mut res = pool
+            .execute(cypher_query)
+            .await
+            .context("execute query error")?;
+
+        let row = res.next().await?.context("no row returned")?;
+        let n: u64 = row
+            .get("merged_discrepancies")
+            .context("no merged_discrepancies field")?;
+        merged += n;
+    }
+    info!("discrepancies merged: {}", merged);
+    Ok(merged)
+}
+
+/// write the discrepancies as CSV with a header row
+pub fn write_csv(discrepancies: &[Discrepancy], path: &Path) -> Result<()> {
+    let mut file =
+        File::create(path).context(format!("could not create csv {}", path.display()))?;
+    writeln!(
+        file,
+        "address,framework_version,start_version,end_version,start_balance,end_balance,balance_delta,tx_net,unexplained"
+    )?;
+    for d in discrepancies {
+        writeln!(
+            file,
+            "{},{},{},{},{},{},{},{},{}",
+            d.address,
+            d.framework_version,
+            d.start_version,
+            d.end_version,
+            d.start_balance,
+            d.end_balance,
+            d.balance_delta,
+            d.tx_net,
+            d.unexplained
+        )?;
+    }
+    info!("discrepancies written to {}", path.display());
+    Ok(())
+}
+
+/// reconcile all the accounts between two snapshot versions of one framework version
+pub async fn reconcile(
+    pool: &Graph,
+    framework_version: &FrameworkVersion,
+    start_version: u64,
+    end_version: u64,
+    tolerance: f64,
+    persist: bool,
+) -> Result<ReconcileReport> {
+    let changes =
+        query_balance_changes(pool, framework_version, start_version, end_version).await?;
+    let report = find_discrepancies(
+        &changes,
+        framework_version,
+        start_version,
+        end_version,
+        tolerance,
+    );
+    if persist {
+        persist_discrepancies(pool, &report.discrepancies, 1000).await?;
+    }
+    Ok(report)
+}
+
+#[test]
+fn test_find_discrepancies() {
+    let change =
+        |address: &str, start: Option<f64>, end: f64, tx_in: f64, tx_out: f64| BalanceChange {
+            address: address.to_owned(),
+            start_balance: start,
+            end_balance: end,
+            tx_in,
+            tx_out,
+        };
+
+    let changes = vec![
+        // explained by transfers
+        change("0xa", Some(10.0), 15.0, 7.0, 2.0),
+        // created in the range
+        change("0xb", None, 3.0, 3.0, 0.0),
+        // a missing outbound transfer
+        change("0xc", Some(20.0), 13.0, 0.0, 5.0),
+        // rewards
+        change("0xd", Some(1.0), 2.0, 0.0, 0.0),
+        // within tolerance
+        change("0xe", Some(1.0), 1.005, 0.0, 0.0),
+    ];
+
+    let report = find_discrepancies(&changes, &FrameworkVersion::V7, 10, 20, DEFAULT_TOLERANCE);
+    assert!(report.accounts == 5);
+    assert!(report.discrepancies.len() == 2);
+    // largest first
+    assert!(report.discrepancies[0].address == "0xc");
+    assert!(report.discrepancies[0].unexplained == -2.0);
+    assert!(report.discrepancies[1].address == "0xd");
+    assert!(report.discrepancies[1].unexplained == 1.0);
+    assert!(report.total_unexplained == -1.0);
+}
@@ -0,0 +1,326 @@
+//! check that slow wallets only transfer what has been unlocked, and find
+//! accounts which relay funds from slow wallets.
+use anyhow::{Context, Result};
+use log::info;
+use neo4rs::Graph;
+use serde::{Deserialize, Serialize};
+
+use crate::util::LEGACY_REBASE_MULTIPLIER;
+
+/// the difference in coins tolerated, transfers are rounded to cents when loaded
+pub static DEFAULT_TOLERANCE: f64 = 0.01;
+
+/// the slow wallet resource at one snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlowSnapshot {
+    pub version: u64,
+    pub epoch: u64,
+    pub unlocked: f64,
+    pub transferred: Option<f64>,
+}
+
+/// a transfer sent by the slow wallet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboundTx {
+    pub version: u64,
+    pub coins: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ComplianceIssue {
+    /// the state of the wallet shows more transferred than unlocked
+    TransferredExceedsUnlocked {
+        version: u64,
+        unlocked: f64,
+        transferred: f64,
+    },
+    /// the loaded transactions add up to more than was unlocked
+    TxExceedsUnlocked {
+        version: u64,
+        unlocked: f64,
+        tx_total: f64,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SlowWalletReport {
+    pub address: String,
+    pub framework_version: String,
+    pub snapshots: u64,
+    pub first_version: u64,
+    pub last_version: u64,
+    pub unlocked_start: f64,
+    pub unlocked_end: f64,
+    /// coins unlocked per epoch between the first and last snapshot
+    pub unlock_per_epoch: Option<f64>,
+    /// coins sent in transactions after the first snapshot
+    pub outbound_coins: f64,
+    pub issues: Vec<ComplianceIssue>,
+}
+
+/// coins received from a slow wallet and sent on to another account soon after
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayPattern {
+    pub framework_version: String,
+    pub source: String,
+    pub intermediary: String,
+    pub destination: String,
+    pub amount_in: f64,
+    pub amount_out: f64,
+    pub version_in: u64,
+    pub version_out: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SlowWalletComplianceReport {
+    pub wallets: Vec<SlowWalletReport>,
+    pub relays: Vec<RelayPattern>,
+}
+
+impl SlowWalletComplianceReport {
+    /// wallets with any issue
+    pub fn non_compliant(&self) -> impl Iterator<Item = &SlowWalletReport> {
+        self.wallets.iter().filter(|w| !w.issues.is_empty())
+    }
+}
+
+/// Follow the wallet through its snapshots of one framework version, in
+/// version order. Versions restart after V5, so the snapshots and
+/// transactions of different framework versions are never compared.
+/// The outbound coins are expected in the units of the snapshots, see
+/// `query_slow_wallets`.
+/// The transferred amount of the first snapshot is the baseline, to which
+/// the outbound transactions after it are added.
+/// NOTE: the chain may not count every outbound transaction against the
+/// unlocked amount, so a TxExceedsUnlocked issue is a lead, not proof.
+pub fn analyze_slow_wallet(
+    address: &str,
+    framework_version: &str,
+    snapshots: &[SlowSnapshot],
+    outbound: &[OutboundTx],
+    tolerance: f64,
+) -> Option<SlowWalletReport> {
+    let first = snapshots.first()?;
+    let last = snapshots.last()?;
+
+    let mut issues = vec![];
+    let baseline = first.transferred.unwrap_or(0.0);
+    for s in snapshots {
+        if let Some(transferred) = s.transferred {
+            if transferred > s.unlocked + tolerance {
+                issues.push(ComplianceIssue::TransferredExceedsUnlocked {
+                    version: s.version,
+                    unlocked: s.unlocked,
+                    transferred,
+                });
+            }
+        }
+
+        let tx_total = baseline
+            + outbound
+                .iter()
+                .filter(|t| t.version > first.version && t.version <= s.version)
+                .map(|t| t.coins)
+                .sum::<f64>();
+        if tx_total > s.unlocked + tolerance {
+            issues.push(ComplianceIssue::TxExceedsUnlocked {
+                version: s.version,
+                unlocked: s.unlocked,
+                tx_total,
+            });
+        }
+    }
+
+    let unlock_per_epoch = if last.epoch > first.epoch {
+        Some((last.unlocked - first.unlocked) / (last.epoch - first.epoch) as f64)
+    } else {
+        None
+    };
+
+    Some(SlowWalletReport {
+        address: address.to_owned(),
+        framework_version: framework_version.to_owned(),
+        snapshots: snapshots.len() as u64,
+        first_version: first.version,
+        last_version: last.version,
+        unlocked_start: first.unlocked,
+        unlocked_end: last.unlocked,
+        unlock_per_epoch,
+        outbound_coins: outbound
+            .iter()
+            .filter(|t| t.version > first.version)
+            .map(|t| t.coins)
+            .sum(),
+        issues,
+    })
+}
+
+/// the snapshots and outbound transactions of every slow wallet, grouped by
+/// framework version.
+/// V5 transaction coins are rebased when extracted, but the V5 slow wallet
+/// amounts are not, so those coins are divided by the rebase.
+pub async fn query_slow_wallets(
+    pool: &Graph,
+) -> Result<Vec<(String, String, Vec<SlowSnapshot>, Vec<OutboundTx>)>> {
+    let cypher_string = format!(
+        r#"
+MATCH (a:Account)-[:State]->(s:Snapshot)
+WHERE s.slow_wallet = true AND s.slow_unlocked IS NOT NULL
+WITH a, s
+ORDER BY s.version ASC
+WITH a, s.framework_version AS framework_version, collect({{
+  version: s.version,
+  epoch: s.epoch,
+  unlocked: toFloat(s.slow_unlocked),
+  transferred: toFloat(s.slow_transfer)
+}}) AS snaps
+RETURN
+  a.address AS address,
+  framework_version,
+  snaps,
+  [(a)-[t:Tx]->()
+    WHERE t.framework_version = framework_version AND t.coins IS NOT NULL AND t.version IS NOT NULL
+    | {{
+      version: t.version,
+      coins: CASE WHEN t.framework_version = "V5"
+        THEN toFloat(t.coins) / {LEGACY_REBASE_MULTIPLIER}
+        ELSE t.coins END
+    }}] AS txs
+"#
+    );
+
+    let cypher_query = neo4rs::query(&cypher_string);
+    let mut res = pool
+        .execute(cypher_query)
+        .await
+        .context("execute query error")?;
+
+    let mut wallets = vec![];
+    while let Some(row) = res.next().await? {
+        let address: String = row.get("address").context("no address field")?;
+        let framework_version: String = row
+            .get("framework_version")
+            .context("no framework_version field")?;
+        let snaps: Vec<SlowSnapshot> = row.get("snaps").context("no snaps field")?;
+        let txs: Vec<OutboundTx> = row.get("txs").context("no txs field")?;
+        wallets.push((address, framework_version, snaps, txs));
+    }
+    Ok(wallets)
+}
+
+/// Find accounts which received coins from a slow wallet, and sent at least
+/// `min_ratio` of that amount to a third account within `max_versions`.
+/// Both transactions are of the framework version in which the source was
+/// a slow wallet.
+pub async fn query_relays(
+    pool: &Graph,
+    min_ratio: f64,
+    max_versions: u64,
+) -> Result<Vec<RelayPattern>> {
+    let cypher_string = format!(
+        r#"
+MATCH (a:Account)-[:State]->(s:Snapshot {{slow_wallet: true}})
+WITH DISTINCT a, s.framework_version AS framework_version
+MATCH (a)-[t1:Tx]->(b:Account)-[t2:Tx]->(c:Account)
+WHERE t1.framework_version = framework_version
+  AND t2.framework_version = framework_version
+  AND t1.coins > 0
+  AND t2.coins >= t1.coins * {min_ratio}
+  AND t2.version > t1.version
+  AND t2.version <= t1.version + {max_versions}
+  AND c <> a
+  AND c <> b
+  AND b <> a
+RETURN
+  framework_version,
+  a.address AS source,
+  b.address AS intermediary,
+  c.address AS destination,
+  t1.coins AS amount_in,
+  t2.coins AS amount_out,
+  t1.version AS version_in,
+  t2.version AS version_out
+ORDER BY framework_version, version_in
+"#
+    );
+
+    let cypher_query = neo4rs::query(&cypher_string);
+    let mut res = pool
+        .execute(cypher_query)
+        .await
+        .context("execute query error")?;
+
+    let mut relays = vec![];
+    while let Some(row) = res.next().await? {
+        relays.push(row.to::<RelayPattern>()?);
+    }
+    Ok(relays)
+}
+
+/// check every slow wallet, and find the relays of their funds
+pub async fn slow_wallet_compliance(
+    pool: &Graph,
+    tolerance: f64,
+    relay_min_ratio: f64,
+    relay_max_versions: u64,
+) -> Result<SlowWalletComplianceReport> {
+    let mut report = SlowWalletComplianceReport::default();
+    for (address, framework_version, snaps, txs) in query_slow_wallets(pool).await? {
+        if let Some(r) = analyze_slow_wallet(&address, &framework_version, &snaps, &txs, tolerance)
+        {
+            report.wallets.push(r);
+        }
+    }
+    report.relays = query_relays(pool, relay_min_ratio, relay_max_versions).await?;
+
+    info!(
+        "slow wallets: {}, non compliant: {}, relays: {}",
+        report.wallets.len(),
+        report.non_compliant().count(),
+        report.relays.len()
+    );
+    Ok(report)
+}
+
+#[test]
+fn test_analyze_slow_wallet() {
+    let snap = |version: u64, epoch: u64, unlocked: f64, transferred: f64| SlowSnapshot {
+        version,
+        epoch,
+        unlocked,
+        transferred: Some(transferred),
+    };
+    let tx = |version: u64, coins: f64| OutboundTx { version, coins };
+
+    let snaps = vec![
+        snap(10, 1, 10.0, 5.0),
+        snap(20, 3, 20.0, 15.0),
+        snap(30, 5, 30.0, 31.0),
+    ];
+    // before the first snapshot, already counted in its transferred amount
+    let txs = vec![tx(5, 5.0), tx(15, 10.0), tx(25, 20.0)];
+
+    let r = analyze_slow_wallet("0xa", "V6", &snaps, &txs, DEFAULT_TOLERANCE).unwrap();
+    assert!(r.snapshots == 3);
+    assert!(r.unlock_per_epoch == Some(5.0));
+    assert!(r.outbound_coins == 30.0);
+    assert!(r.issues.len() == 2);
+    assert!(
+        r.issues[0]
+            == ComplianceIssue::TransferredExceedsUnlocked {
+                version: 30,
+                unlocked: 30.0,
+                transferred: 31.0
+            }
+    );
+    assert!(
+        r.issues[1]
+            == ComplianceIssue::TxExceedsUnlocked {
+                version: 30,
+                unlocked: 30.0,
+                tx_total: 35.0
+            }
+    );
+
+    assert!(analyze_slow_wallet("0xb", "V6", &[], &txs, DEFAULT_TOLERANCE).is_none());
+}
@@ -0,0 +1,236 @@
+//! on disk cache of decoded archives, so retrying a load does not need to decode again
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Read},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use diem_crypto::{hash::DefaultHasher, HashValue};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    schema_account_state::WarehouseAccState, schema_transaction::WarehouseTxMaster,
+    validator_operators::OperatorRelation,
+};
+
+const CACHE_EXTENSION: &str = "cache.gz";
+
+/// the decoders change between releases, so the crate version is part of the key
+const CACHE_SALT: &str = concat!(
+    "libra-forensic-db archive cache ",
+    env!("CARGO_PKG_VERSION")
+);
+
+/// Bump this whenever a decoder, or a type stored in `DecodedArchive`,
+/// changes. Entries written by other versions are never read again, and
+/// are left for `prune` to remove.
+pub const CACHE_SCHEMA_VERSION: u64 = 2;
+
+/// The decoded records of one archive.
+/// NOTE: stored as gzipped bincode. BCS has no floats, which the
+/// snapshot balances are.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DecodedArchive {
+    pub archive_id: String,
+    pub txs: Vec<WarehouseTxMaster>,
+    pub snaps: Vec<WarehouseAccState>,
+    pub operators: Vec<OperatorRelation>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct CacheStats {
+    pub entries: u64,
+    pub total_bytes: u64,
+    /// least recently used entry
+    pub oldest: Option<DateTime<Utc>>,
+    /// most recently used entry
+    pub newest: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct PruneResult {
+    pub removed: u64,
+    pub freed_bytes: u64,
+}
+
+/// A directory of decoded archives, keyed by the hash of the archive files
+#[derive(Debug, Clone)]
+pub struct ArchiveCache {
+    pub dir: PathBuf,
+}
+
+impl ArchiveCache {
+    pub fn new(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir).context(format!("could not create cache dir {}", dir.display()))?;
+        Ok(Self {
+            dir: dir.to_owned(),
+        })
+    }
+
+    fn entry_path(&self, key: &HashValue) -> PathBuf {
+        self.dir
+            .join(format!("{}.{}", key.to_hex(), CACHE_EXTENSION))
+    }
+
+    /// read an entry, any error reading it is treated as a miss.
+    /// A hit updates the modified time, so that pruning removes the least recently used.
+    pub fn get(&self, key: &HashValue) -> Option<DecodedArchive> {
+        let path = self.entry_path(key);
+        if !path.exists() {
+            return None;
+        }
+
+        let read = || -> Result<DecodedArchive> {
+            let decoder = GzDecoder::new(BufReader::new(File::open(&path)?));
+            Ok(bincode::deserialize_from(decoder)?)
+        };
+
+        match read() {
+            Ok(d) => {
+                if let Ok(f) = File::options().write(true).open(&path) {
+                    let _ = f.set_modified(SystemTime::now());
+                }
+                Some(d)
+            }
+            Err(e) => {
+                warn!(
+                    "removing unreadable cache entry {}, {:#}",
+                    path.display(),
+                    e
+                );
+                let _ = fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    /// write an entry. It is written to a temp file first, so that an
+    /// interrupted write is never read back as a valid entry.
+    pub fn put(&self, key: &HashValue, decoded: &DecodedArchive) -> Result<()> {
+        let path = self.entry_path(key);
+        let tmp_path = path.with_extension("tmp");
+
+        let mut encoder = GzEncoder::new(
+            BufWriter::new(File::create(&tmp_path)?),
+            Compression::default(),
+        );
+        bincode::serialize_into(&mut encoder, decoded)?;
+        encoder.finish()?.into_inner()?.sync_all()?;
+
+        fs::rename(&tmp_path, &path)?;
+        info!("cached decoded archive: {}", decoded.archive_id);
+        Ok(())
+    }
+
+    /// all the entries with their size and modified time, oldest first
+    fn entries(&self) -> Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let mut entries = vec![];
+        for e in fs::read_dir(&self.dir)? {
+            let path = e?.path();
+            let is_entry = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.ends_with(CACHE_EXTENSION))
+                .unwrap_or(false);
+            if !is_entry {
+                continue;
+            }
+            let meta = fs::metadata(&path)?;
+            entries.push((path, meta.len(), meta.modified()?));
+        }
+        entries.sort_by_key(|(_, _, modified)| *modified);
+        Ok(entries)
+    }
+
+    pub fn stats(&self) -> Result<CacheStats> {
+        let entries = self.entries()?;
+        Ok(CacheStats {
+            entries: entries.len() as u64,
+            total_bytes: entries.iter().map(|(_, size, _)| size).sum(),
+            oldest: entries.first().map(|(_, _, m)| DateTime::<Utc>::from(*m)),
+            newest: entries.last().map(|(_, _, m)| DateTime::<Utc>::from(*m)),
+        })
+    }
+
+    /// remove entries not used within `max_age`, then remove the least
+    /// recently used entries until the cache is under `max_bytes`.
+    pub fn prune(&self, max_age: Option<Duration>, max_bytes: Option<u64>) -> Result<PruneResult> {
+        let mut res = PruneResult::default();
+        let now = SystemTime::now();
+        let mut remaining: u64 = 0;
+
+        let mut keep = vec![];
+        for (path, size, modified) in self.entries()? {
+            let expired = max_age
+                .map(|age| now.duration_since(modified).unwrap_or_default() > age)
+                .unwrap_or(false);
+            if expired {
+                fs::remove_file(&path)?;
+                res.removed += 1;
+                res.freed_bytes += size;
+            } else {
+                remaining += size;
+                keep.push((path, size));
+            }
+        }
+
+        if let Some(max) = max_bytes {
+            for (path, size) in keep {
+                if remaining <= max {
+                    break;
+                }
+                fs::remove_file(&path)?;
+                remaining -= size;
+                res.removed += 1;
+                res.freed_bytes += size;
+            }
+        }
+
+        info!(
+            "cache entries removed: {}, bytes freed: {}",
+            res.removed, res.freed_bytes
+        );
+        Ok(res)
+    }
+}
+
+/// hash of all the files in an archive directory: names, lengths and contents.
+/// Use the archive as found, before it is decompressed, so that the key does
+/// not depend on how it was decompressed, e.g. the manifest fixes.
+pub fn archive_content_hash(archive_dir: &Path) -> Result<HashValue> {
+    let mut files = vec![];
+    for e in fs::read_dir(archive_dir).context(format!(
+        "could not read archive dir {}",
+        archive_dir.display()
+    ))? {
+        let path = e?.path();
+        if fs::metadata(&path)?.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    let mut hasher = DefaultHasher::new(CACHE_SALT.as_bytes());
+    hasher.update(&CACHE_SCHEMA_VERSION.to_be_bytes());
+    let mut buf = vec![0u8; 64 * 1024];
+    for path in files {
+        let name = path.file_name().context("no file name")?.to_string_lossy();
+        hasher.update(name.as_bytes());
+        hasher.update(&fs::metadata(&path)?.len().to_be_bytes());
+
+        let mut file = File::open(&path)?;
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+    }
+    Ok(hasher.finish())
+}
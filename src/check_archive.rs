@@ -0,0 +1,211 @@
+//! deep verification of archives before loading
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use diem_crypto::hash::CryptoHash;
+use libra_storage::read_tx_chunk::{load_chunk, load_tx_chunk_manifest};
+use log::{error, info};
+use serde::Serialize;
+
+use crate::{
+    extract_snapshot::{extract_current_snapshot, extract_v5_snapshot},
+    scan::{BundleContent, FrameworkVersion, ManifestInfo},
+    unzip_temp,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    /// no deep check is implemented for this content
+    Skipped,
+}
+
+/// machine readable result of a deep check of one archive
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveCheckReport {
+    pub archive_id: String,
+    pub archive_dir: String,
+    pub contents: String,
+    pub framework_version: FrameworkVersion,
+    pub status: CheckStatus,
+    /// number of chunks (transactions) or accounts (snapshots) decoded
+    pub records_checked: u64,
+    pub errors: Vec<String>,
+}
+
+impl ArchiveCheckReport {
+    fn new(man: &ManifestInfo) -> Self {
+        Self {
+            archive_id: man.archive_id.clone(),
+            archive_dir: man.archive_dir.display().to_string(),
+            contents: format!("{:?}", man.contents),
+            framework_version: man.version.clone(),
+            status: CheckStatus::Skipped,
+            records_checked: 0,
+            errors: vec![],
+        }
+    }
+
+    fn finish(&mut self) {
+        self.status = if self.errors.is_empty() {
+            CheckStatus::Pass
+        } else {
+            CheckStatus::Fail
+        };
+    }
+}
+
+/// decode every chunk of an archive and check it is internally consistent.
+/// Errors found in the archive are collected in the report,
+/// instead of returning early.
+pub async fn deep_check_archive(man: &ManifestInfo) -> ArchiveCheckReport {
+    let mut report = ArchiveCheckReport::new(man);
+
+    let res = match unzip_temp::maybe_handle_gz(&man.archive_dir) {
+        Ok((archive_dir, temp)) => {
+            let res = match man.contents {
+                BundleContent::Transaction => check_transaction_archive(&archive_dir).await,
+                BundleContent::StateSnapshot => {
+                    check_snapshot_archive(&archive_dir, &man.version).await
+                }
+                _ => {
+                    info!(
+                        "no deep check for {:?} archive: {}",
+                        man.contents, man.archive_id
+                    );
+                    return report;
+                }
+            };
+            drop(temp);
+            res
+        }
+        Err(e) => Err(e),
+    };
+
+    match res {
+        Ok((count, mut errors)) => {
+            report.records_checked = count;
+            report.errors.append(&mut errors);
+        }
+        Err(e) => report.errors.push(format!("{:#}", e)),
+    }
+    report.finish();
+
+    if report.status == CheckStatus::Fail {
+        error!(
+            "archive failed checks: {}, errors: {}",
+            report.archive_id,
+            report.errors.len()
+        );
+    }
+    report
+}
+
+/// checks the transaction, transaction_info and event vectors are the same length,
+/// the transaction hashes match the TransactionInfo,
+/// and the chunk versions match the manifest.
+/// Returns the count of chunks checked and any inconsistencies found.
+pub async fn check_transaction_archive(archive_path: &Path) -> Result<(u64, Vec<String>)> {
+    let manifest_file = archive_path.join("transaction.manifest");
+    let manifest = load_tx_chunk_manifest(&manifest_file).context(format!(
+        "could not read transaction.manifest at {}",
+        archive_path.display()
+    ))?;
+
+    let mut errors = vec![];
+    let mut chunks_checked = 0u64;
+
+    if let Some(first) = manifest.chunks.first() {
+        if first.first_version != manifest.first_version {
+            errors.push(format!(
+                "first chunk starts at version {}, manifest starts at {}",
+                first.first_version, manifest.first_version
+            ));
+        }
+    }
+    if let Some(last) = manifest.chunks.last() {
+        if last.last_version != manifest.last_version {
+            errors.push(format!(
+                "last chunk ends at version {}, manifest ends at {}",
+                last.last_version, manifest.last_version
+            ));
+        }
+    }
+
+    let mut expected_next: Option<u64> = None;
+    for each_chunk_manifest in manifest.chunks {
+        let first_version = each_chunk_manifest.first_version;
+        let last_version = each_chunk_manifest.last_version;
+        let chunk_name = each_chunk_manifest.transactions.clone();
+
+        if let Some(next) = expected_next {
+            if first_version != next {
+                errors.push(format!(
+                    "chunk {} starts at version {}, expected {}",
+                    chunk_name, first_version, next
+                ));
+            }
+        }
+        expected_next = Some(last_version + 1);
+
+        let chunk = match load_chunk(archive_path, each_chunk_manifest).await {
+            Ok(c) => c,
+            Err(e) => {
+                errors.push(format!("could not decode chunk {}: {:#}", chunk_name, e));
+                continue;
+            }
+        };
+        chunks_checked += 1;
+
+        let expected_len = (last_version + 1).saturating_sub(first_version) as usize;
+        if chunk.txns.len() != expected_len {
+            errors.push(format!(
+                "chunk {} has {} transactions, manifest version range {}-{} expects {}",
+                chunk_name,
+                chunk.txns.len(),
+                first_version,
+                last_version,
+                expected_len
+            ));
+        }
+
+        if chunk.txns.len() != chunk.txn_infos.len() || chunk.txns.len() != chunk.event_vecs.len() {
+            errors.push(format!(
+                "chunk {} vectors are not the same length, transactions: {}, txn_infos: {}, events: {}",
+                chunk_name,
+                chunk.txns.len(),
+                chunk.txn_infos.len(),
+                chunk.event_vecs.len()
+            ));
+        }
+
+        for (i, (tx, info)) in chunk.txns.iter().zip(chunk.txn_infos.iter()).enumerate() {
+            if tx.hash() != info.transaction_hash() {
+                errors.push(format!(
+                    "chunk {} transaction hash mismatch at version {}",
+                    chunk_name,
+                    first_version + i as u64
+                ));
+            }
+        }
+    }
+
+    Ok((chunks_checked, errors))
+}
+
+/// decodes all the account states in a snapshot
+pub async fn check_snapshot_archive(
+    archive_path: &Path,
+    framework_version: &FrameworkVersion,
+) -> Result<(u64, Vec<String>)> {
+    let snaps = match framework_version {
+        FrameworkVersion::V5 => extract_v5_snapshot(archive_path).await?,
+        FrameworkVersion::Unknown => {
+            return Ok((0, vec!["no framework version detected".to_string()]))
+        }
+        _ => extract_current_snapshot(archive_path).await?,
+    };
+
+    Ok((snaps.len() as u64, vec![]))
+}
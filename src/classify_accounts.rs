@@ -0,0 +1,276 @@
+//! label accounts by type, with the version range during which each type applied
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{Context, Result};
+use diem_types::account_address::AccountAddress;
+use log::info;
+use neo4rs::Graph;
+use serde::{Deserialize, Serialize};
+
+use crate::util::de_address_from_any_string;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum AccountClass {
+    SlowWallet,
+    DonorVoice,
+    Validator,
+    Operator,
+    Miner,
+    ExchangeWallet,
+}
+
+impl AccountClass {
+    /// the classes derived from the chain records, exchange wallets are configured
+    pub fn from_chain() -> [Self; 5] {
+        [
+            Self::SlowWallet,
+            Self::DonorVoice,
+            Self::Validator,
+            Self::Operator,
+            Self::Miner,
+        ]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::SlowWallet => "SlowWallet",
+            Self::DonorVoice => "DonorVoice",
+            Self::Validator => "Validator",
+            Self::Operator => "Operator",
+            Self::Miner => "Miner",
+            Self::ExchangeWallet => "ExchangeWallet",
+        }
+    }
+
+    /// prefix of the version range properties on the Account
+    pub fn property_prefix(&self) -> &'static str {
+        match self {
+            Self::SlowWallet => "slow_wallet",
+            Self::DonorVoice => "donor_voice",
+            Self::Validator => "validator",
+            Self::Operator => "operator",
+            Self::Miner => "miner",
+            Self::ExchangeWallet => "exchange_wallet",
+        }
+    }
+
+    /// the records which show the class applied to `addr` at `version`
+    /// of `framework_version`. Miner proofs were only submitted in V5.
+    /// The records from which the loaders set the DonorVoice and Validator
+    /// labels are evidence here too, so classifying does not drop them.
+    fn evidence_cypher(&self) -> &'static str {
+        match self {
+            Self::SlowWallet => {
+                r#"
+  MATCH (addr:Account)-[:State]->(s:Snapshot)
+  WHERE s.slow_wallet = true
+  RETURN addr, s.version AS version, s.framework_version AS framework_version
+"#
+            }
+            Self::DonorVoice => {
+                r#"
+  MATCH (addr:Account)-[:State]->(s:Snapshot)
+  WHERE s.donor_voice = true
+  RETURN addr, s.version AS version, s.framework_version AS framework_version
+  UNION ALL
+  MATCH (addr:Account)
+  WHERE addr.donor_voice_version IS NOT NULL
+  RETURN addr, addr.donor_voice_version AS version, addr.donor_voice_framework_version AS framework_version
+"#
+            }
+            Self::Validator => {
+                r#"
+  MATCH (addr:Account)-[:ValidatorState]->(vs:ValidatorState)
+  RETURN addr, vs.version AS version, vs.framework_version AS framework_version
+  UNION ALL
+  MATCH ()-[t:Tx]->(addr:Account)
+  WHERE t.function CONTAINS 'create_validator_account' OR t.function CONTAINS 'create_acc_val'
+  RETURN addr, t.version AS version, t.framework_version AS framework_version
+  UNION ALL
+  MATCH ()-[rel:Operates]->(addr:Account)
+  RETURN addr, rel.first_version AS version, rel.framework_version AS framework_version
+"#
+            }
+            Self::Operator => {
+                r#"
+  MATCH (vs:ValidatorState)-[:Operator]->(addr:Account)
+  RETURN addr, vs.version AS version, vs.framework_version AS framework_version
+  UNION ALL
+  MATCH ()-[t:Tx]->(addr:Account)
+  WHERE t.function CONTAINS 'create_validator_operator_account'
+  RETURN addr, t.version AS version, t.framework_version AS framework_version
+  UNION ALL
+  MATCH (addr:Account)-[rel:Operates]->()
+  RETURN addr, rel.first_version AS version, rel.framework_version AS framework_version
+"#
+            }
+            Self::Miner => {
+                r#"
+  MATCH (addr:Account)-[:State]->(s:Snapshot)
+  WHERE s.miner_height IS NOT NULL
+  RETURN addr, s.version AS version, s.framework_version AS framework_version
+  UNION ALL
+  MATCH (addr:Account)-[:Mined]->(me:MinerEpoch)
+  RETURN addr, me.first_version AS version, "V5" AS framework_version
+"#
+            }
+            Self::ExchangeWallet => {
+                r#"
+  MATCH (addr:Account)
+  WHERE addr.exchange IS NOT NULL
+  OPTIONAL MATCH (addr)-[t:Tx]-()
+  RETURN addr, t.version AS version, t.framework_version AS framework_version
+"#
+            }
+        }
+    }
+
+    /// Versions restart after V5, so the range is a pair of
+    /// (framework_version, version) at each end, ordered by era first.
+    /// The label is removed first from the accounts labeled by a previous
+    /// run, so that those without evidence any more are not kept.
+    pub fn cypher_classify_str(&self) -> String {
+        let evidence = self.evidence_cypher();
+        let label = self.label();
+        let prefix = self.property_prefix();
+        format!(
+            r#"
+CALL {{
+  MATCH (prev:{label})
+  REMOVE
+    prev:{label},
+    prev.{prefix}_from_framework_version,
+    prev.{prefix}_from_version,
+    prev.{prefix}_to_framework_version,
+    prev.{prefix}_to_version
+}}
+CALL {{{evidence}}}
+WITH addr, version, framework_version,
+  CASE framework_version
+    WHEN "V5" THEN 0
+    WHEN "V6" THEN 1
+    WHEN "V7" THEN 2
+    ELSE 3
+  END AS era
+ORDER BY era ASC, version ASC
+// collect drops the NULL, for accounts without a dated record
+WITH addr, collect(
+  CASE WHEN version IS NULL THEN NULL
+  ELSE {{framework_version: framework_version, version: version}} END
+) AS seen
+WITH addr, seen[0] AS first, seen[size(seen) - 1] AS last
+SET
+  addr:{label},
+  addr.{prefix}_from_framework_version = first.framework_version,
+  addr.{prefix}_from_version = first.version,
+  addr.{prefix}_to_framework_version = last.framework_version,
+  addr.{prefix}_to_version = last.version
+RETURN COUNT(addr) AS labeled_accounts
+"#
+        )
+    }
+}
+
+/// an address controlled by an exchange, from a configuration file e.g.
+/// [{"address": "0x...", "exchange": "some exchange"}]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExchangeWallet {
+    #[serde(deserialize_with = "de_address_from_any_string")]
+    pub address: Option<AccountAddress>,
+    pub exchange: String,
+}
+
+impl ExchangeWallet {
+    pub fn parse_json_file(path: &Path) -> Result<Vec<Self>> {
+        let s = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&s)?)
+    }
+
+    /// create a cypher query string for the map object
+    pub fn to_cypher_map(list: &[Self]) -> String {
+        let mut list_literal = "".to_owned();
+        for el in list {
+            // skip empty records
+            let Some(addr) = &el.address else {
+                continue;
+            };
+            list_literal.push_str(&format!(
+                r#"{{address: "{}", exchange: {}}}"#,
+                addr.to_hex_literal(),
+                // json string escaping is valid in cypher
+                serde_json::to_string(&el.exchange).unwrap_or_default(),
+            ));
+            list_literal.push(',');
+        }
+        list_literal.pop(); // need to drop last comma ","
+        format!("[{}]", list_literal)
+    }
+
+    /// Only accounts already in the graph are marked. The marks of a
+    /// previous configuration are removed.
+    pub fn cypher_mark_exchange_str(list_str: &str) -> String {
+        format!(
+            r#"
+CALL {{
+  MATCH (prev:Account)
+  WHERE prev.exchange IS NOT NULL
+  REMOVE prev.exchange
+}}
+WITH {list_str} AS wallets
+UNWIND wallets AS w
+MATCH (addr:Account {{address: w.address}})
+SET addr.exchange = w.exchange
+RETURN COUNT(addr) AS marked_accounts
+"#
+        )
+    }
+}
+
+async fn run_count_query(pool: &Graph, cypher_string: &str, field: &str) -> Result<u64> {
+    let cypher_query = neo4rs::query(cypher_string);
+    let mut res = pool
+        .execute(cypher_query)
+        .await
+        .context("execute query error")?;
+
+    let row = res.next().await?.context("no row returned")?;
+    row.get(field).context(format!("no {} field", field))
+}
+
+/// label the accounts of one class. Running it again updates the version
+/// ranges, and unlabels the accounts without evidence any more.
+/// Returns the count of accounts labeled.
+pub async fn classify(pool: &Graph, class: AccountClass) -> Result<u64> {
+    let labeled = run_count_query(pool, &class.cypher_classify_str(), "labeled_accounts").await?;
+    info!("{} accounts labeled: {}", class.label(), labeled);
+    Ok(labeled)
+}
+
+/// mark the configured exchange wallets, then label all the classes.
+/// Returns the count of accounts labeled in each class.
+pub async fn classify_all(
+    pool: &Graph,
+    exchange_wallets: &[ExchangeWallet],
+) -> Result<BTreeMap<AccountClass, u64>> {
+    let mut results = BTreeMap::new();
+    for class in AccountClass::from_chain() {
+        results.insert(class, classify(pool, class).await?);
+    }
+
+    if !exchange_wallets.is_empty() {
+        let list_str = ExchangeWallet::to_cypher_map(exchange_wallets);
+        let marked = run_count_query(
+            pool,
+            &ExchangeWallet::cypher_mark_exchange_str(&list_str),
+            "marked_accounts",
+        )
+        .await?;
+        info!("exchange wallets found in the graph: {}", marked);
+    }
+    results.insert(
+        AccountClass::ExchangeWallet,
+        classify(pool, AccountClass::ExchangeWallet).await?,
+    );
+
+    Ok(results)
+}
@@ -0,0 +1,213 @@
+//! find gaps, overlaps and duplicates in the version ranges of scanned archives
+use anyhow::Result;
+use log::warn;
+use neo4rs::Graph;
+use serde::Serialize;
+
+use crate::{
+    queue,
+    scan::{ArchiveMap, BundleContent, FrameworkVersion},
+};
+
+/// the range an archive covers.
+/// For epoch ending archives the range is in epochs, otherwise in versions.
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveRange {
+    pub archive_id: String,
+    /// versions restart at the V5 fork, so ranges only compare within one framework version
+    pub framework_version: FrameworkVersion,
+    pub first: u64,
+    pub last: u64,
+    /// whether all batches are completed in the queue, if it was checked
+    pub loaded: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum CoverageIssue {
+    /// no archive covers first..=last
+    Gap { first: u64, last: u64 },
+    /// two archives share some of first..=last
+    Overlap {
+        archive_a: String,
+        archive_b: String,
+        first: u64,
+        last: u64,
+    },
+    /// two archives cover exactly the same range
+    Duplicate {
+        archive_a: String,
+        archive_b: String,
+        first: u64,
+        last: u64,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CoverageReport {
+    pub contents: String,
+    pub ranges: Vec<ArchiveRange>,
+    pub issues: Vec<CoverageIssue>,
+    /// archives where the manifest range could not be read
+    pub unknown_range: Vec<String>,
+}
+
+/// sort the ranges and find the issues between neighbors
+pub fn find_range_issues(ranges: &mut [ArchiveRange]) -> Vec<CoverageIssue> {
+    ranges.sort_by_key(|r| (r.first, r.last));
+
+    let mut issues = vec![];
+    // the archive which reaches the highest so far
+    let mut furthest: Option<&ArchiveRange> = None;
+
+    for r in ranges.iter() {
+        if let Some(prev) = furthest {
+            if r.first == prev.first && r.last == prev.last {
+                issues.push(CoverageIssue::Duplicate {
+                    archive_a: prev.archive_id.clone(),
+                    archive_b: r.archive_id.clone(),
+                    first: r.first,
+                    last: r.last,
+                });
+            } else if r.first <= prev.last {
+                issues.push(CoverageIssue::Overlap {
+                    archive_a: prev.archive_id.clone(),
+                    archive_b: r.archive_id.clone(),
+                    first: r.first,
+                    last: r.last.min(prev.last),
+                });
+            } else if r.first > prev.last + 1 {
+                issues.push(CoverageIssue::Gap {
+                    first: prev.last + 1,
+                    last: r.first - 1,
+                });
+            }
+
+            if r.last <= prev.last {
+                continue;
+            }
+        }
+        furthest = Some(r);
+    }
+    issues
+}
+
+/// build a coverage report for one type of content in the archive map.
+/// Transactions are checked for contiguous versions, epoch endings for
+/// contiguous epochs, and snapshots only for duplicates.
+/// Ranges of different framework versions are checked separately.
+pub fn coverage_report(map: &ArchiveMap, contents: BundleContent) -> CoverageReport {
+    let mut by_framework: Vec<(FrameworkVersion, Vec<ArchiveRange>)> = vec![];
+    let mut unknown_range = vec![];
+
+    for man in map.0.values().filter(|m| m.contents == contents) {
+        let range = match contents {
+            BundleContent::EpochEnding => man.first_epoch.zip(man.last_epoch),
+            _ => man.first_version.zip(man.last_version),
+        };
+        match range {
+            Some((first, last)) => {
+                let r = ArchiveRange {
+                    archive_id: man.archive_id.clone(),
+                    framework_version: man.version.clone(),
+                    first,
+                    last,
+                    loaded: None,
+                };
+                match by_framework.iter_mut().find(|(fv, _)| fv == &man.version) {
+                    Some((_, group)) => group.push(r),
+                    None => by_framework.push((man.version.clone(), vec![r])),
+                }
+            }
+            None => unknown_range.push(man.archive_id.clone()),
+        }
+    }
+
+    let mut ranges = vec![];
+    let mut issues = vec![];
+    for (_, mut group) in by_framework {
+        issues.append(&mut find_range_issues(&mut group));
+        ranges.append(&mut group);
+    }
+    if contents == BundleContent::StateSnapshot {
+        // snapshots are points in time, they are not expected to be contiguous
+        issues.retain(|i| matches!(i, CoverageIssue::Duplicate { .. }));
+    }
+
+    CoverageReport {
+        contents: format!("{:?}", contents),
+        ranges,
+        issues,
+        unknown_range,
+    }
+}
+
+/// reports for each of the content types found in the archive map
+pub fn coverage_all(map: &ArchiveMap) -> Vec<CoverageReport> {
+    [
+        BundleContent::Transaction,
+        BundleContent::StateSnapshot,
+        BundleContent::EpochEnding,
+    ]
+    .into_iter()
+    .map(|c| coverage_report(map, c))
+    .filter(|r| !r.ranges.is_empty() || !r.unknown_range.is_empty())
+    .collect()
+}
+
+/// check the queue to see which of the archive ranges are already loaded
+pub async fn set_loaded_from_queue(report: &mut CoverageReport, pool: &Graph) -> Result<()> {
+    for r in report.ranges.iter_mut() {
+        match queue::are_all_completed(pool, &r.archive_id).await {
+            Ok(b) => r.loaded = Some(b),
+            Err(e) => warn!("could not check queue for {}, {}", r.archive_id, e),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_find_range_issues() {
+    let range = |id: &str, first: u64, last: u64| ArchiveRange {
+        archive_id: id.to_string(),
+        framework_version: FrameworkVersion::V7,
+        first,
+        last,
+        loaded: None,
+    };
+
+    let mut ranges = vec![
+        range("c", 201, 300),
+        range("a", 1, 100),
+        range("b", 101, 150),
+        // gap 151-200
+        range("d", 250, 350),
+        range("e", 250, 350),
+    ];
+
+    let issues = find_range_issues(&mut ranges);
+    assert!(ranges.first().unwrap().archive_id == "a");
+    assert!(issues.len() == 3);
+    assert!(
+        issues[0]
+            == CoverageIssue::Gap {
+                first: 151,
+                last: 200
+            }
+    );
+    assert!(matches!(
+        issues[1],
+        CoverageIssue::Overlap {
+            first: 250,
+            last: 300,
+            ..
+        }
+    ));
+    assert!(matches!(
+        issues[2],
+        CoverageIssue::Duplicate {
+            first: 250,
+            last: 350,
+            ..
+        }
+    ));
+}
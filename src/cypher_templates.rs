@@ -20,6 +20,7 @@ ON MATCH SET rel.cypher_modified_at = timestamp()
 SET
     rel.block_datetime = tx.block_datetime,
     rel.block_timestamp = tx.block_timestamp,
+    rel.version = tx.version,
     rel.relation = tx.relation,
     rel.function = tx.function,
     rel.framework_version = tx.framework_version
@@ -1,6 +1,10 @@
-use crate::schema_transaction::{EntryFunctionArgs, RelationLabel, UserEventTypes, WarehouseEvent};
+use crate::{
+    scan::FrameworkVersion,
+    schema_transaction::{EntryFunctionArgs, RelationLabel, UserEventTypes, WarehouseEvent},
+    validator_operators::OperatorRelation,
+};
 use anyhow::bail;
-use diem_types::transaction::SignedTransaction;
+use diem_types::{account_address::AccountAddress, transaction::SignedTransaction};
 use libra_backwards_compatibility::sdk::{
     v6_libra_framework_sdk_builder::EntryFunctionCall as V6EntryFunctionCall,
     v7_libra_framework_sdk_builder::EntryFunctionCall as V7EntryFunctionCall,
@@ -104,6 +108,98 @@ fn maybe_get_v6_relation(
     Some((args, relation))
 }
 
+/// The operator and validator of the validator registration and
+/// configuration entry functions, for V6 and V7.
+/// Archives of both V6 and V7 are labeled V7 (see scan), so for those the V7
+/// builder is tried first, then the V6 builder.
+pub fn maybe_operator_relation(
+    user_tx: &SignedTransaction,
+    version: u64,
+    framework_version: &FrameworkVersion,
+) -> Option<OperatorRelation> {
+    let relation = |validator: AccountAddress, function: &str| OperatorRelation {
+        operator: user_tx.sender(),
+        validator,
+        version,
+        function: function.to_owned(),
+        framework_version: framework_version.clone(),
+        operator_name: None,
+        validator_name: None,
+        network_addresses: None,
+        fullnode_addresses: None,
+    };
+
+    match framework_version {
+        FrameworkVersion::V7 => maybe_v7_operator_relation(user_tx, &relation)
+            .or_else(|| maybe_v6_operator_relation(user_tx, &relation)),
+        FrameworkVersion::V6 => maybe_v6_operator_relation(user_tx, &relation),
+        // V5 archives have no entry functions, see json_rescue_v5_extract
+        _ => None,
+    }
+}
+
+fn maybe_v7_operator_relation(
+    user_tx: &SignedTransaction,
+    relation: &dyn Fn(AccountAddress, &str) -> OperatorRelation,
+) -> Option<OperatorRelation> {
+    match V7EntryFunctionCall::decode(user_tx.payload()) {
+        // the validator is its own operator
+        Some(V7EntryFunctionCall::ValidatorUniverseRegisterValidator {
+            network_addresses,
+            fullnode_addresses,
+            ..
+        }) => Some(OperatorRelation {
+            network_addresses: Some(hex::encode(network_addresses)),
+            fullnode_addresses: Some(hex::encode(fullnode_addresses)),
+            ..relation(user_tx.sender(), "validator_universe::register_validator")
+        }),
+        Some(V7EntryFunctionCall::StakeUpdateNetworkAndFullnodeAddresses {
+            validator_address,
+            new_network_addresses,
+            new_fullnode_addresses,
+        }) => Some(OperatorRelation {
+            network_addresses: Some(hex::encode(new_network_addresses)),
+            fullnode_addresses: Some(hex::encode(new_fullnode_addresses)),
+            ..relation(
+                validator_address,
+                "stake::update_network_and_fullnode_addresses",
+            )
+        }),
+        _ => None,
+    }
+}
+
+fn maybe_v6_operator_relation(
+    user_tx: &SignedTransaction,
+    relation: &dyn Fn(AccountAddress, &str) -> OperatorRelation,
+) -> Option<OperatorRelation> {
+    match V6EntryFunctionCall::decode(user_tx.payload()) {
+        // the validator is its own operator
+        Some(V6EntryFunctionCall::ValidatorUniverseRegisterValidator {
+            network_addresses,
+            fullnode_addresses,
+            ..
+        }) => Some(OperatorRelation {
+            network_addresses: Some(hex::encode(network_addresses)),
+            fullnode_addresses: Some(hex::encode(fullnode_addresses)),
+            ..relation(user_tx.sender(), "validator_universe::register_validator")
+        }),
+        Some(V6EntryFunctionCall::StakeUpdateNetworkAndFullnodeAddresses {
+            validator_address,
+            new_network_addresses,
+            new_fullnode_addresses,
+        }) => Some(OperatorRelation {
+            network_addresses: Some(hex::encode(new_network_addresses)),
+            fullnode_addresses: Some(hex::encode(new_fullnode_addresses)),
+            ..relation(
+                validator_address,
+                "stake::update_network_and_fullnode_addresses",
+            )
+        }),
+        _ => None,
+    }
+}
+
 fn is_onboarding_event(events: &[WarehouseEvent]) -> bool {
     let withdraw = events.iter().any(|e| {
         if let UserEventTypes::Withdraw(_) = e.event {
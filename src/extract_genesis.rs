@@ -0,0 +1,121 @@
+//! account state at the origin of the chain, from the V6 hard fork recovery file
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::Deserialize;
+
+use crate::{
+    scan::FrameworkVersion,
+    schema_account_state::{ValidatorState, WarehouseAccState, WarehouseTime},
+    util::{parse_address_any_string, COIN_DECIMAL_PRECISION, LEGACY_REBASE_MULTIPLIER},
+};
+
+/// The fields of a legacy recovery record needed for the genesis state.
+/// The V6 chain was started from a JSON export of the V5 final state,
+/// as a list of these records.
+/// NOTE: genesis.blob write sets are not decoded, the recovery file has the same
+/// accounts in a format which does not depend on the framework version.
+#[derive(Debug, Deserialize)]
+pub struct LegacyRecoveryRecord {
+    pub account: Option<String>,
+    pub role: Option<RecoveryRole>,
+    pub balance: Option<RecoveryBalance>,
+    pub slow_wallet: Option<RecoverySlowWallet>,
+    pub miner_state: Option<RecoveryTowerState>,
+    pub cumulative_deposits: Option<serde_json::Value>,
+}
+
+/// the V5 account role
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum RecoveryRole {
+    System,
+    Validator,
+    Operator,
+    EndUser,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecoveryBalance {
+    pub coin: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecoverySlowWallet {
+    pub unlocked: u64,
+    pub transferred: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecoveryTowerState {
+    pub verified_tower_height: u64,
+}
+
+/// V5 coin values are rebased to V6 units at genesis.
+/// In f64, since the rebased units of large balances overflow a u64.
+fn rebased(coins: u64) -> f64 {
+    coins as f64 * LEGACY_REBASE_MULTIPLIER as f64 / COIN_DECIMAL_PRECISION as f64
+}
+
+/// read the recovery file into account states at version 0.
+/// Records without an account (e.g. global resources) are skipped.
+/// Validators get a ValidatorState, which labels them at genesis. Only the
+/// role is known, the other fields are defaults.
+pub fn extract_genesis_recovery(recovery_json: &Path) -> Result<Vec<WarehouseAccState>> {
+    let json = std::fs::read_to_string(recovery_json).context(format!(
+        "could not read recovery file {}",
+        recovery_json.display()
+    ))?;
+    let records: Vec<LegacyRecoveryRecord> =
+        serde_json::from_str(&json).context("could not parse recovery file")?;
+
+    let time = WarehouseTime {
+        framework_version: FrameworkVersion::V6,
+        timestamp: 0,
+        version: 0,
+        epoch: 0,
+    };
+
+    let mut warehouse_state = vec![];
+    for r in records {
+        let Some(address_str) = &r.account else {
+            continue;
+        };
+        let address = match parse_address_any_string(address_str) {
+            Ok(a) => a,
+            Err(e) => {
+                warn!("could not parse recovery address {}, {}", address_str, e);
+                continue;
+            }
+        };
+
+        let mut s = WarehouseAccState::new(address);
+        s.time = time.clone();
+
+        if let Some(b) = &r.balance {
+            s.balance = rebased(b.coin);
+        }
+        if let Some(sw) = &r.slow_wallet {
+            s.slow_wallet_acc = true;
+            s.slow_wallet_unlocked = Some(rebased(sw.unlocked));
+            s.slow_wallet_transferred = Some(rebased(sw.transferred));
+        }
+        if let Some(tower) = &r.miner_state {
+            s.miner_height = Some(tower.verified_tower_height);
+        }
+        // Infer if it is a donor voice account
+        if r.cumulative_deposits.is_some() {
+            s.donor_voice_acc = true;
+        }
+        if r.role == Some(RecoveryRole::Validator) {
+            s.validator = Some(ValidatorState::default());
+        }
+
+        warehouse_state.push(s);
+    }
+
+    info!("genesis accounts parsed: {}", warehouse_state.len());
+    Ok(warehouse_state)
+}
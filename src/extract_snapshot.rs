@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::{fmt::Display, path::Path};
 
 use anyhow::Result;
 use diem_types::account_view::AccountView;
@@ -12,15 +12,24 @@ use libra_storage::read_snapshot::{accounts_from_snapshot_backup, load_snapshot_
 use libra_types::{
     exports::AccountAddress,
     move_resource::{
-        cumulative_deposits::CumulativeDepositResource, libra_coin::LibraCoinStoreResource,
-        wallet::SlowWalletResource,
+        ancestry::AncestryResource, cumulative_deposits::CumulativeDepositResource,
+        libra_coin::LibraCoinStoreResource, wallet::SlowWalletResource,
     },
 };
 use log::{error, info, warn};
 
 use crate::{
+    move_resources::{
+        GivenVouchesResource, JailResource, MultiActionGovernanceResource, MyVouchesResource,
+        ProofOfFeeAuctionResource, ReceivedVouchesResource, StakePoolResource,
+        TimedTransferResource, TxScheduleResource, ValidatorConfigResource,
+        ValidatorUniverseResource,
+    },
     scan::FrameworkVersion,
-    schema_account_state::{WarehouseAccState, WarehouseTime},
+    schema_account_state::{
+        DonorVoicePayment, DonorVoiceState, PaymentStatus, ValidatorState, VouchRecord,
+        WarehouseAccState, WarehouseTime,
+    },
     util::COIN_DECIMAL_PRECISION,
 };
 
@@ -106,6 +115,7 @@ pub async fn extract_current_snapshot(archive_path: &Path) -> Result<Vec<Warehou
 
     // TODO: stream this
     let mut warehouse_state = vec![];
+    let mut universe: Vec<AccountAddress> = vec![];
     for el in accs.iter() {
         if let Some(address) = el.get_account_address()? {
             let mut s = WarehouseAccState::new(address);
@@ -127,15 +137,94 @@ pub async fn extract_current_snapshot(archive_path: &Path) -> Result<Vec<Warehou
                     Some(sw.transferred as f64 / COIN_DECIMAL_PRECISION as f64);
             }
 
-            // Infer if it is a donor voice account
-            if let Some(_sw) = el.get_resource::<CumulativeDepositResource>()? {
+            if let Some(a) = el.get_resource::<AncestryResource>()? {
+                s.ancestry = a.tree;
+            }
+
+            if let Some(v) =
+                skip_undecodable(&address, el.get_resource::<ReceivedVouchesResource>())
+            {
+                s.vouches_received = VouchRecord::from_lists(&v.incoming_vouches, &v.epoch_vouched);
+            } else if let Some(v) =
+                skip_undecodable(&address, el.get_resource::<MyVouchesResource>())
+            {
+                s.vouches_received = VouchRecord::from_lists(&v.my_buddies, &v.epoch_vouched);
+            }
+
+            if let Some(v) = skip_undecodable(&address, el.get_resource::<GivenVouchesResource>()) {
+                s.vouches_given = VouchRecord::from_lists(&v.outgoing_vouches, &v.epoch_vouched);
+            }
+
+            // donor voice accounts track deposits, and have a payment schedule
+            let deposits = el.get_resource::<CumulativeDepositResource>()?;
+            let schedule = skip_undecodable(&address, el.get_resource::<TxScheduleResource>());
+            if deposits.is_some() || schedule.is_some() {
                 s.donor_voice_acc = true;
+                let mut dv = DonorVoiceState::default();
+                if let Some(g) =
+                    skip_undecodable(&address, el.get_resource::<MultiActionGovernanceResource>())
+                {
+                    dv.authorities = g.signers;
+                    dv.threshold = g.cfg_default_n_sigs;
+                }
+                if let Some(d) = deposits {
+                    dv.depositors = d.depositors;
+                }
+                if let Some(sch) = schedule {
+                    dv.payments = donor_voice_payments(&sch);
+                }
+                s.donor_voice = Some(dv);
+            }
+
+            let config = skip_undecodable(&address, el.get_resource::<ValidatorConfigResource>());
+            let auction =
+                skip_undecodable(&address, el.get_resource::<ProofOfFeeAuctionResource>());
+            let jail = skip_undecodable(&address, el.get_resource::<JailResource>());
+            if config.is_some() || auction.is_some() || jail.is_some() {
+                let mut v = ValidatorState::default();
+                if let Some(c) = config {
+                    v.validator_index = Some(c.validator_index);
+                }
+                if let Some(p) = skip_undecodable(&address, el.get_resource::<StakePoolResource>())
+                {
+                    v.operator = Some(p.operator_address);
+                }
+                if let Some(a) = auction {
+                    v.bid = Some(a.bid);
+                    v.bid_expiration_epoch = Some(a.epoch_expiration);
+                }
+                if let Some(j) = jail {
+                    v.jailed = j.is_jailed;
+                    v.lifetime_jailed = j.lifetime_jailed;
+                    v.consecutive_failure_to_rejoin = j.consecutive_failure_to_rejoin;
+                }
+                s.validator = Some(v);
+            }
+
+            if address == AccountAddress::ONE {
+                if let Some(u) =
+                    skip_undecodable(&address, el.get_resource::<ValidatorUniverseResource>())
+                {
+                    universe = u.validators;
+                }
             }
 
             warehouse_state.push(s);
         }
     }
 
+    // the universe is only known after reading 0x1
+    for s in warehouse_state.iter_mut() {
+        if universe.contains(&s.address) {
+            s.validator
+                .get_or_insert_with(ValidatorState::default)
+                .in_universe = true;
+        }
+        if let Some(v) = s.validator.as_mut() {
+            v.set_vouch_eligibility(&s.vouches_received, s.time.epoch);
+        }
+    }
+
     info!(
         "SUCCESS: accounts parsed. # accounts: {}",
         &warehouse_state.len()
@@ -147,3 +236,47 @@ pub async fn extract_current_snapshot(archive_path: &Path) -> Result<Vec<Warehou
 
     Ok(warehouse_state)
 }
+
+/// The resources defined in this crate may not match the layout of every
+/// framework release. Those which cannot be decoded are skipped, instead of
+/// failing the whole snapshot.
+fn skip_undecodable<T, E: Display>(
+    address: &AccountAddress,
+    res: std::result::Result<Option<T>, E>,
+) -> Option<T> {
+    res.unwrap_or_else(|e| {
+        warn!(
+            "could not decode {} resource of {}: {}",
+            std::any::type_name::<T>(),
+            address.to_hex_literal(),
+            e
+        );
+        None
+    })
+}
+
+fn donor_voice_payments(schedule: &TxScheduleResource) -> Vec<DonorVoicePayment> {
+    let to_payment = |t: &TimedTransferResource, status: PaymentStatus| DonorVoicePayment {
+        uid: t.uid.creation_num,
+        payee: t.tx.payee,
+        value: t.tx.value as f64 / COIN_DECIMAL_PRECISION as f64,
+        description: String::from_utf8_lossy(&t.tx.description).to_string(),
+        deadline: t.deadline,
+        epoch_latest_veto_received: t.epoch_latest_veto_received,
+        status,
+    };
+
+    let scheduled = schedule
+        .scheduled
+        .iter()
+        .map(|t| to_payment(t, PaymentStatus::Pending));
+    let paid = schedule
+        .paid
+        .iter()
+        .map(|t| to_payment(t, PaymentStatus::Paid));
+    let vetoed = schedule
+        .veto
+        .iter()
+        .map(|t| to_payment(t, PaymentStatus::Vetoed));
+    scheduled.chain(paid).chain(vetoed).collect()
+}
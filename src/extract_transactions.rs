@@ -1,7 +1,9 @@
-use crate::decode_entry_function::decode_entry_function_all_versions;
+use crate::decode_entry_function::{decode_entry_function_all_versions, maybe_operator_relation};
 use crate::scan::FrameworkVersion;
+use crate::schema_block::WarehouseBlock;
 use crate::schema_transaction::{RelationLabel, UserEventTypes, WarehouseEvent, WarehouseTxMaster};
-use anyhow::Result;
+use crate::validator_operators::OperatorRelation;
+use anyhow::{Context, Result};
 use chrono::DateTime;
 use diem_crypto::HashValue;
 use diem_types::account_config::{NewBlockEvent, WithdrawEvent};
@@ -17,6 +19,22 @@ pub async fn extract_current_transactions(
     archive_path: &Path,
     framework_version: &FrameworkVersion,
 ) -> Result<(Vec<WarehouseTxMaster>, Vec<WarehouseEvent>)> {
+    let (user_txs, events, _) =
+        extract_current_transactions_and_operators(archive_path, framework_version).await?;
+    Ok((user_txs, events))
+}
+
+/// Same as `extract_current_transactions`, with the validator registrations
+/// and configurations found in the same pass. These have no recipient, so
+/// are not among the extracted transactions.
+pub async fn extract_current_transactions_and_operators(
+    archive_path: &Path,
+    framework_version: &FrameworkVersion,
+) -> Result<(
+    Vec<WarehouseTxMaster>,
+    Vec<WarehouseEvent>,
+    Vec<OperatorRelation>,
+)> {
     let manifest_file = archive_path.join("transaction.manifest");
     assert!(
         manifest_file.exists(),
@@ -32,8 +50,10 @@ pub async fn extract_current_transactions(
 
     let mut user_txs: Vec<WarehouseTxMaster> = vec![];
     let mut events: Vec<WarehouseEvent> = vec![];
+    let mut operators: Vec<OperatorRelation> = vec![];
 
     for each_chunk_manifest in manifest.chunks {
+        let first_version = each_chunk_manifest.first_version;
         let chunk = load_chunk(archive_path, each_chunk_manifest).await?;
 
         for (i, tx) in chunk.txns.iter().enumerate() {
@@ -62,23 +82,30 @@ pub async fn extract_current_transactions(
                 timestamp = block.timestamp_usecs();
             }
 
-            let tx_info = chunk
-                .txn_infos
-                .get(i)
-                .expect("could not index on tx_info chunk, vectors may not be same length");
+            let tx_info = chunk.txn_infos.get(i).context(
+                "could not index on tx_info chunk, vectors may not be same length, try `check --deep`",
+            )?;
             let tx_hash_info = tx_info.transaction_hash();
 
-            let tx_events = chunk
-                .event_vecs
-                .get(i)
-                .expect("could not index on events chunk, vectors may not be same length");
+            let tx_events = chunk.event_vecs.get(i).context(
+                "could not index on events chunk, vectors may not be same length, try `check --deep`",
+            )?;
 
             let mut decoded_events = decode_events(tx_hash_info, tx_events)?;
             events.append(&mut decoded_events);
 
             if let Some(signed_transaction) = tx.try_as_signed_user_txn() {
+                if let Some(r) = maybe_operator_relation(
+                    signed_transaction,
+                    first_version + i as u64,
+                    framework_version,
+                ) {
+                    operators.push(r);
+                }
+
                 let tx = make_master_tx(
                     signed_transaction,
+                    first_version + i as u64,
                     epoch,
                     round,
                     timestamp,
@@ -103,12 +130,81 @@ pub async fn extract_current_transactions(
             warn!("some transactions excluded from extraction");
         }
     }
+    info!(
+        "validator operator relations extracted: {}",
+        operators.len()
+    );
 
-    Ok((user_txs, events))
+    Ok((user_txs, events, operators))
+}
+
+/// the blocks of a transaction archive, from the block metadata transactions.
+/// Transactions before the first block metadata belong to a block of the
+/// previous archive, and are not counted.
+pub async fn extract_blocks(
+    archive_path: &Path,
+    framework_version: &FrameworkVersion,
+) -> Result<Vec<WarehouseBlock>> {
+    let manifest_file = archive_path.join("transaction.manifest");
+    let manifest = load_tx_chunk_manifest(&manifest_file)?;
+
+    let mut blocks: Vec<WarehouseBlock> = vec![];
+    // false until the first block, or after a block which could not be read
+    let mut in_block = false;
+    for each_chunk_manifest in manifest.chunks {
+        let first_version = each_chunk_manifest.first_version;
+        let chunk = load_chunk(archive_path, each_chunk_manifest).await?;
+
+        for (i, tx) in chunk.txns.iter().enumerate() {
+            let version = first_version + i as u64;
+
+            if let Some(block) = tx.try_as_block_metadata() {
+                // the height is only found in the event
+                let height = chunk.event_vecs.get(i).and_then(|events| {
+                    events
+                        .iter()
+                        .find_map(|el| NewBlockEvent::try_from_bytes(el.event_data()).ok())
+                        .map(|e| e.height())
+                });
+                let Some(height) = height else {
+                    warn!("no NewBlockEvent found for block at version {}", version);
+                    in_block = false;
+                    continue;
+                };
+                in_block = true;
+
+                blocks.push(WarehouseBlock {
+                    height,
+                    epoch: block.epoch(),
+                    round: block.round(),
+                    proposer: block.proposer(),
+                    timestamp: block.timestamp_usecs(),
+                    block_datetime: DateTime::from_timestamp_micros(block.timestamp_usecs() as i64)
+                        .context("block timestamp out of range")?,
+                    version,
+                    last_version: version,
+                    user_txs: 0,
+                    failed_proposer_indices: block.failed_proposer_indices().clone(),
+                    framework_version: framework_version.clone(),
+                });
+                continue;
+            }
+
+            if let Some(b) = blocks.last_mut().filter(|_| in_block) {
+                b.last_version = version;
+                if tx.try_as_signed_user_txn().is_some() {
+                    b.user_txs += 1;
+                }
+            }
+        }
+    }
+    info!("blocks extracted: {}", blocks.len());
+    Ok(blocks)
 }
 
 pub fn make_master_tx(
     user_tx: &SignedTransaction,
+    version: u64,
     epoch: u64,
     round: u64,
     block_timestamp: u64,
@@ -137,6 +233,7 @@ pub fn make_master_tx(
         tx_hash,
         expiration_timestamp: user_tx.expiration_timestamp_secs(),
         sender: user_tx.sender(),
+        version,
         epoch,
         round,
         block_timestamp,
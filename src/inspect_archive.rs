@@ -0,0 +1,197 @@
+//! decode archives offline and dump the warehouse records as NDJSON
+use std::{io::Write, path::Path};
+
+use anyhow::{bail, Result};
+use diem_types::account_address::AccountAddress;
+use log::info;
+use serde::Serialize;
+
+use crate::{
+    extract_snapshot::{extract_current_snapshot, extract_v5_snapshot},
+    extract_transactions::extract_current_transactions,
+    json_rescue_v5_extract::{
+        extract_v5_json_rescue, extract_v5_json_rescue_from_str, for_each_json_in_tgz,
+    },
+    scan::{BundleContent, FrameworkVersion, ManifestInfo},
+    schema_account_state::WarehouseAccState,
+    schema_transaction::{WarehouseEvent, WarehouseTxMaster},
+    unzip_temp,
+};
+
+/// Which records to keep when inspecting an archive.
+/// All filters are optional, and are combined with AND.
+#[derive(Debug, Clone, Default)]
+pub struct InspectFilter {
+    /// sender or recipient of a transaction, or owner of an account state
+    pub address: Option<AccountAddress>,
+    /// substring of the transaction function name e.g. `ol_account::transfer`
+    pub function: Option<String>,
+    /// lowest ledger version (inclusive)
+    pub start_version: Option<u64>,
+    /// highest ledger version (inclusive)
+    pub end_version: Option<u64>,
+}
+
+impl InspectFilter {
+    pub fn is_empty(&self) -> bool {
+        self.address.is_none()
+            && self.function.is_none()
+            && self.start_version.is_none()
+            && self.end_version.is_none()
+    }
+
+    fn version_in_range(&self, version: u64) -> bool {
+        if let Some(start) = self.start_version {
+            if version < start {
+                return false;
+            }
+        }
+        if let Some(end) = self.end_version {
+            if version > end {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn tx_matches(&self, tx: &WarehouseTxMaster) -> bool {
+        if let Some(addr) = &self.address {
+            if tx.sender != *addr && tx.relation_label.get_recipient() != Some(*addr) {
+                return false;
+            }
+        }
+        if let Some(f) = &self.function {
+            if !tx.function.contains(f.as_str()) {
+                return false;
+            }
+        }
+        self.version_in_range(tx.version)
+    }
+
+    pub fn acc_state_matches(&self, state: &WarehouseAccState) -> bool {
+        if let Some(addr) = &self.address {
+            if state.address != *addr {
+                return false;
+            }
+        }
+        // account states have no function, so a function filter excludes all of them
+        if self.function.is_some() {
+            return false;
+        }
+        self.version_in_range(state.time.version)
+    }
+}
+
+/// One line of NDJSON output, tagged with the record type
+#[derive(Serialize)]
+#[serde(tag = "record")]
+pub enum InspectRecord<'a> {
+    Tx(&'a WarehouseTxMaster),
+    Event(&'a WarehouseEvent),
+    AccState(&'a WarehouseAccState),
+}
+
+/// write the records which pass the filter as NDJSON.
+/// When any filter is set, events are only written if they belong to a
+/// transaction which passed the filter.
+/// Returns the count of lines written.
+pub fn write_ndjson<W: Write>(
+    txs: &[WarehouseTxMaster],
+    events: &[WarehouseEvent],
+    snaps: &[WarehouseAccState],
+    filter: &InspectFilter,
+    writer: &mut W,
+) -> Result<u64> {
+    let mut count = 0u64;
+    let mut kept_hashes = vec![];
+
+    for tx in txs.iter().filter(|t| filter.tx_matches(t)) {
+        kept_hashes.push(tx.tx_hash);
+        serde_json::to_writer(&mut *writer, &InspectRecord::Tx(tx))?;
+        writer.write_all(b"\n")?;
+        count += 1;
+    }
+
+    for ev in events
+        .iter()
+        .filter(|e| filter.is_empty() || kept_hashes.contains(&e.tx_hash))
+    {
+        serde_json::to_writer(&mut *writer, &InspectRecord::Event(ev))?;
+        writer.write_all(b"\n")?;
+        count += 1;
+    }
+
+    for s in snaps.iter().filter(|s| filter.acc_state_matches(s)) {
+        serde_json::to_writer(&mut *writer, &InspectRecord::AccState(s))?;
+        writer.write_all(b"\n")?;
+        count += 1;
+    }
+
+    writer.flush()?;
+    Ok(count)
+}
+
+/// Decode an archive without a database, and stream the records to `writer`.
+/// The path can be an archive directory with a .manifest (optionally gzipped),
+/// or a V5 json rescue `.json` or `.tgz` file.
+pub async fn inspect_archive<W: Write>(
+    archive_path: &Path,
+    filter: &InspectFilter,
+    writer: &mut W,
+) -> Result<u64> {
+    let extension = archive_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+
+    if archive_path.is_file() {
+        return match extension {
+            "json" => {
+                let (txs, events, _) = extract_v5_json_rescue(archive_path)?;
+                write_ndjson(&txs, &events, &[], filter, writer)
+            }
+            "tgz" => {
+                let mut count = 0;
+                for_each_json_in_tgz(archive_path, |_, json| {
+                    let (txs, events, _) = extract_v5_json_rescue_from_str(&json)?;
+                    count += write_ndjson(&txs, &events, &[], filter, writer)?;
+                    Ok(())
+                })?;
+                Ok(count)
+            }
+            _ => bail!(
+                "cannot inspect file {}, expected a .json or .tgz",
+                archive_path.display()
+            ),
+        };
+    }
+
+    let (archive_dir, temp) = unzip_temp::maybe_handle_gz(archive_path)?;
+    let mut man = ManifestInfo::new(&archive_dir);
+    man.set_info()?;
+    info!("inspecting {:?} archive: {}", man.contents, man.archive_id);
+
+    let count = match man.contents {
+        BundleContent::Transaction => {
+            let (txs, events) =
+                extract_current_transactions(&man.archive_dir, &man.version).await?;
+            write_ndjson(&txs, &events, &[], filter, writer)?
+        }
+        BundleContent::StateSnapshot => {
+            let snaps = match man.version {
+                FrameworkVersion::V5 => extract_v5_snapshot(&man.archive_dir).await?,
+                FrameworkVersion::Unknown => bail!("no framework version detected"),
+                _ => extract_current_snapshot(&man.archive_dir).await?,
+            };
+            write_ndjson(&[], &[], &snaps, filter, writer)?
+        }
+        _ => bail!(
+            "cannot inspect {:?} archive at {}",
+            man.contents,
+            archive_dir.display()
+        ),
+    };
+    drop(temp);
+
+    Ok(count)
+}
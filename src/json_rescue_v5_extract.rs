@@ -3,6 +3,9 @@ use crate::{
     schema_transaction::{EntryFunctionArgs, RelationLabel, WarehouseEvent, WarehouseTxMaster},
     unzip_temp::decompress_tar_archive,
     util::{COIN_DECIMAL_PRECISION, LEGACY_REBASE_MULTIPLIER},
+    v5_autopay::{AutopayDisabled, AutopayInstruction, AutopayType},
+    v5_miner_history::{EpochBoundary, MinerProofSegment},
+    validator_operators::{OperatorRelation, ValidatorHumanName, ValidatorRole},
 };
 use chrono::DateTime;
 use diem_crypto::HashValue;
@@ -14,15 +17,22 @@ use libra_backwards_compatibility::{
     version_five::{
         legacy_address_v5::LegacyAddressV5,
         transaction_type_v5::{TransactionPayload, TransactionV5},
-        transaction_view_v5::{ScriptView, TransactionDataView, TransactionViewV5},
+        transaction_view_v5::{EventDataView, ScriptView, TransactionDataView, TransactionViewV5},
     },
 };
 
 use anyhow::{anyhow, Context, Result};
 use diem_temppath::TempPath;
 use diem_types::account_address::AccountAddress;
+use flate2::read::GzDecoder;
 use log::trace;
-use std::path::{Path, PathBuf};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+use tar::Archive;
 
 /// The canonical transaction archives for V5 were kept in a different format as in v6 and v7.
 /// As of Nov 2024, there's a project to recover the V5 transaction archives to be in the same bytecode flat file format as v6 and v7.
@@ -32,8 +42,19 @@ pub fn extract_v5_json_rescue(
 ) -> Result<(Vec<WarehouseTxMaster>, Vec<WarehouseEvent>, Vec<String>)> {
     let json = std::fs::read_to_string(one_json_file).context("could not read file")?;
 
-    let txs: Vec<TransactionViewV5> = serde_json::from_str(&json)
-        .map_err(|e| anyhow!("could not parse JSON to TransactionViewV5, {:?}", e))?;
+    extract_v5_json_rescue_from_str(&json)
+}
+
+pub fn parse_transaction_views_v5(json: &str) -> Result<Vec<TransactionViewV5>> {
+    serde_json::from_str(json)
+        .map_err(|e| anyhow!("could not parse JSON to TransactionViewV5, {:?}", e))
+}
+
+/// same as `extract_v5_json_rescue` for json already in memory
+pub fn extract_v5_json_rescue_from_str(
+    json: &str,
+) -> Result<(Vec<WarehouseTxMaster>, Vec<WarehouseEvent>, Vec<String>)> {
+    let txs = parse_transaction_views_v5(json)?;
 
     decode_transaction_dataview_v5(&txs)
 }
@@ -52,6 +73,7 @@ pub fn decode_transaction_dataview_v5(
         };
 
         let timestamp = t.timestamp_usecs.unwrap_or(0);
+        wtxs.version = t.version;
         if let TransactionDataView::UserTransaction { sender, script, .. } = &t.transaction {
             wtxs.sender = cast_legacy_account(sender)?;
 
@@ -87,6 +109,312 @@ pub fn decode_transaction_dataview_v5(
     Ok((tx_vec, event_vec, unique_functions))
 }
 
+/// Count the miner proofs of one .json file, and find its epoch boundaries.
+/// The proofs are counted per miner and sender, in segments split at the
+/// newepoch events, so each segment falls within one epoch.
+/// The proof payloads are dropped.
+pub fn extract_v5_miner_proofs_from_str(
+    json: &str,
+) -> Result<(Vec<MinerProofSegment>, Vec<EpochBoundary>)> {
+    let txs = parse_transaction_views_v5(json)?;
+
+    decode_miner_proofs_v5(&txs)
+}
+
+pub fn decode_miner_proofs_v5(
+    txs: &[TransactionViewV5],
+) -> Result<(Vec<MinerProofSegment>, Vec<EpochBoundary>)> {
+    let mut boundaries = vec![];
+    // keyed by the count of boundaries seen before the proof
+    let mut segments: BTreeMap<(usize, AccountAddress, Option<AccountAddress>), MinerProofSegment> =
+        BTreeMap::new();
+
+    for t in txs {
+        if let TransactionDataView::UserTransaction { sender, script, .. } = &t.transaction {
+            let mut wtxs = WarehouseTxMaster {
+                framework_version: FrameworkVersion::V5,
+                sender: cast_legacy_account(sender)?,
+                function: make_function_name(script),
+                ..Default::default()
+            };
+            decode_entry_function_v5(&mut wtxs, &t.bytes)?;
+
+            if let Some((miner, operator)) = get_miner_and_operator(&wtxs)? {
+                segments
+                    .entry((boundaries.len(), miner, operator))
+                    .and_modify(|s| {
+                        s.last_version = t.version;
+                        s.proofs += 1;
+                    })
+                    .or_insert(MinerProofSegment {
+                        miner,
+                        operator,
+                        first_version: t.version,
+                        last_version: t.version,
+                        proofs: 1,
+                    });
+            }
+        }
+
+        for e in &t.events {
+            if let EventDataView::NewEpoch { epoch } = &e.data {
+                boundaries.push(EpochBoundary {
+                    epoch: *epoch,
+                    version: t.version,
+                });
+            }
+        }
+    }
+    Ok((segments.into_values().collect(), boundaries))
+}
+
+/// The autopay instructions created, and the accounts which disabled autopay.
+/// Only the autopay transactions are decoded.
+pub fn decode_autopay_v5(
+    txs: &[TransactionViewV5],
+) -> Result<(Vec<AutopayInstruction>, Vec<AutopayDisabled>)> {
+    let mut instructions = vec![];
+    let mut disabled = vec![];
+
+    for t in txs {
+        if let TransactionDataView::UserTransaction { sender, script, .. } = &t.transaction {
+            let function = make_function_name(script);
+            if !function.to_lowercase().contains("autopay") {
+                continue;
+            }
+            let mut wtxs = WarehouseTxMaster {
+                framework_version: FrameworkVersion::V5,
+                sender: cast_legacy_account(sender)?,
+                function,
+                ..Default::default()
+            };
+            decode_entry_function_v5(&mut wtxs, &t.bytes)?;
+
+            // the function is decoded with the v5.2.0 builder when the
+            // genesis builder gives no relation label, e.g. for autopay_disable
+            match &wtxs.entry_function {
+                Some(EntryFunctionArgs::V5(
+                    ScriptFunctionCallGenesis::AutopayCreateInstruction {
+                        uid,
+                        in_type,
+                        payee,
+                        end_epoch,
+                        value,
+                    },
+                ))
+                | Some(EntryFunctionArgs::V520(
+                    ScriptFunctionCallV520::AutopayCreateInstruction {
+                        uid,
+                        in_type,
+                        payee,
+                        end_epoch,
+                        value,
+                    },
+                )) => instructions.push(AutopayInstruction {
+                    sender: wtxs.sender,
+                    payee: cast_legacy_account(payee)?,
+                    uid: *uid,
+                    in_type: AutopayType::from_in_type(*in_type),
+                    value: *value,
+                    end_epoch: *end_epoch,
+                    version: t.version,
+                }),
+                Some(EntryFunctionArgs::V5(ScriptFunctionCallGenesis::AutopayDisable {
+                    ..
+                }))
+                | Some(EntryFunctionArgs::V520(ScriptFunctionCallV520::AutopayDisable {
+                    ..
+                })) => disabled.push(AutopayDisabled {
+                    sender: wtxs.sender,
+                    version: t.version,
+                }),
+                _ => {}
+            }
+        }
+    }
+    Ok((instructions, disabled))
+}
+
+/// The validator and operator accounts created, and which operator
+/// configured which validator.
+/// Only the validator administration transactions are decoded.
+pub fn decode_validator_operators_v5(
+    txs: &[TransactionViewV5],
+) -> Result<(Vec<OperatorRelation>, Vec<ValidatorHumanName>)> {
+    let mut relations = vec![];
+    let mut names = vec![];
+
+    for t in txs {
+        if let TransactionDataView::UserTransaction { sender, script, .. } = &t.transaction {
+            let function = make_function_name(script);
+            let f = function.to_lowercase();
+            if !(f.contains("validator") || f.contains("operator") || f.contains("acc_val")) {
+                continue;
+            }
+
+            let sender = cast_legacy_account(sender)?;
+            let t5: TransactionV5 = bcs::from_bytes(&t.bytes)
+                .map_err(|err| anyhow!("could not bcs decode tx_bytes, msg: {:?}", err))?;
+            let TransactionV5::UserTransaction(u) = &t5 else {
+                continue;
+            };
+            let payload = &u.raw_txn.payload;
+
+            let relation = |operator, validator| OperatorRelation {
+                operator,
+                validator,
+                version: t.version,
+                function: function.clone(),
+                framework_version: FrameworkVersion::V5,
+                operator_name: None,
+                validator_name: None,
+                network_addresses: None,
+                fullnode_addresses: None,
+            };
+            let name = |address, human_name: &[u8], role| ValidatorHumanName {
+                address,
+                human_name: String::from_utf8_lossy(human_name).to_string(),
+                role,
+                version: t.version,
+            };
+
+            match ScriptFunctionCallGenesis::decode(payload) {
+                Some(ScriptFunctionCallGenesis::CreateValidatorAccount {
+                    new_account_address,
+                    human_name,
+                    ..
+                }) => names.push(name(
+                    cast_legacy_account(&new_account_address)?,
+                    &human_name,
+                    ValidatorRole::Validator,
+                )),
+                Some(ScriptFunctionCallGenesis::CreateValidatorOperatorAccount {
+                    new_account_address,
+                    human_name,
+                    ..
+                }) => names.push(name(
+                    cast_legacy_account(&new_account_address)?,
+                    &human_name,
+                    ValidatorRole::Operator,
+                )),
+                // sent by the validator
+                Some(ScriptFunctionCallGenesis::SetValidatorOperator {
+                    operator_name,
+                    operator_account,
+                }) => relations.push(OperatorRelation {
+                    operator_name: Some(String::from_utf8_lossy(&operator_name).to_string()),
+                    ..relation(cast_legacy_account(&operator_account)?, sender)
+                }),
+                // sent by the operator
+                Some(ScriptFunctionCallGenesis::RegisterValidatorConfig {
+                    validator_account,
+                    validator_network_addresses,
+                    fullnode_network_addresses,
+                    ..
+                }) => relations.push(OperatorRelation {
+                    network_addresses: Some(hex::encode(validator_network_addresses)),
+                    fullnode_addresses: Some(hex::encode(fullnode_network_addresses)),
+                    ..relation(sender, cast_legacy_account(&validator_account)?)
+                }),
+                Some(ScriptFunctionCallGenesis::CreateAccVal {
+                    challenge,
+                    ow_human_name,
+                    op_address,
+                    op_validator_network_addresses,
+                    op_fullnode_network_addresses,
+                    op_human_name,
+                    ..
+                }) => relations.push(OperatorRelation {
+                    operator_name: Some(String::from_utf8_lossy(&op_human_name).to_string()),
+                    validator_name: Some(String::from_utf8_lossy(&ow_human_name).to_string()),
+                    network_addresses: Some(hex::encode(op_validator_network_addresses)),
+                    fullnode_addresses: Some(hex::encode(op_fullnode_network_addresses)),
+                    ..relation(
+                        cast_legacy_account(&op_address)?,
+                        address_from_challenge(&challenge)?,
+                    )
+                }),
+                _ => match ScriptFunctionCallV520::decode(payload) {
+                    Some(ScriptFunctionCallV520::CreateValidatorAccount {
+                        new_account_address,
+                        human_name,
+                        ..
+                    }) => names.push(name(
+                        cast_legacy_account(&new_account_address)?,
+                        &human_name,
+                        ValidatorRole::Validator,
+                    )),
+                    Some(ScriptFunctionCallV520::CreateValidatorOperatorAccount {
+                        new_account_address,
+                        human_name,
+                        ..
+                    }) => names.push(name(
+                        cast_legacy_account(&new_account_address)?,
+                        &human_name,
+                        ValidatorRole::Operator,
+                    )),
+                    Some(ScriptFunctionCallV520::CreateAccVal {
+                        challenge,
+                        ow_human_name,
+                        op_address,
+                        op_validator_network_addresses,
+                        op_fullnode_network_addresses,
+                        op_human_name,
+                        ..
+                    }) => relations.push(OperatorRelation {
+                        operator_name: Some(String::from_utf8_lossy(&op_human_name).to_string()),
+                        validator_name: Some(String::from_utf8_lossy(&ow_human_name).to_string()),
+                        network_addresses: Some(hex::encode(op_validator_network_addresses)),
+                        fullnode_addresses: Some(hex::encode(op_fullnode_network_addresses)),
+                        ..relation(
+                            cast_legacy_account(&op_address)?,
+                            address_from_challenge(&challenge)?,
+                        )
+                    }),
+                    _ => {}
+                },
+            }
+        }
+    }
+    Ok((relations, names))
+}
+
+/// create_acc_val is sent by the onboarding account. The new validator's
+/// address is the second half of the auth key at the start of the VDF challenge.
+fn address_from_challenge(challenge: &[u8]) -> Result<AccountAddress> {
+    let addr = challenge
+        .get(16..32)
+        .context("challenge too short to contain an auth key")?;
+    Ok(AccountAddress::from_hex_literal(&format!(
+        "0x{}",
+        hex::encode(addr)
+    ))?)
+}
+
+/// The miner of a proof, and the operator if it was sent on the miner's behalf
+fn get_miner_and_operator(
+    wtx: &WarehouseTxMaster,
+) -> Result<Option<(AccountAddress, Option<AccountAddress>)>> {
+    if !matches!(wtx.relation_label, RelationLabel::Miner) {
+        return Ok(None);
+    }
+    let owner = match &wtx.entry_function {
+        Some(EntryFunctionArgs::V5(ScriptFunctionCallGenesis::MinerstateCommitByOperator {
+            owner_address,
+            ..
+        })) => Some(cast_legacy_account(owner_address)?),
+        Some(EntryFunctionArgs::V520(ScriptFunctionCallV520::MinerstateCommitByOperator {
+            owner_address,
+            ..
+        })) => Some(cast_legacy_account(owner_address)?),
+        _ => None,
+    };
+    Ok(Some(match owner {
+        Some(owner) => (owner, Some(wtx.sender)),
+        None => (wtx.sender, None),
+    }))
+}
+
 pub fn decode_entry_function_v5(wtx: &mut WarehouseTxMaster, tx_bytes: &[u8]) -> Result<()> {
     // test we can bcs decode to the transaction object
     let t: TransactionV5 = bcs::from_bytes(tx_bytes).map_err(|err| {
@@ -205,6 +533,9 @@ fn maybe_decode_v520_function(
 
                 wtx.entry_function = Some(EntryFunctionArgs::V520(sf.to_owned()));
             }
+            ScriptFunctionCallV520::AutopayCreateInstruction { .. } => {
+                wtx.relation_label = RelationLabel::Configuration;
+            }
             ScriptFunctionCallV520::CreateAccUser { .. } => {
                 wtx.relation_label = RelationLabel::Onboarding(wtx.sender, 0);
             }
@@ -255,6 +586,37 @@ pub fn decompress_to_temppath(tgz_file: &Path) -> Result<TempPath> {
     Ok(temp_dir)
 }
 
+/// read each .json member of a .tgz archive directly from the gz stream,
+/// without extracting to disk.
+/// The callback gets the member's file name and contents.
+pub fn for_each_json_in_tgz<F>(tgz_file: &Path, mut f: F) -> Result<()>
+where
+    F: FnMut(String, String) -> Result<()>,
+{
+    let decoder = GzDecoder::new(File::open(tgz_file)?);
+    let mut archive = Archive::new(decoder);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_path_buf();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let file_name = path
+            .file_name()
+            .context("no file name in tar entry")?
+            .to_string_lossy()
+            .to_string();
+
+        let mut json = String::new();
+        entry
+            .read_to_string(&mut json)
+            .context(format!("could not read {} from tgz", file_name))?;
+        f(file_name, json)?;
+    }
+    Ok(())
+}
+
 /// gets all json files decompressed from tgz
 pub fn list_all_json_files(search_dir: &Path) -> Result<Vec<PathBuf>> {
     let path = search_dir.canonicalize()?;
@@ -294,6 +656,6 @@ fn make_function_name(script: &ScriptView) -> String {
     )
 }
 
-fn cast_legacy_account(legacy: &LegacyAddressV5) -> Result<AccountAddress> {
+pub(crate) fn cast_legacy_account(legacy: &LegacyAddressV5) -> Result<AccountAddress> {
     Ok(AccountAddress::from_hex_literal(&legacy.to_hex_literal())?)
 }
@@ -1,26 +1,31 @@
 use crate::{
     json_rescue_v5_extract::{
-        decompress_to_temppath, extract_v5_json_rescue, list_all_json_files, list_all_tgz_archives,
+        decode_autopay_v5, decode_transaction_dataview_v5, decode_validator_operators_v5,
+        for_each_json_in_tgz, list_all_tgz_archives, parse_transaction_views_v5,
     },
     load_tx_cypher::tx_batch,
     queue::{self},
+    v5_autopay::impl_batch_autopay_insert,
+    validator_operators::impl_batch_operator_insert,
 };
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 use log::{error, info, trace, warn};
 use neo4rs::Graph;
 use std::sync::Arc;
 use std::{path::Path, thread::available_parallelism};
-use tokio::sync::Semaphore;
+use tokio::sync::{mpsc, Semaphore};
 
 /// How many records to read from the archives before attempting insert
 // static LOAD_QUEUE_SIZE: usize = 1000;
 /// When we attempt insert, the chunks of txs that go in to each query
 static QUERY_BATCH_SIZE: usize = 250;
+/// How many json files from a .tgz can be waiting in memory to be loaded
+static JSON_FILES_IN_MEMORY: usize = 2;
 
-/// from a tgz file decompress all the .json files in archive
-/// and then read into the warehouse record format
+/// from a tgz file stream all the .json files in archive
+/// and then read into the warehouse record format.
+/// The .tgz is read directly from the gz stream, nothing is extracted to disk.
 pub async fn single_thread_decompress_extract(tgz_file: &Path, pool: &Graph) -> Result<u64> {
-    let temppath = decompress_to_temppath(tgz_file)?;
     // for caching the archive
     let tgz_filename = tgz_file
         .file_name()
@@ -28,18 +33,28 @@ pub async fn single_thread_decompress_extract(tgz_file: &Path, pool: &Graph) ->
         .to_str()
         .unwrap();
 
-    let json_vec = list_all_json_files(temppath.path())?;
+    // the tar reader is blocking, so it runs on its own thread and sends
+    // each json file as it is read. A small channel keeps memory bounded.
+    let (sender, mut receiver) = mpsc::channel::<(String, String)>(JSON_FILES_IN_MEMORY);
+    let tgz_path = tgz_file.to_path_buf();
+    let reader = tokio::task::spawn_blocking(move || {
+        for_each_json_in_tgz(&tgz_path, |file_name, json| {
+            sender
+                .blocking_send((file_name, json))
+                .map_err(|_| anyhow!("json receiver closed"))
+        })
+    });
 
     let mut found_count = 0u64;
     let mut created_count = 0u64;
+    let mut autopay_count = 0u64;
 
     let mut unique_functions: Vec<String> = vec![];
 
     // TODO: the queue checks could be async, since many files are read
-    for j in json_vec {
-        let archive_id = j.file_name().unwrap().to_str().unwrap();
+    while let Some((archive_id, json)) = receiver.recv().await {
         // checks for .json cases remaining where we were interrupted mid .tgz archive.
-        let complete = queue::are_all_completed(pool, archive_id).await?;
+        let complete = queue::are_all_completed(pool, &archive_id).await?;
         if complete {
             trace!(
                 "skip parsing {}, this file was loaded successfully",
@@ -48,7 +63,13 @@ pub async fn single_thread_decompress_extract(tgz_file: &Path, pool: &Graph) ->
             continue;
         }
 
-        let (records, _, unique) = extract_v5_json_rescue(&j)?;
+        let views = parse_transaction_views_v5(&json).context(format!(
+            "could not parse {} in {}",
+            archive_id, tgz_filename
+        ))?;
+        let (records, _, unique) = decode_transaction_dataview_v5(&views)?;
+        let (autopay, autopay_disabled) = decode_autopay_v5(&views)?;
+        let (operators, operator_names) = decode_validator_operators_v5(&views)?;
 
         unique.iter().for_each(|f| {
             if !unique_functions.contains(f) {
@@ -56,14 +77,23 @@ pub async fn single_thread_decompress_extract(tgz_file: &Path, pool: &Graph) ->
             }
         });
 
-        let res = tx_batch(&records, pool, QUERY_BATCH_SIZE, archive_id).await?;
+        // before the txs, since those mark the file as complete
+        autopay_count += impl_batch_autopay_insert(pool, &autopay, &autopay_disabled).await?;
+        impl_batch_operator_insert(pool, &operators, &operator_names).await?;
+
+        let res = tx_batch(&records, pool, QUERY_BATCH_SIZE, &archive_id).await?;
         created_count += res.created_tx as u64;
         found_count += records.len() as u64;
-        queue::update_task(pool, tgz_filename, true, 0).await?;
     }
+    reader.await??;
+
+    // all the json files were read, so the whole archive can be skipped next time
+    queue::update_task(pool, tgz_filename, true, 0).await?;
+
     if found_count > 0 && created_count > 0 {
         info!("V5 transactions found: {}", found_count);
         info!("V5 transactions inserted: {}", created_count);
+        info!("V5 autopay instructions merged: {}", autopay_count);
         if found_count != created_count {
             warn!("transactions loaded don't match transactions extracted, perhaps previously loaded?");
         }
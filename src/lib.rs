@@ -1,27 +1,41 @@
 pub mod analytics;
+pub mod archive_cache;
 pub mod batch_tx_type;
+pub mod check_archive;
+pub mod classify_accounts;
+pub mod coverage;
 pub mod cypher_templates;
 pub mod decode_entry_function;
 pub mod enrich_exchange_onboarding;
 pub mod enrich_whitepages;
 pub mod extract_exchange_orders;
+pub mod extract_genesis;
 pub mod extract_snapshot;
 pub mod extract_transactions;
+pub mod inspect_archive;
 pub mod json_rescue_v5_extract;
 pub mod json_rescue_v5_load;
 pub mod load;
 pub mod load_account_state;
+pub mod load_blocks;
 pub mod load_exchange_orders;
 pub mod load_tx_cypher;
+pub mod move_resources;
 pub mod neo4j_init;
 pub mod queue;
 pub mod scan;
 pub mod schema_account_state;
+pub mod schema_block;
 pub mod schema_exchange_orders;
 pub mod schema_transaction;
+pub mod sync_rpc;
 pub mod unzip_temp;
 pub mod util;
+pub mod v5_autopay;
+pub mod v5_miner_history;
 pub mod v5_rpc_to_raw;
+pub mod validator_operators;
+pub mod verify_archive;
 pub mod warehouse_cli;
 
 use std::sync::Once;
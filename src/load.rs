@@ -1,24 +1,41 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
 use crate::{
+    archive_cache::{archive_content_hash, ArchiveCache, DecodedArchive},
     batch_tx_type::BatchTxReturn,
     extract_snapshot::{extract_current_snapshot, extract_v5_snapshot},
-    extract_transactions::extract_current_transactions,
+    extract_transactions::extract_current_transactions_and_operators,
     load_account_state::snapshot_batch,
     load_tx_cypher,
     queue::{self, clear_queue, push_queue_from_archive_map},
-    scan::{ArchiveMap, ManifestInfo},
+    scan::{scan_dir_archive, ArchiveMap, BundleContent, ManifestInfo},
     unzip_temp,
+    validator_operators::impl_batch_operator_insert,
+    verify_archive::{epoch_history_from_dir, verify_archive, EpochHistory},
 };
 
 use anyhow::{bail, Context, Result};
+use diem_crypto::HashValue;
+use diem_types::waypoint::Waypoint;
 use log::{error, info, warn};
 use neo4rs::Graph;
+use tokio::sync::watch;
 
 /// takes all the archives from a map, and tries to load them sequentially
+/// If an epoch history is passed, each archive is verified against it,
+/// and the result is recorded on the queue.
+/// If a cache is passed, decoded archives are reused on retries.
 pub async fn ingest_all(
     archive_map: &ArchiveMap,
     pool: &Graph,
     force_queue: bool,
     batch_size: usize,
+    epoch_history: Option<&EpochHistory>,
+    cache: Option<&ArchiveCache>,
 ) -> Result<()> {
     // clear the queue and enqueue all these jobs
     if force_queue {
@@ -42,6 +59,7 @@ pub async fn ingest_all(
     let pending = queue::get_queued(pool).await?;
     info!("pending archives: {}", pending.len());
 
+    let mut failed_archives = vec![];
     // This manifest may be for a .gz file, we should handle here as well
     for (_p, m) in archive_map.0.iter() {
         println!(
@@ -54,12 +72,49 @@ pub async fn ingest_all(
 
         if !complete {
             info!("checking if we need to decompress");
-            let (new_unzip_path, temp) = unzip_temp::maybe_handle_gz(&m.archive_dir)?;
+            let (new_unzip_path, temp) = match unzip_temp::maybe_handle_gz(&m.archive_dir) {
+                Ok(r) => r,
+                Err(e) => {
+                    error!(
+                        "skipping archive, could not decompress: {}, {:#}",
+                        m.archive_id, e
+                    );
+                    queue::update_task_failed(pool, &m.archive_id, &format!("{:#}", e)).await?;
+                    failed_archives.push(m.archive_id.clone());
+                    continue;
+                }
+            };
             let mut better_man = ManifestInfo::new(&new_unzip_path);
             better_man.set_info()?;
 
-            let batch_tx_return = try_load_one_archive(&better_man, pool, batch_size).await?;
+            // the cache key is of the archive as found, before decompressing
+            let cache_entry = match cache {
+                Some(c) => Some((c, archive_content_hash(&m.archive_dir)?)),
+                None => None,
+            };
+            let batch_tx_return =
+                try_load_one_archive(&better_man, pool, batch_size, cache_entry).await?;
             println!("SUCCESS: {}", batch_tx_return);
+
+            if let Some(history) = epoch_history {
+                let v = verify_archive(&better_man, history).await;
+                queue::update_verification(
+                    pool,
+                    &m.archive_id,
+                    v.verified,
+                    v.verified_by.map(|b| b.label()),
+                    v.ledger_version,
+                )
+                .await?;
+                if let Some(e) = v.error {
+                    warn!("archive loaded but NOT verified: {}, {}", m.archive_id, e);
+                } else if !v.verified {
+                    warn!(
+                        "archive loaded, proofs only rooted in manifest waypoints: {}",
+                        m.archive_id
+                    );
+                }
+            }
             drop(temp);
         } else {
             info!(
@@ -69,19 +124,177 @@ pub async fn ingest_all(
         }
     }
 
+    if !failed_archives.is_empty() {
+        bail!(
+            "archives could not be decompressed, and are marked failed in queue: {:?}",
+            failed_archives
+        );
+    }
+
     Ok(())
 }
 
+/// keep scanning the start path, and load archives as they appear.
+/// New archives are enqueued without resetting the progress of archives
+/// already in the queue, and loaded one at a time with `ingest_all`.
+/// An archive which fails to load is retried on the next scan.
+/// On SIGINT the current archive is finished before returning. A second SIGINT
+/// exits immediately, the unfinished batch stays incomplete in the queue
+/// and is retried on the next run.
+pub async fn ingest_watch(
+    start_path: &Path,
+    archive_content: Option<BundleContent>,
+    pool: &Graph,
+    batch_size: usize,
+    verify: bool,
+    trusted_waypoints: &[Waypoint],
+    cache: Option<&ArchiveCache>,
+    interval: Duration,
+) -> Result<()> {
+    let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            warn!("stopping after the current archive, press ctrl-c again to exit now");
+            let _ = shutdown_tx.send(true);
+        }
+        if tokio::signal::ctrl_c().await.is_ok() {
+            error!("exiting, incomplete batches will be retried on the next run");
+            std::process::exit(130);
+        }
+    });
+
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    let mut epoch_history: Option<EpochHistory> = None;
+
+    loop {
+        let new: Vec<(PathBuf, ManifestInfo)> =
+            match scan_dir_archive(start_path, archive_content.clone()) {
+                Ok(map) => map
+                    .0
+                    .into_iter()
+                    .filter(|(p, _)| !seen.contains(p))
+                    // epoch ending archives are only read for verification
+                    .filter(|(_, m)| {
+                        matches!(
+                            m.contents,
+                            BundleContent::Transaction | BundleContent::StateSnapshot
+                        )
+                    })
+                    .collect(),
+                Err(e) => {
+                    warn!("could not scan {}, {:#}", start_path.display(), e);
+                    vec![]
+                }
+            };
+
+        if !new.is_empty() {
+            info!("new archives found: {}", new.len());
+            // new epoch ending archives may have arrived with the others
+            if verify {
+                match epoch_history_from_dir(start_path, trusted_waypoints) {
+                    Ok(h) => epoch_history = Some(h),
+                    Err(e) => warn!("could not read epoch ending archives, {:#}", e),
+                }
+            }
+        }
+
+        for (p, m) in new {
+            if *shutdown_rx.borrow() {
+                break;
+            }
+            if queue::enqueue_if_new(pool, &m.archive_id).await? {
+                info!("enqueued new archive: {}", m.archive_id);
+            }
+
+            let single = ArchiveMap(BTreeMap::from([(p.clone(), m.clone())]));
+            match ingest_all(
+                &single,
+                pool,
+                false,
+                batch_size,
+                epoch_history.as_ref(),
+                cache,
+            )
+            .await
+            {
+                Ok(()) => {
+                    seen.insert(p);
+                }
+                Err(e) => error!(
+                    "could not load archive, will retry: {}, {:#}",
+                    m.archive_id, e
+                ),
+            }
+        }
+
+        if *shutdown_rx.borrow() {
+            info!("watch stopped, queue is up to date");
+            return Ok(());
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = shutdown_rx.changed() => {}
+        }
+    }
+}
+
+/// load one archive which is already decompressed.
+/// If a cache is passed, with the key of the archive as found, the decoded
+/// records are read from it when the archive files are unchanged, otherwise
+/// they are decoded and cached.
 pub async fn try_load_one_archive(
     man: &ManifestInfo,
     pool: &Graph,
     batch_size: usize,
+    cache: Option<(&ArchiveCache, HashValue)>,
 ) -> Result<BatchTxReturn> {
+    let decoded = match cache {
+        Some((c, key)) => match c.get(&key) {
+            Some(d) => {
+                info!("using cached records for archive: {}", man.archive_id);
+                d
+            }
+            None => {
+                let d = decode_archive(man).await?;
+                if let Err(e) = c.put(&key, &d) {
+                    warn!("could not cache archive: {}, {:#}", man.archive_id, e);
+                }
+                d
+            }
+        },
+        None => decode_archive(man).await?,
+    };
+
     let mut all_results = BatchTxReturn::new();
     match man.contents {
-        crate::scan::BundleContent::Unknown => todo!(),
         crate::scan::BundleContent::StateSnapshot => {
-            let snaps = match man.version {
+            snapshot_batch(&decoded.snaps, pool, batch_size, &man.archive_id).await?;
+        }
+        crate::scan::BundleContent::Transaction => {
+            // before the txs, since those mark the archive as complete
+            impl_batch_operator_insert(pool, &decoded.operators, &[]).await?;
+            let batch_res =
+                load_tx_cypher::tx_batch(&decoded.txs, pool, batch_size, &man.archive_id).await?;
+            all_results.increment(&batch_res);
+        }
+        _ => {}
+    }
+    Ok(all_results)
+}
+
+/// extract the warehouse records from an archive
+pub async fn decode_archive(man: &ManifestInfo) -> Result<DecodedArchive> {
+    let mut decoded = DecodedArchive {
+        archive_id: man.archive_id.clone(),
+        ..Default::default()
+    };
+    match man.contents {
+        crate::scan::BundleContent::Unknown => {
+            bail!("unknown archive contents: {}", man.archive_id)
+        }
+        crate::scan::BundleContent::StateSnapshot => {
+            decoded.snaps = match man.version {
                 crate::scan::FrameworkVersion::Unknown => {
                     error!("no framework version detected");
                     bail!("could not load archive from manifest");
@@ -94,15 +307,16 @@ pub async fn try_load_one_archive(
                     extract_current_snapshot(&man.archive_dir).await?
                 }
             };
-            snapshot_batch(&snaps, pool, batch_size, &man.archive_id).await?;
         }
         crate::scan::BundleContent::Transaction => {
-            let (txs, _) = extract_current_transactions(&man.archive_dir, &man.version).await?;
-            let batch_res =
-                load_tx_cypher::tx_batch(&txs, pool, batch_size, &man.archive_id).await?;
-            all_results.increment(&batch_res);
+            let (txs, _, operators) =
+                extract_current_transactions_and_operators(&man.archive_dir, &man.version).await?;
+            decoded.txs = txs;
+            decoded.operators = operators;
+        }
+        crate::scan::BundleContent::EpochEnding => {
+            bail!("epoch ending archives are not loaded: {}", man.archive_id)
         }
-        crate::scan::BundleContent::EpochEnding => todo!(),
     }
-    Ok(all_results)
+    Ok(decoded)
 }
@@ -43,7 +43,18 @@ pub async fn snapshot_batch(
         }
         info!("...loading to db");
 
-        match impl_batch_snapshot_insert(pool, c).await {
+        // the chains and the state of related resources are updated with the batch, so a failure is retried with it
+        let res = async {
+            let batch = impl_batch_snapshot_insert(pool, c).await?;
+            impl_batch_snapshot_chain(pool, c).await?;
+            impl_batch_ancestry_insert(pool, c).await?;
+            impl_batch_vouch_insert(pool, c).await?;
+            impl_batch_donor_voice_insert(pool, c).await?;
+            impl_batch_validator_insert(pool, c).await?;
+            Ok::<_, anyhow::Error>(batch)
+        }
+        .await;
+        match res {
             Ok(batch) => {
                 all_results.increment(&batch);
                 queue::update_task(pool, archive_id, true, i).await?;
@@ -91,3 +102,140 @@ pub async fn impl_batch_snapshot_insert(
         created_tx: merged_snapshots,
     })
 }
+
+/// link the snapshots of the accounts in the batch with NEXT,
+/// and update the latest balance of each account.
+/// Returns the count of NEXT links.
+pub async fn impl_batch_snapshot_chain(
+    pool: &Graph,
+    batch_snapshots: &[WarehouseAccState],
+) -> Result<u64> {
+    let list_str = WarehouseAccState::to_cypher_address_list(batch_snapshots);
+    let cypher_string = WarehouseAccState::cypher_chain_str(&list_str);
+
+    let cypher_query = neo4rs::query(&cypher_string);
+    let mut res = pool
+        .execute(cypher_query)
+        .await
+        .context("execute query error")?;
+
+    let row = res.next().await?.context("no row returned")?;
+    let linked: u64 = row
+        .get("linked_snapshots")
+        .context("no linked_snapshots field")?;
+
+    info!("linked snapshots: {}", linked);
+    Ok(linked)
+}
+
+/// merge the AncestorOf edges from the ancestry of the accounts in the batch.
+/// Returns the count of edges merged.
+pub async fn impl_batch_ancestry_insert(
+    pool: &Graph,
+    batch_snapshots: &[WarehouseAccState],
+) -> Result<u64> {
+    if batch_snapshots.iter().all(|s| s.ancestry.is_empty()) {
+        return Ok(0);
+    }
+    let list_str = WarehouseAccState::to_cypher_ancestry_map(batch_snapshots);
+    let cypher_string = WarehouseAccState::cypher_ancestry_str(&list_str);
+
+    let cypher_query = neo4rs::query(&cypher_string);
+    let mut res = pool
+        .execute(cypher_query)
+        .await
+        .context("execute query error")?;
+
+    let row = res.next().await?.context("no row returned")?;
+    let merged: u64 = row
+        .get("merged_ancestry")
+        .context("no merged_ancestry field")?;
+
+    info!("merged ancestry: {}", merged);
+    Ok(merged)
+}
+
+/// merge the VouchState edges of the accounts in the batch, for the epoch of each snapshot.
+/// Returns the count of edges merged.
+pub async fn impl_batch_vouch_insert(
+    pool: &Graph,
+    batch_snapshots: &[WarehouseAccState],
+) -> Result<u64> {
+    if batch_snapshots
+        .iter()
+        .all(|s| s.vouches_received.is_empty() && s.vouches_given.is_empty())
+    {
+        return Ok(0);
+    }
+    let list_str = WarehouseAccState::to_cypher_vouch_map(batch_snapshots);
+    let cypher_string = WarehouseAccState::cypher_vouch_str(&list_str);
+
+    let cypher_query = neo4rs::query(&cypher_string);
+    let mut res = pool
+        .execute(cypher_query)
+        .await
+        .context("execute query error")?;
+
+    let row = res.next().await?.context("no row returned")?;
+    let merged: u64 = row
+        .get("merged_vouches")
+        .context("no merged_vouches field")?;
+
+    info!("merged vouches: {}", merged);
+    Ok(merged)
+}
+
+/// set the DonorVoice governance on the accounts in the batch, with
+/// Authority and Depositor edges, and Payment nodes.
+/// Returns the count of donor voice accounts merged.
+pub async fn impl_batch_donor_voice_insert(
+    pool: &Graph,
+    batch_snapshots: &[WarehouseAccState],
+) -> Result<u64> {
+    if batch_snapshots.iter().all(|s| s.donor_voice.is_none()) {
+        return Ok(0);
+    }
+    let list_str = WarehouseAccState::to_cypher_donor_voice_map(batch_snapshots);
+    let cypher_string = WarehouseAccState::cypher_donor_voice_str(&list_str);
+
+    let cypher_query = neo4rs::query(&cypher_string);
+    let mut res = pool
+        .execute(cypher_query)
+        .await
+        .context("execute query error")?;
+
+    let row = res.next().await?.context("no row returned")?;
+    let merged: u64 = row
+        .get("merged_donor_voice")
+        .context("no merged_donor_voice field")?;
+
+    info!("merged donor voice: {}", merged);
+    Ok(merged)
+}
+
+/// merge the ValidatorState of the validators in the batch.
+/// Returns the count of validator states merged.
+pub async fn impl_batch_validator_insert(
+    pool: &Graph,
+    batch_snapshots: &[WarehouseAccState],
+) -> Result<u64> {
+    if batch_snapshots.iter().all(|s| s.validator.is_none()) {
+        return Ok(0);
+    }
+    let list_str = WarehouseAccState::to_cypher_validator_map(batch_snapshots);
+    let cypher_string = WarehouseAccState::cypher_validator_str(&list_str);
+
+    let cypher_query = neo4rs::query(&cypher_string);
+    let mut res = pool
+        .execute(cypher_query)
+        .await
+        .context("execute query error")?;
+
+    let row = res.next().await?.context("no row returned")?;
+    let merged: u64 = row
+        .get("merged_validators")
+        .context("no merged_validators field")?;
+
+    info!("merged validators: {}", merged);
+    Ok(merged)
+}
@@ -0,0 +1,91 @@
+//! The Block model is optional, and loaded separately from the transactions,
+//! since most analyses don't need a node per block.
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use log::{error, info};
+use neo4rs::Graph;
+
+use crate::{
+    extract_transactions::extract_blocks,
+    scan::{scan_dir_archive, BundleContent, FrameworkVersion, ManifestInfo},
+    schema_block::WarehouseBlock,
+    unzip_temp,
+};
+
+/// Returns the count of blocks merged, and of transactions linked to them
+pub async fn impl_batch_block_insert(
+    pool: &Graph,
+    batch_blocks: &[WarehouseBlock],
+) -> Result<(u64, u64)> {
+    let list_str = WarehouseBlock::to_cypher_map(batch_blocks);
+    let cypher_string = WarehouseBlock::cypher_batch_insert_str(&list_str);
+
+    let cypher_query = neo4rs::query(&cypher_string);
+    let mut res = pool
+        .execute(cypher_query)
+        .await
+        .context("execute query error")?;
+
+    let row = res.next().await?.context("no row returned")?;
+    let merged: u64 = row.get("merged_blocks").context("no merged_blocks field")?;
+    let linked: u64 = row.get("linked_txs").context("no linked_txs field")?;
+    Ok((merged, linked))
+}
+
+pub async fn block_batch(
+    blocks: &[WarehouseBlock],
+    pool: &Graph,
+    batch_size: usize,
+) -> Result<(u64, u64)> {
+    let mut merged = 0;
+    let mut linked = 0;
+    for c in blocks.chunks(batch_size) {
+        let (m, l) = impl_batch_block_insert(pool, c).await?;
+        merged += m;
+        linked += l;
+    }
+    Ok((merged, linked))
+}
+
+/// Load the blocks of all the V6 and V7 transaction archives in a directory.
+/// Blocks are merged by height, so this can be run again, e.g. after loading
+/// the snapshots which link the failed proposers.
+pub async fn load_blocks_from_dir(
+    start_path: &Path,
+    pool: &Graph,
+    batch_size: usize,
+) -> Result<u64> {
+    let map = scan_dir_archive(start_path, Some(BundleContent::Transaction))?;
+    info!("transaction archives found: {}", map.0.len());
+
+    let mut merged_count = 0;
+    for m in map.0.values() {
+        let (unzip_path, temp) = match unzip_temp::maybe_handle_gz(&m.archive_dir) {
+            Ok(r) => r,
+            Err(e) => {
+                error!(
+                    "skipping archive, could not decompress: {}, {:#}",
+                    m.archive_id, e
+                );
+                continue;
+            }
+        };
+        let mut man = ManifestInfo::new(&unzip_path);
+        man.set_info()?;
+        if man.version == FrameworkVersion::V5 {
+            info!("skipping V5 archive, no block metadata: {}", m.archive_id);
+            continue;
+        }
+
+        let blocks = extract_blocks(&man.archive_dir, &man.version).await?;
+        let (merged, linked) = block_batch(&blocks, pool, batch_size).await?;
+        info!(
+            "archive: {}, blocks merged: {}, transactions linked: {}",
+            m.archive_id, merged, linked
+        );
+        merged_count += merged;
+        drop(temp);
+    }
+    Ok(merged_count)
+}
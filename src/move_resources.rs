@@ -0,0 +1,227 @@
+//! framework resources decoded from state snapshots, which are not
+//! available in libra-types.
+//! The field order must match the Move struct, since they are decoded with BCS.
+use libra_types::exports::AccountAddress;
+use move_core_types::{
+    ident_str,
+    identifier::IdentStr,
+    language_storage::TypeTag,
+    move_resource::{MoveResource, MoveStructType},
+};
+use serde::{Deserialize, Serialize};
+
+/// epochs after which a vouch is no longer counted, from vouch.move
+pub const VOUCH_EXPIRATION_EPOCHS: u64 = 45;
+
+/// vouches a validator needs to join the proof of fee auction, from globals.move
+pub const VALIDATOR_VOUCH_THRESHOLD: u64 = 2;
+
+/// 0x1::vouch::ReceivedVouches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceivedVouchesResource {
+    pub incoming_vouches: Vec<AccountAddress>,
+    pub epoch_vouched: Vec<u64>,
+}
+
+impl MoveStructType for ReceivedVouchesResource {
+    const MODULE_NAME: &'static IdentStr = ident_str!("vouch");
+    const STRUCT_NAME: &'static IdentStr = ident_str!("ReceivedVouches");
+}
+impl MoveResource for ReceivedVouchesResource {}
+
+/// 0x1::vouch::GivenVouches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GivenVouchesResource {
+    pub outgoing_vouches: Vec<AccountAddress>,
+    pub epoch_vouched: Vec<u64>,
+}
+
+impl MoveStructType for GivenVouchesResource {
+    const MODULE_NAME: &'static IdentStr = ident_str!("vouch");
+    const STRUCT_NAME: &'static IdentStr = ident_str!("GivenVouches");
+}
+impl MoveResource for GivenVouchesResource {}
+
+/// 0x1::vouch::MyVouches, the received vouches before GivenVouches
+/// and ReceivedVouches were split out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MyVouchesResource {
+    pub my_buddies: Vec<AccountAddress>,
+    pub epoch_vouched: Vec<u64>,
+}
+
+impl MoveStructType for MyVouchesResource {
+    const MODULE_NAME: &'static IdentStr = ident_str!("vouch");
+    const STRUCT_NAME: &'static IdentStr = ident_str!("MyVouches");
+}
+impl MoveResource for MyVouchesResource {}
+
+/// 0x1::guid::ID
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuidId {
+    pub creation_num: u64,
+    pub addr: AccountAddress,
+}
+
+/// 0x1::account::GUIDCapability
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuidCapability {
+    pub addr: AccountAddress,
+}
+
+/// 0x1::account::WithdrawCapability
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WithdrawCapability {
+    pub account_address: AccountAddress,
+}
+
+/// 0x1::donor_voice_txs::Payment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DonorVoicePaymentResource {
+    pub payee: AccountAddress,
+    pub value: u64,
+    pub description: Vec<u8>,
+}
+
+/// 0x1::donor_voice_txs::TimedTransfer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimedTransferResource {
+    pub uid: GuidId,
+    /// the epoch the payment is made, unless vetoed
+    pub deadline: u64,
+    pub tx: DonorVoicePaymentResource,
+    pub epoch_latest_veto_received: u64,
+}
+
+/// 0x1::donor_voice_txs::TxSchedule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxScheduleResource {
+    pub scheduled: Vec<TimedTransferResource>,
+    pub veto: Vec<TimedTransferResource>,
+    pub paid: Vec<TimedTransferResource>,
+    pub guid_capability: GuidCapability,
+}
+
+impl MoveStructType for TxScheduleResource {
+    const MODULE_NAME: &'static IdentStr = ident_str!("donor_voice_txs");
+    const STRUCT_NAME: &'static IdentStr = ident_str!("TxSchedule");
+}
+impl MoveResource for TxScheduleResource {}
+
+/// 0x1::multi_action::Governance<0x1::multi_action::PropGovSigners>,
+/// the authorities of a multisig account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiActionGovernanceResource {
+    pub cfg_duration_epochs: u64,
+    /// the number of authorities needed to approve an action
+    pub cfg_default_n_sigs: u64,
+    pub signers: Vec<AccountAddress>,
+    pub withdraw_capability: Option<WithdrawCapability>,
+    pub guid_capability: GuidCapability,
+}
+
+impl MoveStructType for MultiActionGovernanceResource {
+    const MODULE_NAME: &'static IdentStr = ident_str!("multi_action");
+    const STRUCT_NAME: &'static IdentStr = ident_str!("Governance");
+
+    fn type_params() -> Vec<TypeTag> {
+        vec!["0x1::multi_action::PropGovSigners"
+            .parse()
+            .expect("valid type tag")]
+    }
+}
+impl MoveResource for MultiActionGovernanceResource {}
+
+/// 0x1::event::EventHandle, the GUID is a wrapper of the ID
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventHandleResource {
+    pub counter: u64,
+    pub guid: GuidId,
+}
+
+/// 0x1::stake::ValidatorConfig
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidatorConfigResource {
+    pub consensus_pubkey: Vec<u8>,
+    pub network_addresses: Vec<u8>,
+    pub fullnode_addresses: Vec<u8>,
+    pub validator_index: u64,
+}
+
+impl MoveStructType for ValidatorConfigResource {
+    const MODULE_NAME: &'static IdentStr = ident_str!("stake");
+    const STRUCT_NAME: &'static IdentStr = ident_str!("ValidatorConfig");
+}
+impl MoveResource for ValidatorConfigResource {}
+
+/// 0x1::stake::StakePool, the coin fields are 0x1::coin::Coin { value }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StakePoolResource {
+    pub active: u64,
+    pub inactive: u64,
+    pub pending_active: u64,
+    pub pending_inactive: u64,
+    pub locked_until_secs: u64,
+    pub operator_address: AccountAddress,
+    pub delegated_voter: AccountAddress,
+    pub initialize_validator_events: EventHandleResource,
+    pub set_operator_events: EventHandleResource,
+    pub add_stake_events: EventHandleResource,
+    pub reactivate_stake_events: EventHandleResource,
+    pub rotate_consensus_key_events: EventHandleResource,
+    pub update_network_and_fullnode_addresses_events: EventHandleResource,
+    pub increase_lockup_events: EventHandleResource,
+    pub join_validator_set_events: EventHandleResource,
+    pub distribute_rewards_events: EventHandleResource,
+    pub unlock_stake_events: EventHandleResource,
+    pub withdraw_stake_events: EventHandleResource,
+    pub leave_validator_set_events: EventHandleResource,
+}
+
+impl MoveStructType for StakePoolResource {
+    const MODULE_NAME: &'static IdentStr = ident_str!("stake");
+    const STRUCT_NAME: &'static IdentStr = ident_str!("StakePool");
+}
+impl MoveResource for StakePoolResource {}
+
+/// 0x1::validator_universe::ValidatorUniverse, stored at 0x1
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidatorUniverseResource {
+    pub validators: Vec<AccountAddress>,
+}
+
+impl MoveStructType for ValidatorUniverseResource {
+    const MODULE_NAME: &'static IdentStr = ident_str!("validator_universe");
+    const STRUCT_NAME: &'static IdentStr = ident_str!("ValidatorUniverse");
+}
+impl MoveResource for ValidatorUniverseResource {}
+
+/// 0x1::proof_of_fee::ProofOfFeeAuction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofOfFeeAuctionResource {
+    /// thousandths of the epoch reward offered as the entry fee
+    pub bid: u64,
+    pub epoch_expiration: u64,
+    pub last_epoch_retracted: u64,
+}
+
+impl MoveStructType for ProofOfFeeAuctionResource {
+    const MODULE_NAME: &'static IdentStr = ident_str!("proof_of_fee");
+    const STRUCT_NAME: &'static IdentStr = ident_str!("ProofOfFeeAuction");
+}
+impl MoveResource for ProofOfFeeAuctionResource {}
+
+/// 0x1::jail::Jail
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JailResource {
+    pub is_jailed: bool,
+    pub lifetime_jailed: u64,
+    pub lifetime_vouchees_jailed: u64,
+    pub consecutive_failure_to_rejoin: u64,
+}
+
+impl MoveStructType for JailResource {
+    const MODULE_NAME: &'static IdentStr = ident_str!("jail");
+    const STRUCT_NAME: &'static IdentStr = ident_str!("Jail");
+}
+impl MoveResource for JailResource {}
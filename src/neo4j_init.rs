@@ -23,6 +23,9 @@ pub static INDEX_HEX_ADDR: &str =
 pub static INDEX_TX_TIMESTAMP: &str =
     "CREATE INDEX tx_timestamp IF NOT EXISTS FOR ()-[r:Tx]-() ON (r.block_datetime)";
 
+pub static INDEX_TX_VERSION: &str =
+    "CREATE INDEX tx_version IF NOT EXISTS FOR ()-[r:Tx]-() ON (r.version)";
+
 pub static INDEX_TX_HASH: &str =
     "CREATE INDEX tx_function IF NOT EXISTS FOR ()-[r:Tx]-() ON (r.tx_hash)";
 
@@ -54,6 +57,14 @@ pub static INDEX_LIFETIME: &str = "
     ";
 
 pub static INDEX_SNAPSHOT: &str = "CREATE INDEX snapshot_account_id IF NOT EXISTS FOR (n:Snapshot) ON (n.address, n.epoch, n.version)";
+pub static INDEX_SNAPSHOT_ADDRESS: &str =
+    "CREATE INDEX snapshot_address IF NOT EXISTS FOR (n:Snapshot) ON (n.address)";
+pub static INDEX_VALIDATOR_STATE: &str = "CREATE INDEX validator_state_id IF NOT EXISTS FOR (n:ValidatorState) ON (n.address, n.epoch, n.version, n.framework_version)";
+pub static INDEX_BLOCK_HEIGHT: &str =
+    "CREATE INDEX block_height IF NOT EXISTS FOR (n:Block) ON (n.height)";
+pub static INDEX_MINER_EPOCH: &str =
+    "CREATE INDEX miner_epoch_id IF NOT EXISTS FOR (n:MinerEpoch) ON (n.address, n.epoch)";
+
 /// get the testing neo4j connection
 pub async fn get_neo4j_localhost_pool(port: u16) -> Result<Graph> {
     let uri = format!("127.0.0.1:{port}");
@@ -83,6 +94,7 @@ pub async fn maybe_create_indexes(graph: &Graph) -> Result<()> {
         TX_CONSTRAINT,
         INDEX_HEX_ADDR,
         INDEX_TX_TIMESTAMP,
+        INDEX_TX_VERSION,
         INDEX_TX_HASH,
         INDEX_TX_AMOUNT,
         INDEX_TX_FRAMEWORK,
@@ -92,6 +104,10 @@ pub async fn maybe_create_indexes(graph: &Graph) -> Result<()> {
         INDEX_EXCHANGE_LINK_LEDGER,
         INDEX_LIFETIME,
         INDEX_SNAPSHOT,
+        INDEX_SNAPSHOT_ADDRESS,
+        INDEX_VALIDATOR_STATE,
+        INDEX_MINER_EPOCH,
+        INDEX_BLOCK_HEIGHT,
     ])
     .await?;
     txn.commit().await?;
@@ -10,13 +10,21 @@ pub async fn update_task(
     completed: bool,
     batch: usize,
 ) -> Result<String> {
+    // a completed retry clears the failure flag set by update_task_failed
+    let clear_failed = if completed {
+        "REMOVE a.failed, a.failed_reason"
+    } else {
+        ""
+    };
     let cypher_string = format!(
         r#"MERGE (a:Queue {{ archive_id: "{}", batch: {} }})
         SET a.completed = {}
+        {}
         RETURN a.archive_id AS archive_id"#,
         archive_id,
         batch,
         completed.to_string().to_lowercase(),
+        clear_failed,
     );
 
     let cypher_query = neo4rs::query(&cypher_string);
@@ -31,6 +39,30 @@ pub async fn update_task(
     Ok(task_id)
 }
 
+/// flag an archive which could not be processed, e.g. a corrupt chunk.
+/// The archive stays incomplete, so it will be retried.
+pub async fn update_task_failed(pool: &Graph, archive_id: &str, reason: &str) -> Result<String> {
+    let cypher_string = format!(
+        r#"MERGE (a:Queue {{ archive_id: "{}", batch: 0 }})
+        SET a.completed = false,
+            a.failed = true,
+            a.failed_reason = $reason
+        RETURN a.archive_id AS archive_id"#,
+        archive_id,
+    );
+
+    let cypher_query = neo4rs::query(&cypher_string).param("reason", reason);
+
+    let mut res = pool
+        .execute(cypher_query)
+        .await
+        .context("execute query error")?;
+
+    let row = res.next().await?.context("no row returned")?;
+    let task_id: String = row.get("archive_id").context("no archive_id field")?;
+    Ok(task_id)
+}
+
 pub async fn get_queued(pool: &Graph) -> Result<Vec<String>> {
     let cypher_string = r#"
       MATCH (a:Queue)
@@ -132,6 +164,79 @@ pub async fn clear_queue(pool: &Graph) -> Result<()> {
     Ok(())
 }
 
+/// record the outcome of the cryptographic verification on all the queue
+/// records of an archive.
+/// `verified_by` tells results rooted in a trusted waypoint from those
+/// only rooted in the archives' own manifests.
+pub async fn update_verification(
+    pool: &Graph,
+    archive_id: &str,
+    verified: bool,
+    verified_by: Option<&str>,
+    ledger_version: Option<u64>,
+) -> Result<u64> {
+    let ledger_version_literal = match ledger_version {
+        Some(v) => v.to_string(),
+        None => "NULL".to_string(),
+    };
+    let verified_by_literal = match verified_by {
+        Some(b) => format!("'{}'", b),
+        None => "NULL".to_string(),
+    };
+    let cypher_string = format!(
+        r#"
+        MATCH (a:Queue {{archive_id: '{}' }})
+        SET a.verified = {},
+            a.verified_by = {},
+            a.verified_ledger_version = {},
+            a.verified_at = timestamp()
+        RETURN COUNT(a) AS updated
+      "#,
+        archive_id,
+        verified.to_string().to_lowercase(),
+        verified_by_literal,
+        ledger_version_literal,
+    );
+
+    let cypher_query = neo4rs::query(&cypher_string);
+
+    let mut res = pool
+        .execute(cypher_query)
+        .await
+        .context("execute query error")?;
+
+    let row = res.next().await?.context("no row returned")?;
+    let updated: u64 = row.get("updated").context("no updated field")?;
+    Ok(updated)
+}
+
+/// enqueue an archive only if it has no queue records yet, so the progress
+/// of an archive seen before is not reset.
+/// Returns true if the archive was added.
+pub async fn enqueue_if_new(pool: &Graph, archive_id: &str) -> Result<bool> {
+    let cypher_string = format!(
+        r#"
+        OPTIONAL MATCH (existing:Queue {{archive_id: "{archive_id}" }})
+        WITH COUNT(existing) = 0 AS is_new
+        FOREACH (_ IN CASE WHEN is_new THEN [1] ELSE [] END |
+            CREATE (:Queue {{archive_id: "{archive_id}", batch: 0, completed: false }})
+        )
+        RETURN is_new
+      "#,
+    );
+
+    let cypher_query = neo4rs::query(&cypher_string);
+
+    let mut res = pool
+        .execute(cypher_query)
+        .await
+        .context("execute query error")?;
+
+    let row = res.next().await?.context("no row returned")?;
+    let is_new: bool = row.get("is_new").context("no is_new field")?;
+    Ok(is_new)
+}
+
 pub async fn push_queue_from_archive_map(map: &ArchiveMap, pool: &Graph) -> Result<()> {
     for (_, a) in map.0.iter() {
         // set at least one batch of each archive_id to false, so it gets picked up in the queue
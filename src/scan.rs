@@ -2,16 +2,22 @@
 #![allow(dead_code)]
 
 use anyhow::{Context, Result};
+use diem_types::{ledger_info::LedgerInfoWithSignatures, proof::TransactionAccumulatorRangeProof};
+use flate2::read::GzDecoder;
 use glob::glob;
 use libra_backwards_compatibility::version_five::{
     state_snapshot_v5::v5_read_from_snapshot_manifest,
     transaction_manifest_v5::v5_read_from_transaction_manifest,
 };
 use libra_storage::read_snapshot::load_snapshot_manifest;
+use log::warn;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::{
     collections::BTreeMap,
     fmt,
+    fs::File,
+    io::{BufReader, Read},
     path::{Path, PathBuf},
 };
 #[derive(Clone, Debug)]
@@ -30,6 +36,14 @@ pub struct ManifestInfo {
     pub contents: BundleContent,
     /// processed
     pub processed: bool,
+    /// first ledger version covered. For snapshots this is the snapshot version.
+    pub first_version: Option<u64>,
+    /// last ledger version covered
+    pub last_version: Option<u64>,
+    /// first epoch covered, if the manifest records it
+    pub first_epoch: Option<u64>,
+    /// last epoch covered, if the manifest records it
+    pub last_epoch: Option<u64>,
 }
 
 impl ManifestInfo {
@@ -46,12 +60,60 @@ impl ManifestInfo {
             version: FrameworkVersion::Unknown,
             contents: BundleContent::Unknown,
             processed: false,
+            first_version: None,
+            last_version: None,
+            first_epoch: None,
+            last_epoch: None,
         }
     }
 
     pub fn set_info(&mut self) -> Result<()> {
         self.set_contents()?;
         self.try_set_framework_version();
+        if let Err(e) = self.set_ranges() {
+            warn!(
+                "could not read version range from manifest at {}, {}",
+                self.archive_dir.display(),
+                e
+            );
+        }
+        Ok(())
+    }
+
+    /// read the first and last version and epoch from the manifest.
+    /// The manifest may be gzipped.
+    pub fn set_ranges(&mut self) -> Result<()> {
+        let manifest = match self.contents {
+            BundleContent::Unknown => return Ok(()),
+            _ => read_manifest_json(&self.archive_dir.join(self.contents.filename()))?,
+        };
+
+        match self.contents {
+            BundleContent::Transaction => {
+                self.first_version = manifest["first_version"].as_u64();
+                self.last_version = manifest["last_version"].as_u64();
+            }
+            BundleContent::StateSnapshot => {
+                self.first_version = manifest["version"].as_u64();
+                self.last_version = self.first_version;
+                // v5 snapshot manifests do not include an epoch
+                self.first_epoch = manifest["epoch"].as_u64();
+                self.last_epoch = self.first_epoch;
+            }
+            BundleContent::EpochEnding => {
+                self.first_epoch = manifest["first_epoch"].as_u64();
+                self.last_epoch = manifest["last_epoch"].as_u64();
+                // waypoints are formatted as "version:hash"
+                let waypoint_version = |w: Option<&Value>| -> Option<u64> {
+                    w?.as_str()?.split(':').next()?.parse().ok()
+                };
+                if let Some(waypoints) = manifest["waypoints"].as_array() {
+                    self.first_version = waypoint_version(waypoints.first());
+                    self.last_version = waypoint_version(waypoints.last());
+                }
+            }
+            BundleContent::Unknown => {}
+        }
         Ok(())
     }
 
@@ -86,8 +148,10 @@ impl ManifestInfo {
                 }
             }
             BundleContent::Transaction => {
-                // TODO: v5 manifests appear to have the same format this is a noop
-                if v5_read_from_transaction_manifest(&self.archive_dir).is_ok() {
+                // v5 manifests have the same format, so the proof is read instead
+                if tx_proof_is_current(&self.archive_dir) {
+                    self.version = FrameworkVersion::V7;
+                } else if v5_read_from_transaction_manifest(&self.archive_dir).is_ok() {
                     self.version = FrameworkVersion::V5;
                 }
             }
@@ -97,7 +161,7 @@ impl ManifestInfo {
         FrameworkVersion::Unknown
     }
 }
-#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
 pub enum FrameworkVersion {
     #[default]
     Unknown,
@@ -143,6 +207,80 @@ impl BundleContent {
     }
 }
 
+/// read a manifest file as json, if the file is not found try the .gz
+pub fn read_manifest_json(manifest_path: &Path) -> Result<Value> {
+    if manifest_path.exists() {
+        let s = std::fs::read_to_string(manifest_path)?;
+        return Ok(serde_json::from_str(&s)?);
+    }
+    let gz_path = manifest_path.with_file_name(format!(
+        "{}.gz",
+        manifest_path
+            .file_name()
+            .context("no manifest file name")?
+            .to_string_lossy()
+    ));
+    let decoder = GzDecoder::new(
+        File::open(&gz_path)
+            .context(format!("no manifest found at {}", manifest_path.display()))?,
+    );
+    Ok(serde_json::from_reader(decoder)?)
+}
+
+/// True if the ledger info in the proof of the first chunk decodes with the
+/// current types, which the V5 ledger info does not.
+/// V6 and V7 archives have the same format, and versions continue across
+/// the upgrade, so both are labeled V7, as the state snapshots are.
+fn tx_proof_is_current(archive_dir: &Path) -> bool {
+    let read_proof = || -> Result<Vec<u8>> {
+        let manifest =
+            read_manifest_json(&archive_dir.join(BundleContent::Transaction.filename()))?;
+        let proof = manifest["chunks"][0]["proof"]
+            .as_str()
+            .context("no proof in transaction manifest")?;
+        // the proof path is relative to the parent of the archive
+        let mut proof_path = archive_dir
+            .parent()
+            .context("archive has no parent directory")?
+            .join(proof);
+        // manifests may name the file before or after it was gzipped
+        if !proof_path.exists() {
+            let name = proof_path
+                .file_name()
+                .context("no proof file name")?
+                .to_string_lossy()
+                .to_string();
+            proof_path = match name.strip_suffix(".gz") {
+                Some(unzipped) => proof_path.with_file_name(unzipped),
+                None => proof_path.with_file_name(format!("{}.gz", name)),
+            };
+        }
+        let file = File::open(&proof_path)?;
+        let mut bytes = vec![];
+        if proof_path.extension().is_some_and(|e| e == "gz") {
+            GzDecoder::new(file).read_to_end(&mut bytes)?;
+        } else {
+            BufReader::new(file).read_to_end(&mut bytes)?;
+        }
+        Ok(bytes)
+    };
+
+    match read_proof() {
+        Ok(bytes) => {
+            bcs::from_bytes::<(TransactionAccumulatorRangeProof, LedgerInfoWithSignatures)>(&bytes)
+                .is_ok()
+        }
+        Err(e) => {
+            warn!(
+                "could not read transaction proof at {}, {}",
+                archive_dir.display(),
+                e
+            );
+            false
+        }
+    }
+}
+
 /// Crawl a directory and find all .manifest files.
 /// Optionally find
 pub fn scan_dir_archive(
@@ -1,16 +1,114 @@
 use libra_types::exports::AccountAddress;
+use serde::{Deserialize, Serialize};
 
-use crate::scan::FrameworkVersion;
+use crate::{
+    move_resources::{VALIDATOR_VOUCH_THRESHOLD, VOUCH_EXPIRATION_EPOCHS},
+    scan::FrameworkVersion,
+};
 
 // holds timestamp, chain height, and epoch
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct WarehouseTime {
     pub framework_version: FrameworkVersion,
     pub timestamp: u64,
     pub version: u64,
     pub epoch: u64,
 }
-#[derive(Debug, Clone)]
+/// a vouch with another account, as found in the state of one of them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VouchRecord {
+    /// the other account
+    pub address: AccountAddress,
+    pub epoch_vouched: u64,
+    /// the vouch is no longer counted from this epoch
+    pub expiry_epoch: u64,
+}
+
+impl VouchRecord {
+    /// pair the addresses with the epochs from a vouch resource.
+    /// Older resources may not have an epoch for each address.
+    pub fn from_lists(addresses: &[AccountAddress], epochs: &[u64]) -> Vec<Self> {
+        addresses
+            .iter()
+            .enumerate()
+            .map(|(i, a)| {
+                let epoch_vouched = epochs.get(i).copied().unwrap_or(0);
+                Self {
+                    address: *a,
+                    epoch_vouched,
+                    expiry_epoch: epoch_vouched + VOUCH_EXPIRATION_EPOCHS,
+                }
+            })
+            .collect()
+    }
+}
+
+/// the validator resources of an account, at one snapshot
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ValidatorState {
+    /// listed in the validator universe at 0x1
+    pub in_universe: bool,
+    pub validator_index: Option<u64>,
+    pub operator: Option<AccountAddress>,
+    /// proof of fee bid, in thousandths of the epoch reward
+    pub bid: Option<u64>,
+    pub bid_expiration_epoch: Option<u64>,
+    pub jailed: bool,
+    pub lifetime_jailed: u64,
+    pub consecutive_failure_to_rejoin: u64,
+    /// received vouches which have not expired at the snapshot epoch
+    pub active_vouches: u64,
+    /// enough active vouches to bid. This does not check that the vouchers
+    /// are unrelated, as the framework does.
+    pub vouch_eligible: bool,
+}
+
+impl ValidatorState {
+    /// count the received vouches which are still valid at `epoch`
+    pub fn set_vouch_eligibility(&mut self, vouches_received: &[VouchRecord], epoch: u64) {
+        self.active_vouches = vouches_received
+            .iter()
+            .filter(|v| v.expiry_epoch > epoch)
+            .count() as u64;
+        self.vouch_eligible = self.active_vouches >= VALIDATOR_VOUCH_THRESHOLD;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PaymentStatus {
+    /// approved by the authorities, waiting for the veto period to end
+    Pending,
+    Paid,
+    Vetoed,
+}
+
+/// a payment of a donor voice account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DonorVoicePayment {
+    /// the creation number of the payment, unique within the account
+    pub uid: u64,
+    pub payee: AccountAddress,
+    pub value: f64,
+    pub description: String,
+    /// the epoch the payment is made, unless vetoed
+    pub deadline: u64,
+    pub epoch_latest_veto_received: u64,
+    pub status: PaymentStatus,
+}
+
+/// the governance of a donor voice (community wallet) account
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DonorVoiceState {
+    /// the multisig authorities
+    pub authorities: Vec<AccountAddress>,
+    /// number of authorities needed to approve a payment
+    pub threshold: u64,
+    /// accounts which deposited, and can vote to veto payments
+    pub depositors: Vec<AccountAddress>,
+    pub payments: Vec<DonorVoicePayment>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 /// The basic information for an account
 pub struct WarehouseAccState {
     pub address: AccountAddress,
@@ -22,6 +120,19 @@ pub struct WarehouseAccState {
     pub slow_wallet_acc: bool,
     pub donor_voice_acc: bool,
     pub miner_height: Option<u64>,
+    /// onboarding lineage from the Ancestry resource, root first and the parent last
+    #[serde(default)]
+    pub ancestry: Vec<AccountAddress>,
+    /// vouches from other accounts
+    #[serde(default)]
+    pub vouches_received: Vec<VouchRecord>,
+    /// vouches for other accounts
+    #[serde(default)]
+    pub vouches_given: Vec<VouchRecord>,
+    #[serde(default)]
+    pub donor_voice: Option<DonorVoiceState>,
+    #[serde(default)]
+    pub validator: Option<ValidatorState>,
 }
 
 impl Default for WarehouseAccState {
@@ -35,6 +146,11 @@ impl Default for WarehouseAccState {
             slow_wallet_acc: false,
             donor_voice_acc: false,
             miner_height: None,
+            ancestry: vec![],
+            vouches_received: vec![],
+            vouches_given: vec![],
+            donor_voice: None,
+            validator: None,
             time: WarehouseTime::default(),
         }
     }
@@ -139,6 +255,334 @@ MERGE (addr)-[rel:State {{version: tx.version}}]->(snap)
 
 RETURN COUNT(snap) AS merged_snapshots
 
+"#
+        )
+    }
+
+    /// the list of unique addresses in a batch, as a cypher list literal
+    pub fn to_cypher_address_list(list: &[Self]) -> String {
+        let mut addrs: Vec<String> = list
+            .iter()
+            .map(|el| format!(r#""{}""#, el.address.to_hex_literal()))
+            .collect();
+        addrs.sort();
+        addrs.dedup();
+        format!("[{}]", addrs.join(","))
+    }
+
+    /// Chain all the snapshots of each account in order with NEXT,
+    /// and set the latest balance on the Account.
+    /// Versions restart after the V5 hard fork, so snapshots are ordered by
+    /// framework version first: V5, then V6 from the genesis at version 0, then V7.
+    /// The whole chain of each account in the batch is rebuilt, so that
+    /// snapshots loaded out of order are placed correctly.
+    pub fn cypher_chain_str(address_list_str: &str) -> String {
+        format!(
+            r#"
+WITH {address_list_str} AS addresses
+UNWIND addresses AS address
+MATCH (addr:Account {{address: address}})-[:State]->(snap:Snapshot)
+WITH addr, snap,
+  CASE snap.framework_version
+    WHEN "V5" THEN 0
+    WHEN "V6" THEN 1
+    WHEN "V7" THEN 2
+    ELSE 3
+  END AS era
+ORDER BY era ASC, snap.version ASC
+WITH addr, collect(DISTINCT snap) AS snaps
+
+// remove the previous links, they may skip a snapshot which was loaded later
+CALL {{
+  WITH snaps
+  UNWIND snaps AS s
+  MATCH (s)-[old:NEXT]->(:Snapshot)
+  DELETE old
+}}
+
+WITH addr, snaps, snaps[size(snaps) - 1] AS latest
+SET
+  addr.latest_balance = latest.balance,
+  addr.latest_version = latest.version,
+  addr.latest_framework_version = latest.framework_version
+
+WITH snaps
+UNWIND range(0, size(snaps) - 2) AS i
+WITH snaps[i] AS prev, snaps[i + 1] AS next
+MERGE (prev)-[rel:NEXT]->(next)
+SET
+  // V5 balances are not rebased, there is no delta across the fork
+  rel.delta_balance = CASE
+    WHEN (prev.framework_version = "V5") <> (next.framework_version = "V5") THEN NULL
+    ELSE next.balance - prev.balance
+  END,
+  rel.delta_seq = next.sequence_num - prev.sequence_num
+
+RETURN COUNT(rel) AS linked_snapshots
+"#
+        )
+    }
+
+    /// one record per ancestor of each account, the parent has depth 1
+    pub fn to_cypher_ancestry_map(list: &[Self]) -> String {
+        let mut list_literal = "".to_owned();
+        for el in list {
+            let len = el.ancestry.len();
+            for (i, ancestor) in el.ancestry.iter().enumerate() {
+                list_literal.push_str(&format!(
+                    r#"{{address: "{}", ancestor: "{}", depth: {}}},"#,
+                    el.address.to_hex_literal(),
+                    ancestor.to_hex_literal(),
+                    len - i
+                ));
+            }
+        }
+        list_literal.pop(); // need to drop last comma ","
+        format!("[{}]", list_literal)
+    }
+
+    pub fn cypher_ancestry_str(list_str: &str) -> String {
+        format!(
+            r#"
+WITH {list_str} AS ancestry
+UNWIND ancestry AS a
+MERGE (child:Account {{address: a.address}})
+MERGE (ancestor:Account {{address: a.ancestor}})
+MERGE (ancestor)-[rel:AncestorOf]->(child)
+SET rel.depth = a.depth
+
+RETURN COUNT(rel) AS merged_ancestry
+"#
+        )
+    }
+
+    /// one record per vouch in the state of each account, from the voucher to the vouchee.
+    /// A vouch between two accounts in the batch is listed by both, which the MERGE dedupes.
+    pub fn to_cypher_vouch_map(list: &[Self]) -> String {
+        let mut list_literal = "".to_owned();
+        for el in list {
+            let received = el
+                .vouches_received
+                .iter()
+                .map(|v| (v.address, el.address, v));
+            let given = el.vouches_given.iter().map(|v| (el.address, v.address, v));
+            for (from, to, v) in received.chain(given) {
+                list_literal.push_str(&format!(
+                    r#"{{from: "{}", to: "{}", epoch: {}, version: {}, epoch_vouched: {}, expiry_epoch: {}}},"#,
+                    from.to_hex_literal(),
+                    to.to_hex_literal(),
+                    el.time.epoch,
+                    el.time.version,
+                    v.epoch_vouched,
+                    v.expiry_epoch
+                ));
+            }
+        }
+        list_literal.pop(); // need to drop last comma ","
+        format!("[{}]", list_literal)
+    }
+
+    /// the vouch state is kept per snapshot epoch, so the social graph can be compared over time
+    pub fn cypher_vouch_str(list_str: &str) -> String {
+        format!(
+            r#"
+WITH {list_str} AS vouches
+UNWIND vouches AS v
+MERGE (from:Account {{address: v.from}})
+MERGE (to:Account {{address: v.to}})
+MERGE (from)-[rel:VouchState {{epoch: v.epoch}}]->(to)
+SET
+  rel.version = v.version,
+  rel.epoch_vouched = v.epoch_vouched,
+  rel.expiry_epoch = v.expiry_epoch,
+  rel.expired = v.epoch >= v.expiry_epoch
+
+RETURN COUNT(rel) AS merged_vouches
+"#
+        )
+    }
+
+    /// one record per donor voice account in the batch, with its authorities, depositors and payments
+    pub fn to_cypher_donor_voice_map(list: &[Self]) -> String {
+        let addr_list = |addrs: &[AccountAddress]| {
+            let quoted: Vec<String> = addrs
+                .iter()
+                .map(|a| format!(r#""{}""#, a.to_hex_literal()))
+                .collect();
+            format!("[{}]", quoted.join(","))
+        };
+
+        let mut list_literal = "".to_owned();
+        for el in list {
+            let Some(dv) = &el.donor_voice else {
+                continue;
+            };
+            let payments: Vec<String> = dv
+                .payments
+                .iter()
+                .map(|p| {
+                    format!(
+                        r#"{{uid: {}, payee: "{}", value: {}, description: {}, deadline: {}, epoch_latest_veto_received: {}, status: "{:?}"}}"#,
+                        p.uid,
+                        p.payee.to_hex_literal(),
+                        p.value,
+                        // json string escaping is valid in cypher
+                        serde_json::to_string(&p.description).unwrap_or_default(),
+                        p.deadline,
+                        p.epoch_latest_veto_received,
+                        p.status
+                    )
+                })
+                .collect();
+
+            list_literal.push_str(&format!(
+                r#"{{address: "{}", epoch: {}, version: {}, framework_version: "{}", threshold: {}, authorities: {}, depositors: {}, payments: [{}]}},"#,
+                el.address.to_hex_literal(),
+                el.time.epoch,
+                el.time.version,
+                el.time.framework_version,
+                dv.threshold,
+                addr_list(&dv.authorities),
+                addr_list(&dv.depositors),
+                payments.join(",")
+            ));
+        }
+        list_literal.pop(); // need to drop last comma ","
+        format!("[{}]", list_literal)
+    }
+
+    /// An older snapshot does not overwrite the state from a newer one.
+    /// Versions restart after the V5 fork, so the era is compared before the version.
+    /// Authority edges record the last epoch they were seen, since authorities can be rotated.
+    pub fn cypher_donor_voice_str(list_str: &str) -> String {
+        format!(
+            r#"
+WITH {list_str} AS donor_voices
+UNWIND donor_voices AS d
+// the governance is kept on the Account, labeled DonorVoice
+MERGE (dv:Account {{address: d.address}})
+SET dv:DonorVoice
+
+WITH d, dv,
+  CASE d.framework_version
+    WHEN "V5" THEN 0
+    WHEN "V6" THEN 1
+    WHEN "V7" THEN 2
+    ELSE 3
+  END AS era,
+  CASE dv.donor_voice_framework_version
+    WHEN "V5" THEN 0
+    WHEN "V6" THEN 1
+    WHEN "V7" THEN 2
+    ELSE 3
+  END AS prev_era
+WHERE dv.donor_voice_version IS NULL
+  OR prev_era < era
+  OR (prev_era = era AND dv.donor_voice_version <= d.version)
+SET
+  dv.threshold = d.threshold,
+  dv.donor_voice_epoch = d.epoch,
+  dv.donor_voice_version = d.version,
+  dv.donor_voice_framework_version = d.framework_version
+
+FOREACH (a IN d.authorities |
+  MERGE (auth:Account {{address: a}})
+  MERGE (auth)-[rel:Authority]->(dv)
+  SET rel.last_seen_epoch = d.epoch
+)
+
+FOREACH (a IN d.depositors |
+  MERGE (dep:Account {{address: a}})
+  MERGE (dep)-[:Depositor]->(dv)
+)
+
+FOREACH (p IN d.payments |
+  MERGE (pay:Payment {{donor_voice: d.address, uid: p.uid}})
+  SET
+    pay.payee = p.payee,
+    pay.value = p.value,
+    pay.description = p.description,
+    pay.deadline = p.deadline,
+    pay.epoch_latest_veto_received = p.epoch_latest_veto_received,
+    pay.status = p.status,
+    pay.epoch = d.epoch
+  MERGE (dv)-[:Scheduled]->(pay)
+  MERGE (payee:Account {{address: p.payee}})
+  MERGE (pay)-[:PaidTo]->(payee)
+)
+
+RETURN COUNT(dv) AS merged_donor_voice
+"#
+        )
+    }
+
+    /// one record per validator in the batch
+    pub fn to_cypher_validator_map(list: &[Self]) -> String {
+        let opt = |n: Option<u64>| match n {
+            Some(n) => n.to_string(),
+            None => "NULL".to_string(),
+        };
+
+        let mut list_literal = "".to_owned();
+        for el in list {
+            let Some(v) = &el.validator else {
+                continue;
+            };
+            let operator_literal = match v.operator {
+                Some(o) => format!(r#""{}""#, o.to_hex_literal()),
+                None => "NULL".to_string(),
+            };
+            list_literal.push_str(&format!(
+                r#"{{address: "{}", epoch: {}, version: {}, framework_version: "{}", in_universe: {}, validator_index: {}, operator: {}, bid: {}, bid_expiration_epoch: {}, jailed: {}, lifetime_jailed: {}, consecutive_failure_to_rejoin: {}, active_vouches: {}, vouch_eligible: {}}},"#,
+                el.address.to_hex_literal(),
+                el.time.epoch,
+                el.time.version,
+                el.time.framework_version,
+                v.in_universe,
+                opt(v.validator_index),
+                operator_literal,
+                opt(v.bid),
+                opt(v.bid_expiration_epoch),
+                v.jailed,
+                v.lifetime_jailed,
+                v.consecutive_failure_to_rejoin,
+                v.active_vouches,
+                v.vouch_eligible
+            ));
+        }
+        list_literal.pop(); // need to drop last comma ","
+        format!("[{}]", list_literal)
+    }
+
+    /// label the account as a Validator, and merge a ValidatorState for the snapshot
+    pub fn cypher_validator_str(list_str: &str) -> String {
+        format!(
+            r#"
+WITH {list_str} AS validators
+UNWIND validators AS v
+MERGE (addr:Account {{address: v.address}})
+SET addr:Validator
+// versions restart after V5, so the framework version is part of the key
+MERGE (vs:ValidatorState {{address: v.address, epoch: v.epoch, version: v.version, framework_version: v.framework_version}})
+SET
+  vs.in_universe = v.in_universe,
+  vs.validator_index = v.validator_index,
+  vs.operator = v.operator,
+  vs.bid = v.bid,
+  vs.bid_expiration_epoch = v.bid_expiration_epoch,
+  vs.jailed = v.jailed,
+  vs.lifetime_jailed = v.lifetime_jailed,
+  vs.consecutive_failure_to_rejoin = v.consecutive_failure_to_rejoin,
+  vs.active_vouches = v.active_vouches,
+  vs.vouch_eligible = v.vouch_eligible
+MERGE (addr)-[:ValidatorState {{epoch: v.epoch}}]->(vs)
+
+FOREACH (_ IN CASE WHEN v.operator IS NOT NULL THEN [1] ELSE [] END |
+  MERGE (op:Account {{address: v.operator}})
+  MERGE (vs)-[:Operator]->(op)
+)
+
+RETURN COUNT(vs) AS merged_validators
 "#
         )
     }
@@ -0,0 +1,104 @@
+use chrono::{DateTime, Utc};
+use diem_types::account_address::AccountAddress;
+use serde::{Deserialize, Serialize};
+
+use crate::scan::FrameworkVersion;
+
+/// a block, from its block metadata transaction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WarehouseBlock {
+    pub height: u64,
+    pub epoch: u64,
+    pub round: u64,
+    /// the zero address for NIL blocks
+    pub proposer: AccountAddress,
+    pub timestamp: u64,
+    pub block_datetime: DateTime<Utc>,
+    /// the version of the block metadata transaction
+    pub version: u64,
+    /// the version of the last transaction in the block
+    pub last_version: u64,
+    pub user_txs: u64,
+    /// index in the validator set of the proposers which failed before this block
+    pub failed_proposer_indices: Vec<u32>,
+    pub framework_version: FrameworkVersion,
+}
+
+impl WarehouseBlock {
+    pub fn is_nil_block(&self) -> bool {
+        self.proposer == AccountAddress::ZERO
+    }
+
+    /// create a cypher query string for the map object
+    pub fn to_cypher_map(list: &[Self]) -> String {
+        let mut list_literal = "".to_owned();
+        for el in list {
+            list_literal.push_str(&format!(
+                r#"{{height: {}, epoch: {}, round: {}, proposer: "{}", nil_block: {}, timestamp: {}, block_datetime: datetime("{}"), version: {}, last_version: {}, user_txs: {}, failed_proposer_indices: {:?}, framework_version: "{}"}},"#,
+                el.height,
+                el.epoch,
+                el.round,
+                el.proposer.to_hex_literal(),
+                el.is_nil_block(),
+                el.timestamp,
+                el.block_datetime.to_rfc3339(),
+                el.version,
+                el.last_version,
+                el.user_txs,
+                el.failed_proposer_indices,
+                el.framework_version,
+            ));
+        }
+        list_literal.pop(); // need to drop last comma ","
+        format!("[{}]", list_literal)
+    }
+
+    /// Failed proposers are found from the ValidatorState of the epoch, so
+    /// are only linked when snapshots of the epoch were loaded first.
+    /// Likewise only the transactions already loaded get the block height.
+    /// Versions restart after V5, so only the transactions of the same
+    /// framework version are linked.
+    pub fn cypher_batch_insert_str(list_str: &str) -> String {
+        format!(
+            r#"
+WITH {list_str} AS blocks
+UNWIND blocks AS blk
+MERGE (b:Block {{height: blk.height}})
+SET
+  b.epoch = blk.epoch,
+  b.round = blk.round,
+  b.nil_block = blk.nil_block,
+  b.timestamp = blk.timestamp,
+  b.block_datetime = blk.block_datetime,
+  b.version = blk.version,
+  b.last_version = blk.last_version,
+  b.user_txs = blk.user_txs,
+  b.failed_proposer_indices = blk.failed_proposer_indices,
+  b.framework_version = blk.framework_version
+FOREACH (_ IN CASE WHEN blk.nil_block THEN [] ELSE [1] END |
+  MERGE (p:Account {{address: blk.proposer}})
+  MERGE (p)-[:Proposed]->(b)
+)
+WITH b, blk
+CALL {{
+  WITH b, blk
+  UNWIND blk.failed_proposer_indices AS idx
+  MATCH (a:Account)-[:ValidatorState]->(:ValidatorState {{epoch: blk.epoch, validator_index: idx}})
+  MERGE (a)-[:FailedProposal]->(b)
+  RETURN count(a) AS failed_linked
+}}
+CALL {{
+  WITH blk
+  MATCH ()-[t:Tx]->()
+  WHERE t.framework_version = blk.framework_version
+    AND t.version >= blk.version
+    AND t.version <= blk.last_version
+  SET t.block_height = blk.height
+  RETURN count(t) AS txs_linked
+}}
+
+RETURN COUNT(b) AS merged_blocks, sum(txs_linked) AS linked_txs
+"#
+        )
+    }
+}
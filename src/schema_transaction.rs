@@ -75,9 +75,34 @@ pub struct WarehouseEvent {
     pub tx_hash: HashValue,
     pub event: UserEventTypes,
     pub event_name: String,
+    #[serde(with = "json_value_as_string")]
     pub data: serde_json::Value,
 }
 
+/// A serde_json::Value can only be deserialized from self describing formats.
+/// For binary formats (e.g. the archive cache) it is written as a json string,
+/// and json output is unchanged.
+mod json_value_as_string {
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &serde_json::Value, s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            v.serialize(s)
+        } else {
+            s.serialize_str(&v.to_string())
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<serde_json::Value, D::Error> {
+        if d.is_human_readable() {
+            serde_json::Value::deserialize(d)
+        } else {
+            let s = String::deserialize(d)?;
+            serde_json::from_str(&s).map_err(D::Error::custom)
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 
 pub enum UserEventTypes {
@@ -102,6 +127,8 @@ pub struct WarehouseTxMaster {
     pub tx_hash: HashValue,
     pub relation_label: RelationLabel,
     pub sender: AccountAddress,
+    /// ledger version of the transaction
+    pub version: u64,
     pub function: String,
     pub epoch: u64,
     pub round: u64,
@@ -120,6 +147,7 @@ impl Default for WarehouseTxMaster {
             tx_hash: HashValue::zero(),
             relation_label: RelationLabel::Configuration,
             sender: AccountAddress::ZERO,
+            version: 0,
             function: "none".to_owned(),
             epoch: 0,
             round: 0,
@@ -154,10 +182,11 @@ impl WarehouseTxMaster {
             }
         };
         format!(
-            r#"{{ args: {tx_args}, coins: {coins_literal}, tx_hash: "{}", block_datetime: datetime("{}"), block_timestamp: {}, relation: "{}", function: "{}", sender: "{}", recipient: "{}", framework_version: "{}"}}"#,
+            r#"{{ args: {tx_args}, coins: {coins_literal}, tx_hash: "{}", block_datetime: datetime("{}"), block_timestamp: {}, version: {}, relation: "{}", function: "{}", sender: "{}", recipient: "{}", framework_version: "{}"}}"#,
             self.tx_hash.to_hex_literal(),
             self.block_datetime.to_rfc3339(),
             self.block_timestamp,
+            self.version,
             self.relation_label.to_cypher_label(),
             self.function,
             self.sender.to_hex_literal(),
@@ -0,0 +1,233 @@
+//! incremental sync of transactions from a fullnode REST API
+use anyhow::{Context, Result};
+use diem_crypto::HashValue;
+use diem_types::{
+    contract_event::ContractEvent,
+    transaction::{Transaction, TransactionInfo},
+    write_set::WriteSet,
+};
+use log::{info, warn};
+use neo4rs::Graph;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    batch_tx_type::BatchTxReturn,
+    extract_transactions::{decode_events, make_master_tx},
+    load_tx_cypher,
+    scan::FrameworkVersion,
+    schema_transaction::WarehouseTxMaster,
+};
+
+/// the most transactions a fullnode returns in one page
+pub const MAX_PAGE_SIZE: u64 = 100;
+
+/// The BCS encoding of a transaction returned by the REST API
+/// when requested with `Accept: application/x-bcs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionOnChainData {
+    pub version: u64,
+    pub transaction: Transaction,
+    pub info: TransactionInfo,
+    pub events: Vec<ContractEvent>,
+    pub accumulator_root_hash: HashValue,
+    pub changes: WriteSet,
+}
+
+/// numbers are strings in the REST API json
+#[derive(Debug, Deserialize)]
+struct LedgerInfoResponse {
+    ledger_version: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockResponse {
+    first_version: String,
+}
+
+/// the block metadata in effect while reading transactions in order
+#[derive(Debug, Default, Clone)]
+pub struct BlockState {
+    pub epoch: u64,
+    pub round: u64,
+    pub timestamp: u64,
+}
+
+pub struct RpcClient {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl RpcClient {
+    /// the url of the fullnode REST API, e.g. http://localhost:8080/v1
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: url.trim_end_matches('/').to_owned(),
+        }
+    }
+
+    /// latest version the node has committed
+    pub async fn ledger_version(&self) -> Result<u64> {
+        let res: LedgerInfoResponse = self
+            .client
+            .get(&self.base_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .context("could not parse ledger info")?;
+        Ok(res.ledger_version.parse()?)
+    }
+
+    /// first version of the block which includes `version`
+    pub async fn block_first_version(&self, version: u64) -> Result<u64> {
+        let res: BlockResponse = self
+            .client
+            .get(format!("{}/blocks/by_version/{}", self.base_url, version))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .context(format!("could not parse block at version {}", version))?;
+        Ok(res.first_version.parse()?)
+    }
+
+    pub async fn get_transactions(
+        &self,
+        start: u64,
+        limit: u64,
+    ) -> Result<Vec<TransactionOnChainData>> {
+        let bytes = self
+            .client
+            .get(format!("{}/transactions", self.base_url))
+            .query(&[("start", start), ("limit", limit)])
+            .header(reqwest::header::ACCEPT, "application/x-bcs")
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        bcs::from_bytes(&bytes).context(format!(
+            "could not decode transactions page starting at {}",
+            start
+        ))
+    }
+}
+
+/// convert a page of transactions, in version order.
+/// Block metadata transactions update the block state for the user
+/// transactions which follow.
+pub fn rpc_to_warehouse(
+    txs: &[TransactionOnChainData],
+    block: &mut BlockState,
+    framework_version: &FrameworkVersion,
+) -> Result<Vec<WarehouseTxMaster>> {
+    let mut user_txs = vec![];
+    for t in txs {
+        if let Some(b) = t.transaction.try_as_block_metadata() {
+            block.epoch = b.epoch();
+            block.round = b.round();
+            block.timestamp = b.timestamp_usecs();
+        }
+
+        if let Some(signed_transaction) = t.transaction.try_as_signed_user_txn() {
+            let events = decode_events(t.info.transaction_hash(), &t.events)?;
+            let tx = make_master_tx(
+                signed_transaction,
+                t.version,
+                block.epoch,
+                block.round,
+                block.timestamp,
+                events,
+                framework_version,
+            )?;
+
+            // same filter as extracting from archives
+            if tx.relation_label.get_recipient().is_some() {
+                user_txs.push(tx);
+            }
+        }
+    }
+    Ok(user_txs)
+}
+
+/// highest V6 or V7 transaction version loaded in the graph, from any source.
+/// V5 versions are of the chain before the hard fork, and run higher.
+pub async fn get_highest_version(pool: &Graph) -> Result<Option<u64>> {
+    let cypher_string = r#"
+        MATCH ()-[r:Tx]->()
+        WHERE r.version IS NOT NULL
+          AND r.framework_version IN ["V6", "V7"]
+        RETURN max(r.version) AS max_version
+    "#;
+
+    let cypher_query = neo4rs::query(cypher_string);
+
+    let mut res = pool
+        .execute(cypher_query)
+        .await
+        .context("execute query error")?;
+
+    let row = res.next().await?.context("no row returned")?;
+    Ok(row.get::<Option<u64>>("max_version").unwrap_or(None))
+}
+
+/// page transactions from the fullnode and load them, starting after the
+/// highest version in the graph (or `start_version`), until the node's
+/// ledger version or `max_versions` are reached.
+/// Each page is loaded with its own queue id, so an interrupted sync
+/// can be run again.
+/// The transactions are recorded with the framework version the node runs.
+pub async fn sync_rpc(
+    client: &RpcClient,
+    pool: &Graph,
+    start_version: Option<u64>,
+    max_versions: Option<u64>,
+    batch_size: usize,
+    framework_version: &FrameworkVersion,
+) -> Result<BatchTxReturn> {
+    let start = match start_version {
+        Some(v) => v,
+        None => get_highest_version(pool).await?.map(|v| v + 1).unwrap_or(0),
+    };
+    let ledger_version = client.ledger_version().await?;
+    let end = match max_versions {
+        Some(m) => ledger_version.min(start.saturating_add(m).saturating_sub(1)),
+        None => ledger_version,
+    };
+    info!(
+        "syncing from version {} to {}, node ledger version {}",
+        start, end, ledger_version
+    );
+
+    let mut all_results = BatchTxReturn::new();
+    if start > end {
+        info!("already synced");
+        return Ok(all_results);
+    }
+
+    // start at the beginning of the block, so the block metadata is known
+    let mut next = client.block_first_version(start).await?;
+    let mut block = BlockState::default();
+
+    while next <= end {
+        let limit = MAX_PAGE_SIZE.min(end - next + 1);
+        let page = client.get_transactions(next, limit).await?;
+        let Some(last) = page.last().map(|t| t.version) else {
+            warn!("no transactions returned at version {}", next);
+            break;
+        };
+
+        let txs = rpc_to_warehouse(&page, &mut block, framework_version)?;
+        let queue_id = format!("rpc_{}-{}", next, last);
+        let batch_res = load_tx_cypher::tx_batch(&txs, pool, batch_size, &queue_id).await?;
+        all_results.increment(&batch_res);
+        info!("synced versions {} to {}", next, last);
+
+        next = last + 1;
+    }
+
+    Ok(all_results)
+}
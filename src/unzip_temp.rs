@@ -1,52 +1,36 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use diem_temppath::TempPath;
 use flate2::read::GzDecoder;
 use glob::glob;
 // use libra_storage::read_tx_chunk::load_tx_chunk_manifest;
-use log::{info, warn};
+use log::{error, info, warn};
 use std::{
     fs::File,
     io::copy,
     path::{Path, PathBuf},
+    thread::{self, available_parallelism},
 };
 use tar::Archive;
 
 /// Decompresses a gzip-compressed file at `src_path` and saves the decompressed contents
 /// to `dst_dir` with the same file name, but without the `.gz` extension.
-fn decompress_file(src_path: &Path, dst_dir: &Path, tar_opt: bool) -> Result<PathBuf> {
+fn decompress_file(src_path: &Path, dst_dir: &Path) -> Result<PathBuf> {
     // Open the source file in read-only mode
-    let src_file = File::open(src_path)?;
+    let src_file = File::open(src_path).context("could not open file")?;
 
     // Create a GzDecoder to handle the decompression
     let mut decoder = GzDecoder::new(src_file);
 
     // Generate the destination path with the destination directory and new file name
-    let file_stem = src_path.file_stem().unwrap(); // removes ".gz"
+    let file_stem = src_path.file_stem().context("no file name")?; // removes ".gz"
     let dst_path = dst_dir.join(file_stem); // combines dst_dir with file_stem
 
-    if tar_opt {
-        let mut archive = Archive::new(decoder);
-        // archive.unpack(".")?;
-        for file in archive.entries().unwrap() {
-            // Make sure there wasn't an I/O error
-            let file = file.unwrap();
-
-            // Inspect metadata about the file
-            println!("{:?}", file.header().path().unwrap());
-            println!("{}", file.header().size().unwrap());
-
-            // files implement the Read trait
-            // let mut s = String::new();
-            // file.read_to_string(&mut s).unwrap();
-            // println!("{}", s);
-        }
-    } else {
-        // Open the destination file in write mode
-        let mut dst_file = File::create(&dst_path)?;
+    // Open the destination file in write mode
+    let mut dst_file =
+        File::create(&dst_path).context(format!("could not create {}", dst_path.display()))?;
 
-        // Copy the decompressed data into the destination file
-        copy(&mut decoder, &mut dst_file)?;
-    }
+    // Copy the decompressed data into the destination file
+    copy(&mut decoder, &mut dst_file).context("gzip stream is corrupt or truncated")?;
 
     Ok(dst_path)
 }
@@ -67,6 +51,8 @@ pub fn decompress_tar_archive(src_path: &Path, dst_dir: &Path) -> Result<()> {
 
 /// Unzip all .gz files into the same directory
 /// Warning: this will take up a lot of disk space, should not be used in production for all files. Use for on the fly decompression.
+/// Files are decompressed in parallel. If any file fails, all the failures
+/// are logged with the file name and cause, and an error is returned.
 /// NOTE: Not for tarballs
 pub fn decompress_all_gz(parent_dir: &Path, dst_dir: &Path) -> Result<()> {
     let path = parent_dir.canonicalize()?;
@@ -76,37 +62,76 @@ pub fn decompress_all_gz(parent_dir: &Path, dst_dir: &Path) -> Result<()> {
         path.to_str().context("cannot parse starting dir")?
     );
 
+    let mut files = vec![];
     for entry in glob(&pattern)? {
-        match entry {
-            Ok(src_path) => {
-                let _ = decompress_file(&src_path, dst_dir, false);
-            }
-            Err(e) => {
-                println!("{:?}", e);
-            }
+        files.push(entry.context("could not read path while searching for .gz files")?);
+    }
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let threads = available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(files.len());
+    let chunk_size = files.len().div_ceil(threads);
+
+    let errors: Vec<String> = thread::scope(|s| {
+        let handles: Vec<_> = files
+            .chunks(chunk_size)
+            .map(|chunk| {
+                s.spawn(move || {
+                    chunk
+                        .iter()
+                        .filter_map(|src_path| {
+                            decompress_file(src_path, dst_dir)
+                                .err()
+                                .map(|e| format!("{}: {:#}", src_path.display(), e))
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|h| {
+                h.join()
+                    .unwrap_or_else(|_| vec!["decompression thread panicked".to_string()])
+            })
+            .collect()
+    });
+
+    if !errors.is_empty() {
+        for e in &errors {
+            error!("could not decompress {}", e);
         }
+        bail!(
+            "{} of {} files could not be decompressed in {}, first error: {}",
+            errors.len(),
+            files.len(),
+            parent_dir.display(),
+            errors[0]
+        );
     }
     Ok(())
 }
 
 // The manifest file might have written as .gz, when then should not be.
 // TODO: Deprecate when archives sources fixed (currently some epochs in V7 broken for epochs in Jan 2025)
+// NOTE: this must only be called on a temp directory, never on the user's archive.
 fn maybe_fix_manifest(archive_path: &Path) -> Result<()> {
     let pattern = format!("{}/**/*.manifest", archive_path.display());
     for manifest_path in glob(&pattern)?.flatten() {
+        // never write through a link to the source archive
+        if manifest_path.symlink_metadata()?.file_type().is_symlink() {
+            warn!(
+                "skipping manifest fix, file is a link: {}",
+                manifest_path.display()
+            );
+            continue;
+        }
         let literal = std::fs::read_to_string(&manifest_path)?.replace(".gz", "");
-        // let mut manifest = load_tx_chunk_manifest(&manifest_path)?;
-        // debug!("old manifest:\n{:#}", &serde_json::to_string(&manifest)?);
-
-        // manifest.chunks.iter_mut().for_each(|e| {
-        //     if e.proof.contains(".gz") {
-        //         e.proof = e.proof.trim_end_matches(".gz").to_string();
-        //     }
-        //     if e.transactions.contains(".gz") {
-        //         e.transactions = e.transactions.trim_end_matches(".gz").to_string();
-        //     }
-        // });
-        // let literal = serde_json::to_string(&manifest)?;
 
         warn!(
             "rewriting .manifest file to remove .gz paths, {}, {:#}",
@@ -118,24 +143,83 @@ fn maybe_fix_manifest(archive_path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// check if any manifest in the archive refers to .gz files
+fn manifest_needs_fix(archive_path: &Path) -> Result<bool> {
+    let pattern = format!("{}/*.manifest", archive_path.display());
+    for manifest_path in glob(&pattern)?.flatten() {
+        if std::fs::read_to_string(&manifest_path)?.contains(".gz") {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Make the uncompressed files of the source archive available in the temp
+/// archive dir, without modifying the source.
+/// Manifests are copied, since they may need to be rewritten.
+/// Other files are linked (or copied where links are not supported).
+/// Files already in the temp dir (e.g. decompressed from .gz) are not replaced.
+fn mirror_uncompressed_files(archive_path: &Path, temp_archive_path: &Path) -> Result<()> {
+    for entry in std::fs::read_dir(archive_path)? {
+        let src_path = entry?.path();
+        if !src_path.is_file() || src_path.extension().is_some_and(|e| e == "gz") {
+            continue;
+        }
+        let dst_path = temp_archive_path.join(src_path.file_name().context("no file name")?);
+        if dst_path.exists() {
+            continue;
+        }
+
+        if src_path.to_string_lossy().ends_with(".manifest") {
+            std::fs::copy(&src_path, &dst_path)?;
+        } else {
+            link_or_copy(&src_path, &dst_path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn link_or_copy(src_path: &Path, dst_path: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(src_path.canonicalize()?, dst_path)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn link_or_copy(src_path: &Path, dst_path: &Path) -> Result<()> {
+    std::fs::copy(src_path, dst_path)?;
+    Ok(())
+}
+
+/// create a temp dir with a sub directory of the same name as the archive.
+/// need to preserve the parent dir name in temp, since the manifest files reference it.
+fn make_temp_archive_dir(archive_path: &Path) -> Result<(PathBuf, TempPath)> {
+    let temp_dir = TempPath::new();
+    temp_dir.create_as_dir()?;
+
+    let dir_name = archive_path
+        .file_name()
+        .context("archive path has no directory name")?;
+    let new_archive_path = temp_dir.path().join(dir_name);
+    std::fs::create_dir_all(&new_archive_path)?;
+    Ok((new_archive_path, temp_dir))
+}
+
 /// If we are using this tool with .gz files, we will unzip on the fly
 /// If the user prefers to not do on the fly, then they need to update
 /// their workflow to `gunzip -r` before starting this.
+/// The source archive is only read, never written to. Any decompressed files
+/// or fixed manifests are placed in a temp directory which is returned.
 pub fn maybe_handle_gz(archive_path: &Path) -> Result<(PathBuf, Option<TempPath>)> {
     // maybe stuff isn't unzipped yet
     let pattern = format!("{}/*.*.gz", archive_path.display());
     if glob(&pattern)?.count() > 0 {
-        let temp_dir = TempPath::new();
-        temp_dir.create_as_dir()?;
-
-        // need to preserve the parent dir name in temp, since the manifest files reference it.
-        let dir_name = archive_path.file_name().unwrap().to_str().unwrap();
-        let new_archive_path = temp_dir.path().join(dir_name);
+        let (new_archive_path, temp_dir) = make_temp_archive_dir(archive_path)?;
 
         info!("Decompressing a temp folder. If you do not want to decompress files on the fly (which are not saved), then you workflow to do a `gunzip -r` before starting this. Temp folder: {}", &new_archive_path.display());
 
-        std::fs::create_dir_all(&new_archive_path)?;
         decompress_all_gz(archive_path, &new_archive_path)?;
+        mirror_uncompressed_files(archive_path, &new_archive_path)?;
         // fix the manifest in the TEMP path
         maybe_fix_manifest(temp_dir.path())?;
         return Ok((new_archive_path, Some(temp_dir)));
@@ -147,7 +231,17 @@ pub fn maybe_handle_gz(archive_path: &Path) -> Result<(PathBuf, Option<TempPath>
         glob(&pattern)?.count() > 0,
         "are you sure you decompressed everything here?"
     );
-    maybe_fix_manifest(archive_path)?;
+
+    if manifest_needs_fix(archive_path)? {
+        let (new_archive_path, temp_dir) = make_temp_archive_dir(archive_path)?;
+        info!(
+            "manifest refers to .gz files, fixing a copy in temp folder: {}",
+            &new_archive_path.display()
+        );
+        mirror_uncompressed_files(archive_path, &new_archive_path)?;
+        maybe_fix_manifest(temp_dir.path())?;
+        return Ok((new_archive_path, Some(temp_dir)));
+    }
 
     Ok((archive_path.to_path_buf(), None))
 }
@@ -155,14 +249,11 @@ pub fn maybe_handle_gz(archive_path: &Path) -> Result<(PathBuf, Option<TempPath>
 // take a single archive file, and get the temp location of the unzipped file
 // NOTE: you must return the TempPath to the caller so otherwise when it
 // drops out of scope the files will be deleted, this is intentional.
-pub fn test_helper_temp_unzipped(
-    archive_file: &Path,
-    tar_opt: bool,
-) -> Result<(PathBuf, TempPath)> {
+pub fn test_helper_temp_unzipped(archive_file: &Path) -> Result<(PathBuf, TempPath)> {
     let temp_dir = TempPath::new();
     temp_dir.create_as_dir()?;
 
-    let path = decompress_file(archive_file, temp_dir.path(), tar_opt)?;
+    let path = decompress_file(archive_file, temp_dir.path())?;
 
     Ok((path, temp_dir))
 }
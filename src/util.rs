@@ -1,3 +1,4 @@
+use anyhow::Result;
 use chrono::{DateTime, Utc};
 use diem_types::account_address::AccountAddress;
 use log::error;
@@ -25,12 +26,7 @@ where
     D: Deserializer<'de>,
 {
     let s: &str = Deserialize::deserialize(deserializer)?;
-    // do better hex decoding than this
-    let mut lower = s.to_ascii_lowercase();
-    if !lower.contains("0x") {
-        lower = format!("0x{}", lower);
-    }
-    match AccountAddress::from_hex_literal(&lower) {
+    match parse_address_any_string(s) {
         Ok(addr) => Ok(Some(addr)),
         Err(_) => {
             error!("could not parse address: {}", &s);
@@ -38,3 +34,13 @@ where
         }
     }
 }
+
+/// Parse an address which may be upper or lowercase, with or without 0x
+pub fn parse_address_any_string(s: &str) -> Result<AccountAddress> {
+    // do better hex decoding than this
+    let mut lower = s.to_ascii_lowercase();
+    if !lower.contains("0x") {
+        lower = format!("0x{}", lower);
+    }
+    Ok(AccountAddress::from_hex_literal(&lower)?)
+}
@@ -0,0 +1,201 @@
+//! V5 autopay instructions, the recurring payments most donations to
+//! community wallets were made with.
+use anyhow::{Context, Result};
+use diem_types::account_address::AccountAddress;
+use log::info;
+use neo4rs::Graph;
+use serde::{Deserialize, Serialize};
+
+/// the autopay.move instruction types
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AutopayType {
+    PercentOfBalance,
+    PercentOfChange,
+    FixedRecurring,
+    FixedOnce,
+    Unknown(u8),
+}
+
+impl AutopayType {
+    pub fn from_in_type(in_type: u8) -> Self {
+        match in_type {
+            0 => Self::PercentOfBalance,
+            1 => Self::PercentOfChange,
+            2 => Self::FixedRecurring,
+            3 => Self::FixedOnce,
+            n => Self::Unknown(n),
+        }
+    }
+
+    pub fn is_percent(&self) -> bool {
+        matches!(self, Self::PercentOfBalance | Self::PercentOfChange)
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Self::PercentOfBalance => "PercentOfBalance".to_owned(),
+            Self::PercentOfChange => "PercentOfChange".to_owned(),
+            Self::FixedRecurring => "FixedRecurring".to_owned(),
+            Self::FixedOnce => "FixedOnce".to_owned(),
+            Self::Unknown(n) => format!("Unknown({})", n),
+        }
+    }
+}
+
+/// an autopay_create_instruction transaction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AutopayInstruction {
+    pub sender: AccountAddress,
+    pub payee: AccountAddress,
+    /// chosen by the sender, unique among its instructions
+    pub uid: u64,
+    pub in_type: AutopayType,
+    /// for the percent types, hundredths of a percent. For the fixed types,
+    /// the amount as submitted, before the V6 rebase.
+    pub value: u64,
+    /// the instruction runs until this epoch
+    pub end_epoch: u64,
+    pub version: u64,
+}
+
+/// an autopay_disable transaction, which removes all the instructions of the sender
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AutopayDisabled {
+    pub sender: AccountAddress,
+    pub version: u64,
+}
+
+impl AutopayInstruction {
+    /// create a cypher query string for the map object
+    pub fn to_cypher_map(list: &[Self]) -> String {
+        let mut list_literal = "".to_owned();
+        for el in list {
+            let pct = if el.in_type.is_percent() {
+                format!("{:.2}", el.value as f64 / 100.0)
+            } else {
+                "NULL".to_owned()
+            };
+            list_literal.push_str(&format!(
+                r#"{{sender: "{}", payee: "{}", uid: {}, type: "{}", value: {}, pct: {}, end_epoch: {}, version: {}}},"#,
+                el.sender.to_hex_literal(),
+                el.payee.to_hex_literal(),
+                el.uid,
+                el.in_type.label(),
+                el.value,
+                pct,
+                el.end_epoch,
+                el.version,
+            ));
+        }
+        list_literal.pop(); // need to drop last comma ","
+        format!("[{}]", list_literal)
+    }
+
+    /// The uid can be used again after autopay is disabled, so the edge is
+    /// keyed by uid and version.
+    /// The archives are not loaded in order, so the sender may already have
+    /// disabled autopay after this instruction.
+    pub fn cypher_batch_insert_str(list_str: &str) -> String {
+        format!(
+            r#"
+WITH {list_str} AS instructions
+UNWIND instructions AS i
+MERGE (from:Account {{address: i.sender}})
+MERGE (to:Account {{address: i.payee}})
+MERGE (from)-[rel:Autopay {{uid: i.uid, version: i.version}}]->(to)
+SET
+  rel.type = i.type,
+  rel.value = i.value,
+  rel.pct = i.pct,
+  rel.end_epoch = i.end_epoch,
+  rel.cancelled_version = reduce(
+    c = NULL,
+    d IN coalesce(from.autopay_disabled_versions, []) |
+    CASE WHEN d > i.version AND (c IS NULL OR d < c) THEN d ELSE c END
+  )
+
+RETURN COUNT(rel) AS merged_autopay
+"#
+        )
+    }
+}
+
+impl AutopayDisabled {
+    /// create a cypher query string for the map object
+    pub fn to_cypher_map(list: &[Self]) -> String {
+        let mut list_literal = "".to_owned();
+        for el in list {
+            list_literal.push_str(&format!(
+                r#"{{sender: "{}", version: {}}},"#,
+                el.sender.to_hex_literal(),
+                el.version,
+            ));
+        }
+        list_literal.pop(); // need to drop last comma ","
+        format!("[{}]", list_literal)
+    }
+
+    /// keep the version on the account for instructions loaded later,
+    /// and cancel the earlier instructions
+    pub fn cypher_batch_insert_str(list_str: &str) -> String {
+        format!(
+            r#"
+WITH {list_str} AS disables
+UNWIND disables AS d
+MERGE (from:Account {{address: d.sender}})
+SET from.autopay_disabled_versions = CASE
+  WHEN d.version IN coalesce(from.autopay_disabled_versions, []) THEN from.autopay_disabled_versions
+  ELSE coalesce(from.autopay_disabled_versions, []) + d.version
+END
+WITH from, d
+OPTIONAL MATCH (from)-[rel:Autopay]->()
+WHERE rel.version < d.version
+  AND (rel.cancelled_version IS NULL OR rel.cancelled_version > d.version)
+SET rel.cancelled_version = d.version
+
+RETURN COUNT(DISTINCT from) AS disabled_accounts
+"#
+        )
+    }
+}
+
+/// Returns the count of autopay edges merged
+pub async fn impl_batch_autopay_insert(
+    pool: &Graph,
+    instructions: &[AutopayInstruction],
+    disabled: &[AutopayDisabled],
+) -> Result<u64> {
+    let mut merged = 0;
+    if !instructions.is_empty() {
+        let list_str = AutopayInstruction::to_cypher_map(instructions);
+        let cypher_string = AutopayInstruction::cypher_batch_insert_str(&list_str);
+        let cypher_query = neo4rs::query(&cypher_string);
+        let mut res = pool
+            .execute(cypher_query)
+            .await
+            .context("execute query error")?;
+
+        let row = res.next().await?.context("no row returned")?;
+        merged = row
+            .get("merged_autopay")
+            .context("no merged_autopay field")?;
+    }
+
+    if !disabled.is_empty() {
+        let list_str = AutopayDisabled::to_cypher_map(disabled);
+        let cypher_string = AutopayDisabled::cypher_batch_insert_str(&list_str);
+        let cypher_query = neo4rs::query(&cypher_string);
+        let mut res = pool
+            .execute(cypher_query)
+            .await
+            .context("execute query error")?;
+
+        let row = res.next().await?.context("no row returned")?;
+        let accounts: u64 = row
+            .get("disabled_accounts")
+            .context("no disabled_accounts field")?;
+        info!("accounts which disabled autopay: {}", accounts);
+    }
+
+    Ok(merged)
+}
@@ -0,0 +1,274 @@
+//! V5 miner (tower) proof submissions, counted per epoch.
+//! The proof payloads are large and of no use for analysis, so only the
+//! count of proofs, and which operator submitted them, are kept.
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use diem_types::account_address::AccountAddress;
+use log::{info, warn};
+use neo4rs::Graph;
+use serde::{Deserialize, Serialize};
+
+use crate::json_rescue_v5_extract::{
+    extract_v5_miner_proofs_from_str, for_each_json_in_tgz, list_all_tgz_archives,
+};
+
+/// the proofs of one miner, from one sender, within a range of versions
+/// which does not cross an epoch boundary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MinerProofSegment {
+    pub miner: AccountAddress,
+    /// the sender, when the proof was submitted by an operator for the miner
+    pub operator: Option<AccountAddress>,
+    pub first_version: u64,
+    pub last_version: u64,
+    pub proofs: u64,
+}
+
+/// a newepoch event. The transactions after this version are in the new epoch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EpochBoundary {
+    pub epoch: u64,
+    pub version: u64,
+}
+
+/// all the proofs of a miner in one epoch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MinerEpoch {
+    pub address: AccountAddress,
+    pub epoch: u64,
+    pub proofs: u64,
+    pub first_version: u64,
+    pub last_version: u64,
+    /// the proofs submitted by each operator, the rest were sent by the miner
+    pub operators: BTreeMap<AccountAddress, u64>,
+}
+
+impl MinerEpoch {
+    /// create a cypher query string for the map object
+    pub fn to_cypher_map(list: &[Self]) -> String {
+        let mut list_literal = "".to_owned();
+        for el in list {
+            let operators = el
+                .operators
+                .iter()
+                .map(|(op, proofs)| {
+                    format!(
+                        r#"{{address: "{}", proofs: {}}}"#,
+                        op.to_hex_literal(),
+                        proofs
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+
+            list_literal.push_str(&format!(
+                r#"{{address: "{}", epoch: {}, proofs: {}, first_version: {}, last_version: {}, operators: [{}]}},"#,
+                el.address.to_hex_literal(),
+                el.epoch,
+                el.proofs,
+                el.first_version,
+                el.last_version,
+                operators,
+            ));
+        }
+        list_literal.pop(); // need to drop last comma ","
+        format!("[{}]", list_literal)
+    }
+
+    /// The counts are set, not added, since they are aggregated over all the
+    /// archives before loading. Loading again gives the same result.
+    pub fn cypher_batch_insert_str(list_str: &str) -> String {
+        format!(
+            r#"
+WITH {list_str} AS miner_epochs
+UNWIND miner_epochs AS m
+MERGE (miner:Account {{address: m.address}})
+MERGE (me:MinerEpoch {{address: m.address, epoch: m.epoch}})
+SET
+  me.proofs = m.proofs,
+  me.first_version = m.first_version,
+  me.last_version = m.last_version,
+  me.operator_proofs = reduce(total = 0, o IN m.operators | total + o.proofs)
+MERGE (miner)-[:Mined {{epoch: m.epoch}}]->(me)
+FOREACH (o IN m.operators |
+  MERGE (op:Account {{address: o.address}})
+  MERGE (op)-[rel:SubmittedProofs]->(me)
+  SET rel.proofs = o.proofs
+)
+
+RETURN COUNT(me) AS merged_miner_epochs
+"#
+        )
+    }
+}
+
+/// The epoch of a version, from the boundaries found.
+/// None if no boundary precedes the version.
+pub fn epoch_at_version(boundaries: &BTreeMap<u64, u64>, version: u64) -> Option<u64> {
+    boundaries
+        .range(..=version)
+        .next_back()
+        .map(|(_, epoch)| *epoch)
+}
+
+/// Group the proof segments by miner and epoch.
+/// Returns the miner epochs, and the count of proofs which could not be
+/// placed in an epoch, because the archive with the preceding newepoch event
+/// was not read.
+pub fn aggregate_miner_epochs(
+    segments: &[MinerProofSegment],
+    boundaries: &[EpochBoundary],
+) -> (Vec<MinerEpoch>, u64) {
+    let boundaries: BTreeMap<u64, u64> = boundaries.iter().map(|b| (b.version, b.epoch)).collect();
+
+    let mut epochs: HashMap<(AccountAddress, u64), MinerEpoch> = HashMap::new();
+    let mut unresolved = 0;
+    for s in segments {
+        let Some(epoch) = epoch_at_version(&boundaries, s.first_version) else {
+            unresolved += s.proofs;
+            continue;
+        };
+
+        let me = epochs
+            .entry((s.miner, epoch))
+            .or_insert_with(|| MinerEpoch {
+                address: s.miner,
+                epoch,
+                proofs: 0,
+                first_version: s.first_version,
+                last_version: s.last_version,
+                operators: BTreeMap::new(),
+            });
+        me.proofs += s.proofs;
+        me.first_version = me.first_version.min(s.first_version);
+        me.last_version = me.last_version.max(s.last_version);
+        if let Some(op) = s.operator {
+            *me.operators.entry(op).or_default() += s.proofs;
+        }
+    }
+
+    let mut list: Vec<MinerEpoch> = epochs.into_values().collect();
+    list.sort_by_key(|m| (m.epoch, m.address));
+    (list, unresolved)
+}
+
+/// read the miner proofs and epoch boundaries from every .json in the .tgz archives.
+/// This is blocking.
+pub fn scan_v5_miner_proofs(
+    archive_dir: &Path,
+) -> Result<(Vec<MinerProofSegment>, Vec<EpochBoundary>)> {
+    let mut segments = vec![];
+    let mut boundaries = vec![];
+
+    let tgz_list = list_all_tgz_archives(archive_dir)?;
+    info!("tgz archives found: {}", tgz_list.len());
+    for (n, tgz_path) in tgz_list.iter().enumerate() {
+        info!("PROGRESS: {}/{}", n, tgz_list.len());
+        for_each_json_in_tgz(tgz_path, |file_name, json| {
+            let (s, b) = extract_v5_miner_proofs_from_str(&json)
+                .context(format!("could not parse {}", file_name))?;
+            segments.extend(s);
+            boundaries.extend(b);
+            Ok(())
+        })?;
+    }
+    Ok((segments, boundaries))
+}
+
+pub async fn impl_batch_miner_epoch_insert(pool: &Graph, batch: &[MinerEpoch]) -> Result<u64> {
+    let list_str = MinerEpoch::to_cypher_map(batch);
+    let cypher_string = MinerEpoch::cypher_batch_insert_str(&list_str);
+
+    let cypher_query = neo4rs::query(&cypher_string);
+    let mut res = pool
+        .execute(cypher_query)
+        .await
+        .context("execute query error")?;
+
+    let row = res.next().await?.context("no row returned")?;
+    let merged: u64 = row
+        .get("merged_miner_epochs")
+        .context("no merged_miner_epochs field")?;
+    Ok(merged)
+}
+
+/// Load the miner epochs of all the V5 archives in a directory.
+/// The epoch of a proof is only known from the newepoch events, which are in
+/// other files, so all the archives are read before anything is loaded.
+/// Returns the count of miner epochs merged.
+pub async fn load_v5_miner_history(
+    archive_dir: &Path,
+    pool: &Graph,
+    batch_size: usize,
+) -> Result<u64> {
+    let dir = archive_dir.to_path_buf();
+    let (segments, boundaries) =
+        tokio::task::spawn_blocking(move || scan_v5_miner_proofs(&dir)).await??;
+    info!(
+        "proof segments: {}, epoch boundaries: {}",
+        segments.len(),
+        boundaries.len()
+    );
+
+    let (miner_epochs, unresolved) = aggregate_miner_epochs(&segments, &boundaries);
+    if unresolved > 0 {
+        warn!(
+            "proofs without a known epoch, not loaded: {}. Are archives missing?",
+            unresolved
+        );
+    }
+
+    let mut merged = 0;
+    for chunk in miner_epochs.chunks(batch_size) {
+        merged += impl_batch_miner_epoch_insert(pool, chunk).await?;
+    }
+    info!("miner epochs merged: {}", merged);
+    Ok(merged)
+}
+
+#[test]
+fn test_aggregate_miner_epochs() {
+    let alice = AccountAddress::from_hex_literal("0xa11ce").unwrap();
+    let bob = AccountAddress::from_hex_literal("0xb0b").unwrap();
+    let seg = |miner, operator, first_version, last_version, proofs| MinerProofSegment {
+        miner,
+        operator,
+        first_version,
+        last_version,
+        proofs,
+    };
+
+    let boundaries = vec![
+        EpochBoundary {
+            epoch: 2,
+            version: 10,
+        },
+        EpochBoundary {
+            epoch: 3,
+            version: 100,
+        },
+    ];
+    let segments = vec![
+        // before any epoch boundary was seen
+        seg(alice, None, 5, 5, 1),
+        seg(alice, None, 20, 50, 3),
+        seg(alice, Some(bob), 60, 90, 2),
+        seg(alice, None, 110, 120, 4),
+    ];
+
+    let (list, unresolved) = aggregate_miner_epochs(&segments, &boundaries);
+    assert!(unresolved == 1);
+    assert!(list.len() == 2);
+    assert!(list[0].epoch == 2);
+    assert!(list[0].proofs == 5);
+    assert!(list[0].first_version == 20);
+    assert!(list[0].last_version == 90);
+    assert!(list[0].operators.get(&bob) == Some(&2));
+    assert!(list[1].epoch == 3);
+    assert!(list[1].proofs == 4);
+    assert!(list[1].operators.is_empty());
+}
@@ -1,12 +1,312 @@
-use libra_backwards_compatibility::version_five::transaction_type_v5::TransactionV5;
+//! import archived V5 JSON-RPC responses (get_transactions, get_events, get_account)
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 
-use libra_backwards_compatibility::version_five::transaction_view_v5::BytesView;
+use anyhow::{bail, ensure, Context, Result};
+use diem_crypto::HashValue;
+use glob::glob;
+use libra_backwards_compatibility::version_five::{
+    transaction_type_v5::TransactionV5,
+    transaction_view_v5::{BytesView, TransactionDataView, TransactionViewV5},
+};
+use log::{info, warn};
+use neo4rs::Graph;
+use serde::Deserialize;
+use serde_json::Value;
 
-pub fn bytesview_to_transaction_v5(b: &BytesView) {
-    let tx: TransactionV5 = bcs::from_bytes(b.inner()).unwrap();
-    if let TransactionV5::UserTransaction(signed_transaction) = tx {
-        println!("{:?}", &signed_transaction);
+use crate::{
+    batch_tx_type::BatchTxReturn,
+    json_rescue_v5_extract::{cast_legacy_account, decode_transaction_dataview_v5},
+    load_account_state::snapshot_batch,
+    load_tx_cypher,
+    scan::FrameworkVersion,
+    schema_account_state::WarehouseAccState,
+    schema_transaction::{UserEventTypes, WarehouseEvent, WarehouseTxMaster},
+    util::{parse_address_any_string, COIN_DECIMAL_PRECISION},
+};
+
+/// decode the bcs bytes of a V5 transaction
+pub fn bytesview_to_transaction_v5(b: &BytesView) -> Result<TransactionV5> {
+    bcs::from_bytes(b.inner()).context("could not bcs decode V5 transaction bytes")
+}
+
+/// The warehouse records are made from the json view, and the entry function
+/// from the bytes, so check both describe the same transaction.
+pub fn check_view_matches_bytes(view: &TransactionViewV5) -> Result<()> {
+    let tx = bytesview_to_transaction_v5(&view.bytes)?;
+    if let (
+        TransactionV5::UserTransaction(u),
+        TransactionDataView::UserTransaction {
+            sender,
+            sequence_number,
+            ..
+        },
+    ) = (&tx, &view.transaction)
+    {
+        ensure!(
+            cast_legacy_account(&u.raw_txn.sender)? == cast_legacy_account(sender)?,
+            "sender of the transaction bytes does not match the view"
+        );
+        ensure!(
+            u.raw_txn.sequence_number == *sequence_number,
+            "sequence number of the transaction bytes does not match the view"
+        );
+    }
+    Ok(())
+}
+
+/// The JSON-RPC envelope of a V5 node response
+#[derive(Debug, Deserialize)]
+pub struct V5RpcEnvelope {
+    pub diem_ledger_version: Option<u64>,
+    pub diem_ledger_timestampusec: Option<u64>,
+    pub result: Value,
+}
+
+/// The records found in one or more response files.
+/// NOTE: events are linked to their transactions, but like the events of
+/// archives they are not loaded to the db.
+#[derive(Debug, Default)]
+pub struct V5RpcImport {
+    pub txs: Vec<WarehouseTxMaster>,
+    /// events of transactions which have no warehouse record
+    pub events: Vec<WarehouseEvent>,
+    pub accounts: Vec<WarehouseAccState>,
+    /// tx hash by version, to link events fetched separately
+    hashes: HashMap<u64, HashValue>,
+    /// events from get_events, waiting for their transaction
+    unlinked_events: Vec<(u64, Value)>,
+    /// event key and sequence number, the same event may be in more than one response
+    seen_events: HashSet<(String, u64)>,
+}
+
+impl V5RpcImport {
+    /// add one response file. The method is inferred from the result:
+    /// a list of transactions, a list of events, or an account.
+    pub fn add_response_file(&mut self, path: &Path) -> Result<()> {
+        let json =
+            std::fs::read_to_string(path).context(format!("could not read {}", path.display()))?;
+        let envelope: V5RpcEnvelope = serde_json::from_str(&json)
+            .context(format!("not a JSON-RPC response: {}", path.display()))?;
+        self.add_response(envelope)
+    }
+
+    pub fn add_response(&mut self, envelope: V5RpcEnvelope) -> Result<()> {
+        match envelope.result {
+            Value::Array(list) => {
+                let Some(first) = list.first() else {
+                    return Ok(());
+                };
+                if first.get("bytes").is_some() && first.get("transaction").is_some() {
+                    self.add_transactions(list)
+                } else if first.get("transaction_version").is_some() {
+                    for e in list {
+                        let Some(version) = e.get("transaction_version").and_then(|v| v.as_u64())
+                        else {
+                            continue;
+                        };
+                        self.unlinked_events.push((version, e));
+                    }
+                    Ok(())
+                } else {
+                    bail!("unknown list in JSON-RPC result")
+                }
+            }
+            v @ Value::Object(_) if v.get("balances").is_some() => {
+                let mut acc = decode_account_view(&v)?;
+                // V5 state has epoch 0, as in extract_v5_snapshot, so an account
+                // also found in a state archive merges into the same Snapshot
+                acc.set_time(
+                    envelope.diem_ledger_timestampusec.unwrap_or(0),
+                    envelope.diem_ledger_version.unwrap_or(0),
+                    0,
+                );
+                self.accounts.push(acc);
+                Ok(())
+            }
+            // get_account returns null for accounts which don't exist
+            Value::Null => Ok(()),
+            _ => bail!("unknown JSON-RPC result"),
+        }
+    }
+
+    fn add_transactions(&mut self, list: Vec<Value>) -> Result<()> {
+        let mut events_by_version: HashMap<u64, Vec<Value>> = HashMap::new();
+        let mut views: Vec<TransactionViewV5> = vec![];
+        for v in list {
+            let events = v
+                .get("events")
+                .and_then(|e| e.as_array())
+                .cloned()
+                .unwrap_or_default();
+            let view: TransactionViewV5 =
+                serde_json::from_value(v).context("could not parse JSON to TransactionViewV5")?;
+
+            check_view_matches_bytes(&view)
+                .context(format!("transaction at version {}", view.version))?;
+
+            self.hashes
+                .insert(view.version, HashValue::from_slice(view.hash.to_vec())?);
+            events_by_version.insert(view.version, events);
+            views.push(view);
+        }
+
+        let (mut txs, _, _) = decode_transaction_dataview_v5(&views)?;
+        for tx in txs.iter_mut() {
+            for e in events_by_version.remove(&tx.version).unwrap_or_default() {
+                if self.is_new_event(&e) {
+                    tx.events.push(decode_event_view(tx.tx_hash, e));
+                }
+            }
+        }
+        // events of transactions which have no warehouse record are still kept
+        for (version, list) in events_by_version {
+            if let Some(hash) = self.hashes.get(&version).copied() {
+                for e in list {
+                    if self.is_new_event(&e) {
+                        self.events.push(decode_event_view(hash, e));
+                    }
+                }
+            }
+        }
+        self.txs.append(&mut txs);
+        Ok(())
+    }
+
+    /// false if the event was already added
+    fn is_new_event(&mut self, e: &Value) -> bool {
+        let key = e
+            .get("key")
+            .and_then(|k| k.as_str())
+            .unwrap_or_default()
+            .to_owned();
+        let seq = e
+            .get("sequence_number")
+            .and_then(|s| s.as_u64())
+            .unwrap_or_default();
+        self.seen_events.insert((key, seq))
+    }
+
+    /// link the events from get_events to transactions, once all files are added.
+    /// Returns the count of events with no known transaction, which are dropped.
+    pub fn link_events(&mut self) -> usize {
+        let mut unlinked = 0;
+        for (version, e) in std::mem::take(&mut self.unlinked_events) {
+            if !self.is_new_event(&e) {
+                continue;
+            }
+            match self.hashes.get(&version).copied() {
+                Some(hash) => {
+                    let ev = decode_event_view(hash, e);
+                    match self.txs.iter_mut().find(|t| t.tx_hash == hash) {
+                        Some(tx) => tx.events.push(ev),
+                        None => self.events.push(ev),
+                    }
+                }
+                None => unlinked += 1,
+            }
+        }
+        if unlinked > 0 {
+            warn!(
+                "events dropped, their transactions are not in the responses: {}",
+                unlinked
+            );
+        }
+        unlinked
+    }
+}
+
+/// V5 events have no BCS payload in the response, so the json data is kept as is
+fn decode_event_view(tx_hash: HashValue, e: Value) -> WarehouseEvent {
+    let data = e.get("data").cloned().unwrap_or(Value::Null);
+    let event_name = data
+        .get("type")
+        .and_then(|t| t.as_str())
+        .unwrap_or("unknown")
+        .to_owned();
+    WarehouseEvent {
+        tx_hash,
+        event: UserEventTypes::Other,
+        event_name,
+        data,
+    }
+}
+
+/// the account balance and sequence number from a get_account AccountView
+fn decode_account_view(v: &Value) -> Result<WarehouseAccState> {
+    let address = v
+        .get("address")
+        .and_then(|a| a.as_str())
+        .context("account has no address")?;
+    let mut acc = WarehouseAccState::new(parse_address_any_string(address)?);
+    acc.time.framework_version = FrameworkVersion::V5;
+    acc.sequence_num = v
+        .get("sequence_number")
+        .and_then(|s| s.as_u64())
+        .unwrap_or(0);
+
+    let coins = v
+        .get("balances")
+        .and_then(|b| b.as_array())
+        .into_iter()
+        .flatten()
+        .find(|b| b.get("currency").and_then(|c| c.as_str()) == Some("GAS"))
+        .and_then(|b| b.get("amount"))
+        .and_then(|a| a.as_u64())
+        .unwrap_or(0);
+    acc.balance = coins as f64 / COIN_DECIMAL_PRECISION as f64;
+    Ok(acc)
+}
+
+/// read one response file, or all the .json response files under a directory
+pub fn import_v5_rpc(path: &Path) -> Result<V5RpcImport> {
+    let files: Vec<PathBuf> = if path.is_dir() {
+        let pattern = format!(
+            "{}/**/*.json",
+            path.to_str().context("cannot parse starting dir")?
+        );
+        glob(&pattern)?.filter_map(|p| p.ok()).collect()
+    } else {
+        vec![path.to_owned()]
+    };
+
+    let mut import = V5RpcImport::default();
+    for f in files {
+        import.add_response_file(&f)?;
+    }
+    import.link_events();
+
+    info!(
+        "V5 JSON-RPC transactions: {}, accounts: {}",
+        import.txs.len(),
+        import.accounts.len()
+    );
+    Ok(import)
+}
+
+/// import the responses and load them to the db
+pub async fn load_v5_rpc(path: &Path, pool: &Graph, batch_size: usize) -> Result<BatchTxReturn> {
+    let import = import_v5_rpc(path)?;
+    let archive_id = format!(
+        "v5_rpc_{}",
+        path.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    );
+
+    let res = load_tx_cypher::tx_batch(&import.txs, pool, batch_size, &archive_id).await?;
+    if !import.accounts.is_empty() {
+        snapshot_batch(
+            &import.accounts,
+            pool,
+            batch_size,
+            &format!("{}_accounts", archive_id),
+        )
+        .await?;
     }
+    Ok(res)
 }
 
 #[ignore]
@@ -14,5 +314,6 @@ pub fn bytesview_to_transaction_v5(b: &BytesView) {
 fn test_bytesview_cast() {
     let bytes = hex::decode("00c8336044cdf1878d9738ed0a041b235e0000000000000000030000000000000000000000000000000111546f7765725374617465536372697074731d6d696e657273746174655f636f6d6d69745f62795f6f70657261746f720005107ec16859c24200d8e074809d252ac740212022229f389e88b56c48527f456f68cb765cdb792c4b5d2cac46d489364f61b106ec0aea0a003d17e04e050af8605fbb3e316407ea8fe2ccdf237e1b5a9ef8bf9ec3558e7ae8ffca3468d88e96e8121c3749f6681863b172206320934f8cc394fa5041d8d2d1490021bfdba4840f2f374415b5fdc52e25d79e873558fe090d7e627cc0bfac3594a2001f836d03ba301a45c2ce647f4d072542399a6b1f5ceeabead77905b93ed77d910079946e47b3bce5747328e9594f30f8ff6825a0564cb2468cbc50ac81f0e1990eff89b7eb81b0348288e91fd5a3452d9a0b275d1ecef2c2bab7defbd352472b39f3007e42b3f04a6063b8f7ac7900f01e1bdf325e0bdbac6ea8baf6b1a1dae6f248c3ff919dfca3ec7f26c93ed4d5513d81432a5b9e167fefde6641eb601947c3517dd90066dc5742273263641a3417e83a95eb999a93d089d97686c6b28e1f3f1f38daa4006575fe0882b10e96354ff2bb50b0d9e85dcca13c53763b6d5020b78e81fce36100387f52eb5d9a0ac573d86cf1e762a8d7ccd07af875e3a80ca65f662cb6cd85e4000ea581fa8a4e5a1b68cd7bc4c3605fd792c990a4a735183d728645cb1150fd770021f15ce84fe56c6be017fc566200303d5d7b75a1480d013a744de6725631cb76001cca7768b321c438013c02623241b3864239115e447f81db09769b3c36bfa3e3003b3cda85c084e072959c9d833e88525c9f1259e446db3918ef684434845fb9ccffcca6de724a82fea06b29102ac6fcc97ab857873a92437e21dff917dbfc4e1d69002b06376d6e656eb727b32715fcb9efce084e7a7260dbfe6d438464936796afbd0026de3ddd474aead0ff49f08a311c4117b5ae88886da407bc4e3b9716056cea4500084363abf26dd9181af9418bcbcadaec3625941c6e0a0afbc8df586a8a1d744e0001d8a59df8053336b547b2eabea4faf8eb00e7e6472b026046f719ca5b8fd9a5005d303e6c14375e109c37459f36b72d972b29ede44464a48f1702a9f82e8bd0d8ffc089001820dc51c3e1a1d1a205871a802fc6288b0256d85a5827f70c93f5a179006974fb2130ec2d74c7e8281f1b5e8847a4cdaba0c2abc54ee336ebea6b97febcffeca2e1b10eea280405f4c01de7687bc09d010c5b15b7e75ef8a837d5f412b14100546d651b59c0e50dcce208e1b3a39bc5625ef2797886435928ef79b4a45f77a100081ec5c722fe0611f9428fb92ad267637d163336b2b8db661f1921864392c92f001ce3095ad551c70930e8ab25ce2fa033fe3713e610aa1e694823bec8218d622200110e180b55d3b51dce5cf5c3e7469e41c946e1a5d84b3ae4fd4f4911b9a236a90008e18492ddcbeb44330a6c336d849a420660c1f3f2e812d7a6a76b762aa6508f00011520920a153f325441da1cc1122afaddd742a346dbaaa9347ea7e621252f97001f8caf5edf79baf850632ecc4ce4b0b1b712299917f5408beee17245471ad0b4ffe10f1cd147c03cd217a60b54a40048d3a217328cc8ae90a465b219faa8a69667007a880a6081666b909df985c32efbc00ec53372d6960a8a5be11315d03eda1a2dffc89bc7cbd2c2e9cafee92e76decfa1dd79883b47449d2591f96ce12c08dfc9dd00716971162dd4d476a66c0dba6b210d931a3cfbff80412d60d7a41800f9975f62ffa0e8f4b7d55ef173ef66737c3c2138d51ac50cf0fc45d0e7f00ad66bd214359f005e6447c8afb5f62dd613bf070036d10ffdc69586e21f46e710d88995c4990c4effcdaa9ceb2104a956e8275dad8ab09585bce2aae2f55d73685640df82bc2d85cd0011bf5c5e51a8ff3ea9f8612456f31cc52b32b6bfe8d35fb22ab385f75a8fe2900002fde26ce3f1f2b2d1b2aec2202450106b5fd301029321f7e8f6daefb643fb890005f2a69bbcc02994872f6ca6e255660785b439bc4eb82e466863a070f250cc5300010cd0475df7f2f890703f8a0d08316951b1de551e83a718b5e083d3b63c9ec108000e27070000000008000200000000000010270000000000000100000000000000034741530486796100000000010020d78ee09ad0cfd2d7da6c2cc5fda1d035542d0177cf7aefc34aae63bf39ec127b40efcaeb1d04e32ecfa6845a78825dd878e7b7367853643b412485c5d4cc775ba20b3b4eecf831f3b59273bde833d134763d6f3a2aaebe037112868faedb3f6508").unwrap();
 
-    bytesview_to_transaction_v5(&BytesView::new(bytes));
+    let tx = bytesview_to_transaction_v5(&BytesView::new(bytes)).unwrap();
+    assert!(matches!(tx, TransactionV5::UserTransaction(_)));
 }
@@ -0,0 +1,184 @@
+//! which operator runs which validator, from the transactions which created
+//! and configured them.
+use anyhow::{Context, Result};
+use diem_types::account_address::AccountAddress;
+use log::info;
+use neo4rs::Graph;
+use serde::{Deserialize, Serialize};
+
+use crate::scan::FrameworkVersion;
+
+/// an operator acting for a validator, or a validator configuring itself
+/// when operator and validator are the same
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperatorRelation {
+    pub operator: AccountAddress,
+    pub validator: AccountAddress,
+    pub version: u64,
+    pub function: String,
+    pub framework_version: FrameworkVersion,
+    pub operator_name: Option<String>,
+    pub validator_name: Option<String>,
+    /// hex of the encoded addresses
+    pub network_addresses: Option<String>,
+    pub fullnode_addresses: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ValidatorRole {
+    Validator,
+    Operator,
+}
+
+/// the human readable name an account was created with, when the
+/// counterparty is not known
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidatorHumanName {
+    pub address: AccountAddress,
+    pub human_name: String,
+    pub role: ValidatorRole,
+    pub version: u64,
+}
+
+/// json string escaping is valid in cypher
+fn cypher_opt_str(s: &Option<String>) -> String {
+    match s {
+        Some(s) => serde_json::to_string(s).unwrap_or_default(),
+        None => "NULL".to_owned(),
+    }
+}
+
+impl OperatorRelation {
+    /// create a cypher query string for the map object
+    pub fn to_cypher_map(list: &[Self]) -> String {
+        let mut list_literal = "".to_owned();
+        for el in list {
+            list_literal.push_str(&format!(
+                r#"{{operator: "{}", validator: "{}", version: {}, function: "{}", framework_version: "{}", operator_name: {}, validator_name: {}, network_addresses: {}, fullnode_addresses: {}}},"#,
+                el.operator.to_hex_literal(),
+                el.validator.to_hex_literal(),
+                el.version,
+                el.function,
+                el.framework_version,
+                cypher_opt_str(&el.operator_name),
+                cypher_opt_str(&el.validator_name),
+                cypher_opt_str(&el.network_addresses),
+                cypher_opt_str(&el.fullnode_addresses),
+            ));
+        }
+        list_literal.pop(); // need to drop last comma ","
+        format!("[{}]", list_literal)
+    }
+
+    /// One Operates edge per pair and framework version, with the version
+    /// range it was seen in. Versions restart after V5, so a range never
+    /// spans two framework versions.
+    /// The archives are not loaded in order, so the names and config
+    /// are only replaced by those of a later version.
+    pub fn cypher_batch_insert_str(list_str: &str) -> String {
+        format!(
+            r#"
+WITH {list_str} AS relations
+UNWIND relations AS o
+MERGE (op:Account {{address: o.operator}})
+MERGE (val:Account {{address: o.validator}})
+FOREACH (_ IN CASE WHEN o.operator <> o.validator THEN [1] ELSE [] END |
+  MERGE (op)-[rel:Operates {{framework_version: o.framework_version}}]->(val)
+  SET
+    rel.first_version = CASE WHEN rel.first_version IS NULL OR o.version < rel.first_version THEN o.version ELSE rel.first_version END,
+    rel.last_version = CASE WHEN rel.last_version IS NULL OR o.version > rel.last_version THEN o.version ELSE rel.last_version END,
+    rel.functions = CASE WHEN o.function IN coalesce(rel.functions, []) THEN rel.functions ELSE coalesce(rel.functions, []) + o.function END
+)
+FOREACH (_ IN CASE WHEN o.operator_name IS NOT NULL AND coalesce(op.human_name_version, -1) < o.version THEN [1] ELSE [] END |
+  SET op.human_name = o.operator_name, op.human_name_version = o.version
+)
+FOREACH (_ IN CASE WHEN o.validator_name IS NOT NULL AND coalesce(val.human_name_version, -1) < o.version THEN [1] ELSE [] END |
+  SET val.human_name = o.validator_name, val.human_name_version = o.version
+)
+FOREACH (_ IN CASE WHEN o.network_addresses IS NOT NULL AND coalesce(val.validator_config_version, -1) < o.version THEN [1] ELSE [] END |
+  SET
+    val.network_addresses = o.network_addresses,
+    val.fullnode_addresses = o.fullnode_addresses,
+    val.validator_config_version = o.version
+)
+
+RETURN COUNT(o) AS merged_relations
+"#
+        )
+    }
+}
+
+impl ValidatorHumanName {
+    /// create a cypher query string for the map object
+    pub fn to_cypher_map(list: &[Self]) -> String {
+        let mut list_literal = "".to_owned();
+        for el in list {
+            list_literal.push_str(&format!(
+                r#"{{address: "{}", human_name: {}, role: "{:?}", version: {}}},"#,
+                el.address.to_hex_literal(),
+                serde_json::to_string(&el.human_name).unwrap_or_default(),
+                el.role,
+                el.version,
+            ));
+        }
+        list_literal.pop(); // need to drop last comma ","
+        format!("[{}]", list_literal)
+    }
+
+    pub fn cypher_batch_insert_str(list_str: &str) -> String {
+        format!(
+            r#"
+WITH {list_str} AS names
+UNWIND names AS n
+MERGE (addr:Account {{address: n.address}})
+SET addr.validator_role = n.role
+FOREACH (_ IN CASE WHEN coalesce(addr.human_name_version, -1) < n.version THEN [1] ELSE [] END |
+  SET addr.human_name = n.human_name, addr.human_name_version = n.version
+)
+
+RETURN COUNT(addr) AS named_accounts
+"#
+        )
+    }
+}
+
+/// Returns the count of operator relations merged
+pub async fn impl_batch_operator_insert(
+    pool: &Graph,
+    relations: &[OperatorRelation],
+    names: &[ValidatorHumanName],
+) -> Result<u64> {
+    let mut merged = 0;
+    if !relations.is_empty() {
+        let list_str = OperatorRelation::to_cypher_map(relations);
+        let cypher_string = OperatorRelation::cypher_batch_insert_str(&list_str);
+        let cypher_query = neo4rs::query(&cypher_string);
+        let mut res = pool
+            .execute(cypher_query)
+            .await
+            .context("execute query error")?;
+
+        let row = res.next().await?.context("no row returned")?;
+        merged = row
+            .get("merged_relations")
+            .context("no merged_relations field")?;
+    }
+
+    if !names.is_empty() {
+        let list_str = ValidatorHumanName::to_cypher_map(names);
+        let cypher_string = ValidatorHumanName::cypher_batch_insert_str(&list_str);
+        let cypher_query = neo4rs::query(&cypher_string);
+        let mut res = pool
+            .execute(cypher_query)
+            .await
+            .context("execute query error")?;
+
+        let row = res.next().await?.context("no row returned")?;
+        let named: u64 = row
+            .get("named_accounts")
+            .context("no named_accounts field")?;
+        info!("validator and operator accounts named: {}", named);
+    }
+
+    Ok(merged)
+}
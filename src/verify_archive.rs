@@ -0,0 +1,432 @@
+//! cryptographic verification of archives against epoch ending ledger infos
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{ErrorKind, Read},
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use diem_crypto::hash::CryptoHash;
+use diem_types::{
+    ledger_info::LedgerInfoWithSignatures,
+    proof::{TransactionAccumulatorRangeProof, TransactionInfoWithProof},
+    waypoint::Waypoint,
+};
+use libra_storage::{
+    read_snapshot::load_snapshot_manifest,
+    read_tx_chunk::{load_chunk, load_tx_chunk_manifest},
+};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    scan::{ArchiveMap, BundleContent, ManifestInfo},
+    unzip_temp,
+};
+
+/// The epoch_ending.manifest format
+#[derive(Debug, Deserialize)]
+pub struct EpochEndingManifest {
+    pub first_epoch: u64,
+    pub last_epoch: u64,
+    pub waypoints: Vec<String>,
+    pub chunks: Vec<EpochEndingChunk>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EpochEndingChunk {
+    pub first_epoch: u64,
+    pub last_epoch: u64,
+    pub ledger_infos: String,
+}
+
+/// What the chain of signatures behind a verified ledger info starts from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifiedBy {
+    /// a waypoint given by the caller, e.g. the genesis waypoint
+    TrustedWaypoint,
+    /// only the waypoint in the archive's own manifest, which the archive
+    /// asserts about itself
+    ManifestWaypoint,
+}
+
+impl VerifiedBy {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::TrustedWaypoint => "trusted_waypoint",
+            Self::ManifestWaypoint => "manifest_waypoint",
+        }
+    }
+}
+
+/// The result of verifying one archive, which gets recorded on the queue
+#[derive(Debug, Clone, Serialize)]
+pub struct VerificationResult {
+    pub archive_id: String,
+    /// the proofs are valid, and chain up to a trusted waypoint
+    pub verified: bool,
+    /// None when the proofs are invalid
+    pub verified_by: Option<VerifiedBy>,
+    /// epoch of the ledger info the archive proofs chain up to
+    pub ledger_epoch: Option<u64>,
+    /// version of the ledger info the archive proofs chain up to
+    pub ledger_version: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Epoch ending ledger infos, each one checked against the validator set
+/// of the previous epoch.
+/// The first epoch found (and any epoch after a gap) is only checked
+/// against the waypoint in its manifest, unless it matches a trusted
+/// waypoint given by the caller.
+#[derive(Debug, Default)]
+pub struct EpochHistory {
+    pub ledger_infos: BTreeMap<u64, LedgerInfoWithSignatures>,
+    /// epochs which are trusted by waypoint only, not by signatures
+    pub waypoint_roots: Vec<u64>,
+    /// epochs whose ledger info matches a trusted waypoint
+    pub trusted_epochs: Vec<u64>,
+}
+
+impl EpochHistory {
+    /// read and verify all the epoch ending archives in the map.
+    /// The epochs signed forward from one matching a trusted waypoint are
+    /// trusted, the rest are only rooted in their manifest waypoints.
+    pub fn from_archive_map(map: &ArchiveMap, trusted: &[Waypoint]) -> Result<Self> {
+        let mut unverified: BTreeMap<u64, LedgerInfoWithSignatures> = BTreeMap::new();
+
+        for man in map
+            .0
+            .values()
+            .filter(|m| m.contents == BundleContent::EpochEnding)
+        {
+            let (archive_dir, temp) = unzip_temp::maybe_handle_gz(&man.archive_dir)?;
+            for li in read_epoch_ending_archive(&archive_dir)? {
+                unverified.insert(li.ledger_info().epoch(), li);
+            }
+            drop(temp);
+        }
+
+        let mut history = EpochHistory::default();
+        for (epoch, li) in unverified {
+            let previous = epoch
+                .checked_sub(1)
+                .and_then(|e| history.ledger_infos.get(&e));
+
+            match previous.and_then(|p| p.ledger_info().next_epoch_state()) {
+                Some(state) => {
+                    li.verify_signatures(&state.verifier).context(format!(
+                        "epoch ending ledger info for epoch {} is not signed by the validators of epoch {}",
+                        epoch, state.epoch
+                    ))?;
+                }
+                None => {
+                    warn!(
+                        "no verified ledger info for epoch {}, trusting epoch {} by waypoint only",
+                        epoch.saturating_sub(1),
+                        epoch
+                    );
+                    history.waypoint_roots.push(epoch);
+                }
+            }
+            ensure!(
+                li.ledger_info().ends_epoch(),
+                "ledger info for epoch {} does not end the epoch",
+                epoch
+            );
+            if trusted.iter().any(|w| {
+                w.version() == li.ledger_info().version() && w.verify(li.ledger_info()).is_ok()
+            }) {
+                history.trusted_epochs.push(epoch);
+            }
+            history.ledger_infos.insert(epoch, li);
+        }
+
+        if history.trusted_epochs.len() < trusted.len() {
+            warn!(
+                "trusted waypoints given: {}, matched in epoch ending archives: {}",
+                trusted.len(),
+                history.trusted_epochs.len()
+            );
+        }
+        info!(
+            "epoch ending ledger infos verified: {}, trusted epochs: {:?}",
+            history.ledger_infos.len(),
+            history.trusted_epochs
+        );
+        Ok(history)
+    }
+
+    /// What a ledger info already checked with `verify_ledger_info` is rooted in.
+    /// It is trusted when a trusted epoch lies between the waypoint root of
+    /// its chain and the epoch whose validators signed it.
+    pub fn verified_by(&self, li: &LedgerInfoWithSignatures) -> VerifiedBy {
+        let epoch = li.ledger_info().epoch();
+        let is_epoch_ending = self
+            .ledger_infos
+            .get(&epoch)
+            .map(|known| known.ledger_info() == li.ledger_info())
+            .unwrap_or(false);
+        let signer_epoch = if is_epoch_ending {
+            epoch
+        } else {
+            epoch.saturating_sub(1)
+        };
+
+        let root = self
+            .waypoint_roots
+            .iter()
+            .rev()
+            .find(|r| **r <= signer_epoch);
+        match root {
+            Some(r)
+                if self
+                    .trusted_epochs
+                    .iter()
+                    .any(|t| *t >= *r && *t <= signer_epoch) =>
+            {
+                VerifiedBy::TrustedWaypoint
+            }
+            _ => VerifiedBy::ManifestWaypoint,
+        }
+    }
+
+    /// check a ledger info is signed by the validator set of its epoch.
+    /// The validator set for epoch N is found in the epoch ending ledger info of N-1.
+    pub fn verify_ledger_info(&self, li: &LedgerInfoWithSignatures) -> Result<()> {
+        let epoch = li.ledger_info().epoch();
+        // the proof may use the epoch ending ledger info itself
+        if let Some(known) = self.ledger_infos.get(&epoch) {
+            if known.ledger_info() == li.ledger_info() {
+                return Ok(());
+            }
+        }
+        let previous = epoch
+            .checked_sub(1)
+            .and_then(|e| self.ledger_infos.get(&e))
+            .context(format!(
+                "no epoch ending ledger info found for epoch {}, cannot get validator set",
+                epoch.saturating_sub(1)
+            ))?;
+
+        let state = previous
+            .ledger_info()
+            .next_epoch_state()
+            .context("epoch ending ledger info has no next epoch state")?;
+
+        li.verify_signatures(&state.verifier).context(format!(
+            "ledger info signatures invalid for epoch {}",
+            epoch
+        ))?;
+        Ok(())
+    }
+}
+
+/// read all the ledger infos in an epoch ending archive.
+/// Each ledger info is checked against the manifest waypoints.
+pub fn read_epoch_ending_archive(archive_path: &Path) -> Result<Vec<LedgerInfoWithSignatures>> {
+    let manifest_path = archive_path.join("epoch_ending.manifest");
+    let manifest: EpochEndingManifest =
+        serde_json::from_str(&std::fs::read_to_string(&manifest_path)?).context(format!(
+            "could not parse epoch_ending.manifest at {}",
+            manifest_path.display()
+        ))?;
+
+    let waypoints = manifest
+        .waypoints
+        .iter()
+        .map(|w| Waypoint::from_str(w))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // the chunk paths in the manifest include the archive dir name
+    let parent = archive_path
+        .parent()
+        .context("archive has no parent directory")?;
+
+    let mut ledger_infos = vec![];
+    for chunk in manifest.chunks {
+        for bytes in read_records(&parent.join(&chunk.ledger_infos))? {
+            let li: LedgerInfoWithSignatures = bcs::from_bytes(&bytes)?;
+            let epoch = li.ledger_info().epoch();
+            ensure!(
+                epoch >= chunk.first_epoch && epoch <= chunk.last_epoch,
+                "ledger info epoch {} outside of chunk range {}-{}",
+                epoch,
+                chunk.first_epoch,
+                chunk.last_epoch
+            );
+            let wp = epoch
+                .checked_sub(manifest.first_epoch)
+                .and_then(|i| waypoints.get(i as usize))
+                .context(format!("no waypoint for epoch {}", epoch))?;
+            wp.verify(li.ledger_info())
+                .context(format!("waypoint does not match epoch {}", epoch))?;
+            ledger_infos.push(li);
+        }
+    }
+    Ok(ledger_infos)
+}
+
+/// backup files are a sequence of records, each prefixed with a
+/// big endian u32 of the record length.
+fn read_records(file_path: &Path) -> Result<Vec<Vec<u8>>> {
+    let mut file = File::open(file_path)
+        .context(format!("could not open chunk file {}", file_path.display()))?;
+    let mut records = vec![];
+    loop {
+        let mut size_buf = [0u8; 4];
+        match file.read_exact(&mut size_buf) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        let mut record = vec![0u8; u32::from_be_bytes(size_buf) as usize];
+        file.read_exact(&mut record)?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// check each transaction chunk's accumulator range proof against the
+/// ledger info in its proof file, and that the ledger info is signed by
+/// the validators of its epoch.
+/// Returns the highest ledger info the chunks were verified against.
+pub async fn verify_transaction_archive(
+    archive_path: &Path,
+    history: &EpochHistory,
+) -> Result<LedgerInfoWithSignatures> {
+    let manifest = load_tx_chunk_manifest(&archive_path.join("transaction.manifest"))?;
+    let parent = archive_path
+        .parent()
+        .context("archive has no parent directory")?;
+
+    let mut last_li = None;
+    for each_chunk_manifest in manifest.chunks {
+        let first_version = each_chunk_manifest.first_version;
+        let proof_bytes = std::fs::read(parent.join(&each_chunk_manifest.proof))?;
+        let (range_proof, li): (TransactionAccumulatorRangeProof, LedgerInfoWithSignatures) =
+            bcs::from_bytes(&proof_bytes)?;
+
+        history.verify_ledger_info(&li)?;
+
+        let chunk = load_chunk(archive_path, each_chunk_manifest).await?;
+        let info_hashes: Vec<_> = chunk.txn_infos.iter().map(|i| i.hash()).collect();
+        range_proof
+            .verify(
+                li.ledger_info().transaction_accumulator_hash(),
+                Some(first_version),
+                &info_hashes,
+            )
+            .context(format!(
+                "accumulator proof failed for chunk starting at version {}",
+                first_version
+            ))?;
+
+        for (i, (tx, info)) in chunk.txns.iter().zip(chunk.txn_infos.iter()).enumerate() {
+            ensure!(
+                tx.hash() == info.transaction_hash(),
+                "transaction hash does not match transaction info at version {}",
+                first_version + i as u64
+            );
+        }
+        last_li = Some(li);
+    }
+
+    last_li.context("no chunks in transaction manifest")
+}
+
+/// check the snapshot root hash is proven by the transaction info
+/// at the snapshot version, and that the ledger info is signed
+/// by the validators of its epoch.
+/// NOTE: the account blobs in each chunk are not proven against the root hash here.
+pub fn verify_snapshot_archive(
+    archive_path: &Path,
+    history: &EpochHistory,
+) -> Result<LedgerInfoWithSignatures> {
+    let manifest = load_snapshot_manifest(&archive_path.join("state.manifest"))?;
+    let parent = archive_path
+        .parent()
+        .context("archive has no parent directory")?;
+
+    let proof_bytes = std::fs::read(parent.join(&manifest.proof))?;
+    let (txn_info_with_proof, li): (TransactionInfoWithProof, LedgerInfoWithSignatures) =
+        bcs::from_bytes(&proof_bytes)?;
+
+    history.verify_ledger_info(&li)?;
+    txn_info_with_proof.verify(li.ledger_info(), manifest.version)?;
+
+    ensure!(
+        txn_info_with_proof
+            .transaction_info()
+            .state_checkpoint_hash()
+            == Some(manifest.root_hash),
+        "snapshot root hash does not match the state checkpoint at version {}",
+        manifest.version
+    );
+    Ok(li)
+}
+
+/// verify any kind of archive, collecting the outcome instead of returning an error
+pub async fn verify_archive(man: &ManifestInfo, history: &EpochHistory) -> VerificationResult {
+    let res = match man.contents {
+        BundleContent::Transaction => verify_transaction_archive(&man.archive_dir, history).await,
+        BundleContent::StateSnapshot => verify_snapshot_archive(&man.archive_dir, history),
+        _ => Err(anyhow::anyhow!(
+            "no verification for {:?} archives",
+            man.contents
+        )),
+    };
+
+    match res {
+        Ok(li) => {
+            let verified_by = history.verified_by(&li);
+            if verified_by == VerifiedBy::ManifestWaypoint {
+                warn!(
+                    "archive {} proofs are valid, but only rooted in manifest waypoints",
+                    man.archive_id
+                );
+            }
+            VerificationResult {
+                archive_id: man.archive_id.clone(),
+                verified: verified_by == VerifiedBy::TrustedWaypoint,
+                verified_by: Some(verified_by),
+                ledger_epoch: Some(li.ledger_info().epoch()),
+                ledger_version: Some(li.ledger_info().version()),
+                error: None,
+            }
+        }
+        Err(e) => {
+            error!("archive {} failed verification: {:#}", man.archive_id, e);
+            VerificationResult {
+                archive_id: man.archive_id.clone(),
+                verified: false,
+                verified_by: None,
+                ledger_epoch: None,
+                ledger_version: None,
+                error: Some(format!("{:#}", e)),
+            }
+        }
+    }
+}
+
+/// convenience to scan a directory for epoch ending archives and build the history
+pub fn epoch_history_from_dir(start_path: &Path, trusted: &[Waypoint]) -> Result<EpochHistory> {
+    let map = crate::scan::scan_dir_archive(start_path, Some(BundleContent::EpochEnding))?;
+    if map.0.is_empty() {
+        bail!(
+            "no epoch ending archives found under {}",
+            start_path.display()
+        );
+    }
+    EpochHistory::from_archive_map(&map, trusted)
+}
+
+/// parse waypoints given as `version:hash`
+pub fn parse_waypoints(list: &[String]) -> Result<Vec<Waypoint>> {
+    list.iter()
+        .map(|w| Waypoint::from_str(w).context(format!("could not parse waypoint {}", w)))
+        .collect()
+}
@@ -1,20 +1,37 @@
 use anyhow::{bail, Result};
 use clap::{Parser, Subcommand};
+use diem_types::waypoint::Waypoint;
 use log::{error, info, warn};
 use neo4rs::Graph;
 use serde_json::json;
-use std::path::PathBuf;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use crate::{
     analytics::{self, offline_matching::Matching},
+    archive_cache::{archive_content_hash, ArchiveCache},
+    check_archive::{self, CheckStatus},
+    classify_accounts::{self, ExchangeWallet},
+    coverage,
     enrich_exchange_onboarding::{self, ExchangeOnRamp},
     enrich_whitepages::{self, Whitepages},
+    extract_genesis,
+    inspect_archive::{self, InspectFilter},
     json_rescue_v5_load,
-    load::{ingest_all, try_load_one_archive},
-    load_exchange_orders,
+    load::{decode_archive, ingest_all, ingest_watch, try_load_one_archive},
+    load_account_state::snapshot_batch,
+    load_blocks, load_exchange_orders,
     neo4j_init::{self, get_credentials_from_env, PASS_ENV, URI_ENV, USER_ENV},
-    scan::{scan_dir_archive, BundleContent, ManifestInfo},
-    unzip_temp, util,
+    queue,
+    scan::{scan_dir_archive, BundleContent, FrameworkVersion, ManifestInfo},
+    schema_account_state::WarehouseAccState,
+    sync_rpc::{self, RpcClient},
+    unzip_temp, util, v5_miner_history, v5_rpc_to_raw,
+    verify_archive::{self, EpochHistory, VerifiedBy},
 };
 
 #[derive(Parser)]
@@ -42,6 +59,16 @@ pub struct WarehouseCli {
     /// max tasks to run in parallel
     threads: Option<usize>,
 
+    #[clap(long)]
+    /// directory to cache decoded archives, so that retries skip decoding
+    cache_dir: Option<PathBuf>,
+
+    #[clap(long)]
+    /// waypoint as version:hash, e.g. the genesis waypoint, which verification
+    /// trusts. Without one, verification is only rooted in the archives' own
+    /// manifest waypoints, and nothing is recorded as verified. Can be repeated.
+    trusted_waypoint: Vec<String>,
+
     #[clap(subcommand)]
     command: Sub,
 }
@@ -60,6 +87,15 @@ pub enum Sub {
         #[clap(long, short('b'))]
         /// size of each batch to load
         batch_size: Option<usize>,
+        #[clap(long)]
+        /// verify archive proofs against the epoch ending archives found under start path
+        verify: bool,
+        #[clap(long)]
+        /// keep running, and load new archives as they appear under start path
+        watch: bool,
+        #[clap(long)]
+        /// seconds between scans in watch mode, default 300
+        watch_interval_secs: Option<u64>,
     },
     /// process and load a single archive
     IngestOne {
@@ -70,11 +106,72 @@ pub enum Sub {
         #[clap(long, short('b'))]
         /// size of each batch to load
         batch_size: Option<usize>,
+
+        #[clap(long)]
+        /// verify archive proofs against the epoch ending archives found under this path
+        epoch_archive_dir: Option<PathBuf>,
+    },
+    /// load the optional Block model from the block metadata of V6 and V7
+    /// transaction archives, linking proposers and loaded transactions
+    LoadBlocks {
+        #[clap(long, short('d'))]
+        /// starting path for transaction archives
+        start_path: PathBuf,
+
+        #[clap(long, short('b'))]
+        /// size of each batch to load
+        batch_size: Option<usize>,
+    },
+    /// load the genesis account states from the V6 hard fork recovery file,
+    /// as snapshots at version 0
+    IngestGenesis {
+        #[clap(long)]
+        /// recovery .json file
+        recovery_json: PathBuf,
+        #[clap(long, short('b'))]
+        /// size of each batch to load
+        batch_size: Option<usize>,
     },
     /// check archive is valid and can be decoded
     Check {
         #[clap(long, short('d'))]
         archive_dir: PathBuf,
+        #[clap(long)]
+        /// decode every chunk and verify hashes, lengths and versions
+        deep: bool,
+        #[clap(long)]
+        /// verify proofs against the epoch ending archives found under archive_dir
+        verify: bool,
+    },
+    /// report gaps, overlaps and duplicates in the version ranges of archives
+    Coverage {
+        #[clap(long, short('d'))]
+        /// path to start crawling from
+        start_path: PathBuf,
+        #[clap(long)]
+        /// check the db queue for which archives are already loaded
+        with_queue: bool,
+    },
+    /// decode an archive offline and print the records as NDJSON
+    Inspect {
+        #[clap(long, short('d'))]
+        /// location of archive, or a v5 rescue .json or .tgz file
+        archive_dir: PathBuf,
+        #[clap(long, short('o'))]
+        /// file to write to, otherwise prints to stdout
+        output: Option<PathBuf>,
+        #[clap(long)]
+        /// only records sent, received, or owned by this address
+        address: Option<String>,
+        #[clap(long)]
+        /// only transactions where the function name contains this string
+        function: Option<String>,
+        #[clap(long)]
+        /// lowest ledger version to include
+        start_version: Option<u64>,
+        #[clap(long)]
+        /// highest ledger version to include
+        end_version: Option<u64>,
     },
     /// add supporting data in addition to chain records
     EnrichExchange {
@@ -91,19 +188,84 @@ pub enum Sub {
         /// file with onboarding accounts
         onboarding_json: PathBuf,
     },
+    /// label accounts by type (e.g. SlowWallet, Validator) with the
+    /// version range during which each type applied
+    ClassifyAccounts {
+        #[clap(long)]
+        /// file with exchange wallet addresses
+        /// e.g. [{"address": "0x...", "exchange": "name"}]
+        exchange_json: Option<PathBuf>,
+    },
     /// map owners of accounts from json file
     EnrichWhitepages {
         #[clap(long)]
         /// file with owner map
         owner_json: PathBuf,
     },
+    /// load the latest transactions from a fullnode REST API,
+    /// starting after the highest version in the db
+    SyncRpc {
+        #[clap(long)]
+        /// url of the fullnode REST API e.g. http://localhost:8080/v1
+        url: String,
+        #[clap(long)]
+        /// start at this version instead of the highest version in the db
+        start_version: Option<u64>,
+        #[clap(long)]
+        /// stop after this many versions
+        max_versions: Option<u64>,
+        #[clap(long, short('b'))]
+        /// size of each batch to load
+        batch_size: Option<usize>,
+        #[clap(long)]
+        /// framework version the node runs, default v7
+        framework_version: Option<FrameworkVersion>,
+    },
+    /// load archived V5 JSON-RPC responses (get_transactions, get_events, get_account).
+    /// Events are not loaded, the newepoch events only date the accounts.
+    VersionFiveRpc {
+        #[clap(long)]
+        /// a response .json file, or a directory of them
+        response_path: PathBuf,
+        #[clap(long, short('b'))]
+        /// size of each batch to load
+        batch_size: Option<usize>,
+    },
     VersionFiveTx {
         #[clap(long)]
         /// starting path for v5 .tgz files
         archive_dir: PathBuf,
     },
+    /// count the V5 miner proofs per epoch. All the .tgz archives are read
+    /// before loading, since epochs are found from the newepoch events.
+    VersionFiveMiners {
+        #[clap(long)]
+        /// starting path for v5 .tgz files
+        archive_dir: PathBuf,
+        #[clap(long, short('b'))]
+        /// size of each batch to load
+        batch_size: Option<usize>,
+    },
     #[clap(subcommand)]
     Analytics(AnalyticsSub),
+    #[clap(subcommand)]
+    /// manage the decoded archive cache, requires --cache-dir
+    Cache(CacheSub),
+}
+
+#[derive(Subcommand)]
+pub enum CacheSub {
+    /// count and size of cached archives
+    Stats,
+    /// remove old entries, least recently used first
+    Prune {
+        #[clap(long)]
+        /// remove entries not used in this many days
+        older_than_days: Option<u64>,
+        #[clap(long)]
+        /// remove entries until the cache is smaller than this many megabytes
+        max_size_mb: Option<u64>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -115,6 +277,69 @@ pub enum AnalyticsSub {
         persist: bool,
     },
 
+    /// compare the last V5 snapshot with the first V6 snapshot of each account
+    ForkContinuity {
+        #[clap(long)]
+        /// the last V5 state snapshot archive
+        v5_archive: PathBuf,
+        #[clap(long)]
+        /// the first V6 state snapshot archive
+        v6_archive: PathBuf,
+        #[clap(long)]
+        /// coins of difference tolerated after the rebase, default 0.01
+        tolerance: Option<f64>,
+        #[clap(long)]
+        /// link the snapshots in the db with CONTINUES
+        persist: bool,
+    },
+
+    /// all the accounts onboarded by an address, from the Ancestry resource
+    Descendants {
+        #[clap(long)]
+        /// root of the subtree
+        address: String,
+        #[clap(long)]
+        /// only include descendants up to this many generations below
+        max_depth: Option<u64>,
+    },
+
+    /// compare the balance changes between two snapshot versions
+    /// with the transactions loaded in that range
+    Reconcile {
+        #[clap(long)]
+        /// version of the earlier snapshot
+        start_version: u64,
+        #[clap(long)]
+        /// version of the later snapshot
+        end_version: u64,
+        #[clap(long)]
+        /// framework version of both snapshots, default v7
+        framework_version: Option<FrameworkVersion>,
+        #[clap(long)]
+        /// coins of unexplained difference tolerated, default 0.01
+        tolerance: Option<f64>,
+        #[clap(long)]
+        /// path of the CSV of discrepancies, default ./reconcile_<start>_<end>.csv
+        csv_out: Option<PathBuf>,
+        #[clap(long)]
+        /// commits the Discrepancy results to the db
+        persist: bool,
+    },
+
+    /// check slow wallet transfers against the unlocked amounts, and find
+    /// accounts relaying slow wallet funds
+    SlowWalletCompliance {
+        #[clap(long)]
+        /// coins of difference tolerated, default 0.01
+        tolerance: Option<f64>,
+        #[clap(long)]
+        /// share of the amount received which must be sent on to be a relay, default 0.9
+        relay_min_ratio: Option<f64>,
+        #[clap(long)]
+        /// versions within which the amount must be sent on to be a relay, default 100000
+        relay_max_versions: Option<u64>,
+    },
+
     TradesMatching {
         #[clap(long)]
         /// start day (exclusive) of trades YYYY-MM-DD
@@ -146,18 +371,82 @@ impl WarehouseCli {
                 start_path,
                 archive_content,
                 batch_size,
+                verify,
+                watch,
+                watch_interval_secs,
             } => {
+                if *watch {
+                    let cache = self.archive_cache()?;
+                    let pool = try_db_connection_pool(self).await?;
+                    neo4j_init::maybe_create_indexes(&pool).await?;
+                    if self.clear_queue {
+                        warn!("clearing load queue before watching");
+                        queue::clear_queue(&pool).await?;
+                    }
+
+                    ingest_watch(
+                        start_path,
+                        archive_content.to_owned(),
+                        &pool,
+                        batch_size.unwrap_or(250),
+                        *verify,
+                        &self.trusted_waypoints()?,
+                        cache.as_ref(),
+                        Duration::from_secs(watch_interval_secs.unwrap_or(300)),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+
                 let map = scan_dir_archive(start_path, archive_content.to_owned())?;
+                let epoch_history = if *verify {
+                    Some(verify_archive::epoch_history_from_dir(
+                        start_path,
+                        &self.trusted_waypoints()?,
+                    )?)
+                } else {
+                    None
+                };
 
+                let cache = self.archive_cache()?;
+
+                let pool = try_db_connection_pool(self).await?;
+                neo4j_init::maybe_create_indexes(&pool).await?;
+
+                ingest_all(
+                    &map,
+                    &pool,
+                    self.clear_queue,
+                    batch_size.unwrap_or(250),
+                    epoch_history.as_ref(),
+                    cache.as_ref(),
+                )
+                .await?;
+            }
+            Sub::LoadBlocks {
+                start_path,
+                batch_size,
+            } => {
                 let pool = try_db_connection_pool(self).await?;
                 neo4j_init::maybe_create_indexes(&pool).await?;
 
-                ingest_all(&map, &pool, self.clear_queue, batch_size.unwrap_or(250)).await?;
+                let merged =
+                    load_blocks::load_blocks_from_dir(start_path, &pool, batch_size.unwrap_or(250))
+                        .await?;
+                println!("SUCCESS: blocks merged: {}", merged);
             }
             Sub::IngestOne {
                 archive_dir,
                 batch_size,
+                epoch_archive_dir,
             } => {
+                // the cache key is of the archive as found, before decompressing
+                let cache = self.archive_cache()?;
+                let cache_key = match &cache {
+                    Some(_) => Some(archive_content_hash(archive_dir)?),
+                    None => None,
+                };
+
                 info!("checking if we need to decompress");
                 let (archive_dir, temp) = unzip_temp::maybe_handle_gz(archive_dir)?;
                 let mut man = ManifestInfo::new(&archive_dir);
@@ -165,17 +454,170 @@ impl WarehouseCli {
                 let pool = try_db_connection_pool(self).await?;
                 neo4j_init::maybe_create_indexes(&pool).await?;
 
-                try_load_one_archive(&man, &pool, batch_size.unwrap_or(250)).await?;
+                try_load_one_archive(
+                    &man,
+                    &pool,
+                    batch_size.unwrap_or(250),
+                    cache.as_ref().zip(cache_key),
+                )
+                .await?;
+
+                if let Some(p) = epoch_archive_dir {
+                    let history =
+                        verify_archive::epoch_history_from_dir(p, &self.trusted_waypoints()?)?;
+                    let v = verify_archive::verify_archive(&man, &history).await;
+                    queue::update_verification(
+                        &pool,
+                        &man.archive_id,
+                        v.verified,
+                        v.verified_by.map(|b| b.label()),
+                        v.ledger_version,
+                    )
+                    .await?;
+                    println!("{:#}", json!(&v));
+                }
                 drop(temp);
             }
-            Sub::Check { archive_dir } => {
+            Sub::IngestGenesis {
+                recovery_json,
+                batch_size,
+            } => {
+                let snaps = extract_genesis::extract_genesis_recovery(recovery_json)?;
+                let pool = try_db_connection_pool(self).await?;
+                neo4j_init::maybe_create_indexes(&pool).await?;
+
+                let archive_id = format!(
+                    "genesis_{}",
+                    recovery_json
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_default()
+                );
+                let res =
+                    snapshot_batch(&snaps, &pool, batch_size.unwrap_or(250), &archive_id).await?;
+                println!("SUCCESS: {}", res);
+            }
+            Sub::Check {
+                archive_dir,
+                deep,
+                verify,
+            } => {
                 let am = scan_dir_archive(archive_dir, None)?;
                 if am.0.is_empty() {
                     error!("cannot find .manifest file under {}", archive_dir.display());
                 }
+                let epoch_history = if *verify {
+                    Some(EpochHistory::from_archive_map(
+                        &am,
+                        &self.trusted_waypoints()?,
+                    )?)
+                } else {
+                    None
+                };
+
+                let mut reports = vec![];
+                let mut verifications = vec![];
                 for (p, man) in am.0 {
                     info!("manifest found at {} \n {:?}", p.display(), man);
+                    if *deep {
+                        reports.push(check_archive::deep_check_archive(&man).await);
+                    }
+                    if let Some(history) = &epoch_history {
+                        if man.contents == BundleContent::EpochEnding {
+                            continue;
+                        }
+                        let (unzipped, temp) = unzip_temp::maybe_handle_gz(&man.archive_dir)?;
+                        let mut unzipped_man = ManifestInfo::new(&unzipped);
+                        unzipped_man.set_info()?;
+                        verifications
+                            .push(verify_archive::verify_archive(&unzipped_man, history).await);
+                        drop(temp);
+                    }
+                }
+
+                if *verify {
+                    println!("{:#}", json!(&verifications));
+                    let failed = verifications.iter().filter(|v| v.error.is_some()).count();
+                    let untrusted = verifications
+                        .iter()
+                        .filter(|v| v.verified_by == Some(VerifiedBy::ManifestWaypoint))
+                        .count();
+                    if untrusted > 0 {
+                        warn!(
+                            "{} of {} archives are only rooted in manifest waypoints, pass --trusted-waypoint",
+                            untrusted,
+                            verifications.len()
+                        );
+                    }
+                    if failed > 0 {
+                        bail!(
+                            "{} of {} archives failed verification",
+                            failed,
+                            verifications.len()
+                        );
+                    }
                 }
+
+                if *deep {
+                    println!("{:#}", json!(&reports));
+                    let failed = reports
+                        .iter()
+                        .filter(|r| r.status == CheckStatus::Fail)
+                        .count();
+                    if failed > 0 {
+                        bail!("{} of {} archives failed checks", failed, reports.len());
+                    }
+                }
+            }
+            Sub::Coverage {
+                start_path,
+                with_queue,
+            } => {
+                let map = scan_dir_archive(start_path, None)?;
+                let mut reports = coverage::coverage_all(&map);
+
+                if *with_queue {
+                    let pool = try_db_connection_pool(self).await?;
+                    for r in reports.iter_mut() {
+                        coverage::set_loaded_from_queue(r, &pool).await?;
+                    }
+                }
+
+                for r in &reports {
+                    if r.issues.is_empty() {
+                        info!("{}: no gaps or overlaps found", r.contents);
+                    } else {
+                        warn!("{}: issues found: {}", r.contents, r.issues.len());
+                    }
+                }
+                println!("{:#}", json!(&reports));
+            }
+            Sub::Inspect {
+                archive_dir,
+                output,
+                address,
+                function,
+                start_version,
+                end_version,
+            } => {
+                let filter = InspectFilter {
+                    address: address
+                        .as_deref()
+                        .map(util::parse_address_any_string)
+                        .transpose()?,
+                    function: function.to_owned(),
+                    start_version: *start_version,
+                    end_version: *end_version,
+                };
+
+                let mut writer: Box<dyn Write + Send> = match output {
+                    Some(p) => Box::new(BufWriter::new(File::create(p)?)),
+                    None => Box::new(BufWriter::new(io::stdout())),
+                };
+
+                let count =
+                    inspect_archive::inspect_archive(archive_dir, &filter, &mut writer).await?;
+                info!("records written: {}", count);
             }
             Sub::EnrichExchange {
                 exchange_json: swap_record_json,
@@ -205,6 +647,15 @@ impl WarehouseCli {
 
                 println!("SUCCESS: {} exchange onramp accounts linked", owners_merged);
             }
+            Sub::ClassifyAccounts { exchange_json } => {
+                let exchange_wallets = match exchange_json {
+                    Some(p) => ExchangeWallet::parse_json_file(p)?,
+                    None => vec![],
+                };
+                let pool = try_db_connection_pool(self).await?;
+                let results = classify_accounts::classify_all(&pool, &exchange_wallets).await?;
+                println!("{:#}", json!(&results));
+            }
             Sub::EnrichWhitepages {
                 owner_json: json_file,
             } => {
@@ -216,6 +667,40 @@ impl WarehouseCli {
 
                 println!("SUCCESS: {} owner accounts linked", owners_merged);
             }
+            Sub::SyncRpc {
+                url,
+                start_version,
+                max_versions,
+                batch_size,
+                framework_version,
+            } => {
+                let pool = try_db_connection_pool(self).await?;
+                neo4j_init::maybe_create_indexes(&pool).await?;
+
+                let client = RpcClient::new(url);
+                let res = sync_rpc::sync_rpc(
+                    &client,
+                    &pool,
+                    *start_version,
+                    *max_versions,
+                    batch_size.unwrap_or(250),
+                    framework_version.as_ref().unwrap_or(&FrameworkVersion::V7),
+                )
+                .await?;
+                println!("SUCCESS: {}", res);
+            }
+            Sub::VersionFiveRpc {
+                response_path,
+                batch_size,
+            } => {
+                let pool = try_db_connection_pool(self).await?;
+                neo4j_init::maybe_create_indexes(&pool).await?;
+
+                let res =
+                    v5_rpc_to_raw::load_v5_rpc(response_path, &pool, batch_size.unwrap_or(250))
+                        .await?;
+                println!("SUCCESS: {}", res);
+            }
             Sub::VersionFiveTx { archive_dir } => {
                 let pool = try_db_connection_pool(self).await?;
 
@@ -226,6 +711,21 @@ impl WarehouseCli {
                 )
                 .await?;
             }
+            Sub::VersionFiveMiners {
+                archive_dir,
+                batch_size,
+            } => {
+                let pool = try_db_connection_pool(self).await?;
+                neo4j_init::maybe_create_indexes(&pool).await?;
+
+                let merged = v5_miner_history::load_v5_miner_history(
+                    archive_dir,
+                    &pool,
+                    batch_size.unwrap_or(250),
+                )
+                .await?;
+                println!("SUCCESS: miner epochs merged: {}", merged);
+            }
             Sub::Analytics(analytics_sub) => match analytics_sub {
                 AnalyticsSub::ExchangeRMS { persist } => {
                     if *persist {
@@ -238,6 +738,91 @@ impl WarehouseCli {
                     .await?;
                     println!("{:#}", json!(&results).to_string());
                 }
+                AnalyticsSub::ForkContinuity {
+                    v5_archive,
+                    v6_archive,
+                    tolerance,
+                    persist,
+                } => {
+                    let v5 = decode_snapshot_archive(v5_archive).await?;
+                    let v6 = decode_snapshot_archive(v6_archive).await?;
+                    let report = analytics::fork_continuity::compare_fork(
+                        &v5,
+                        &v6,
+                        tolerance.unwrap_or(analytics::fork_continuity::DEFAULT_TOLERANCE),
+                    );
+
+                    if *persist {
+                        let pool = try_db_connection_pool(self).await?;
+                        analytics::fork_continuity::persist_continuity_links(
+                            &pool,
+                            &report.links,
+                            1000,
+                        )
+                        .await?;
+                    }
+                    println!("{:#}", json!(&report));
+                }
+                AnalyticsSub::Descendants { address, max_depth } => {
+                    let address = util::parse_address_any_string(address)?;
+                    let pool = try_db_connection_pool(self).await?;
+                    let descendants =
+                        analytics::ancestry::get_descendants(&pool, address, *max_depth).await?;
+                    println!("{:#}", json!(&descendants));
+                }
+                AnalyticsSub::Reconcile {
+                    start_version,
+                    end_version,
+                    framework_version,
+                    tolerance,
+                    csv_out,
+                    persist,
+                } => {
+                    if start_version >= end_version {
+                        bail!("--start-version must be lower than --end-version");
+                    }
+                    let pool = try_db_connection_pool(self).await?;
+                    let report = analytics::reconcile::reconcile(
+                        &pool,
+                        framework_version.as_ref().unwrap_or(&FrameworkVersion::V7),
+                        *start_version,
+                        *end_version,
+                        tolerance.unwrap_or(analytics::reconcile::DEFAULT_TOLERANCE),
+                        *persist,
+                    )
+                    .await?;
+
+                    let csv_path = csv_out.clone().unwrap_or_else(|| {
+                        PathBuf::from(format!("reconcile_{}_{}.csv", start_version, end_version))
+                    });
+                    analytics::reconcile::write_csv(&report.discrepancies, &csv_path)?;
+                    println!(
+                        "{:#}",
+                        json!({
+                            "start_version": report.start_version,
+                            "end_version": report.end_version,
+                            "accounts": report.accounts,
+                            "discrepancies": report.discrepancies.len(),
+                            "total_unexplained": report.total_unexplained,
+                            "csv": csv_path,
+                        })
+                    );
+                }
+                AnalyticsSub::SlowWalletCompliance {
+                    tolerance,
+                    relay_min_ratio,
+                    relay_max_versions,
+                } => {
+                    let pool = try_db_connection_pool(self).await?;
+                    let report = analytics::slow_wallet_compliance::slow_wallet_compliance(
+                        &pool,
+                        tolerance.unwrap_or(analytics::slow_wallet_compliance::DEFAULT_TOLERANCE),
+                        relay_min_ratio.unwrap_or(0.9),
+                        relay_max_versions.unwrap_or(100_000),
+                    )
+                    .await?;
+                    println!("{:#}", json!(&report));
+                }
                 AnalyticsSub::TradesMatching {
                     replay_balances,
                     match_simple_dumps,
@@ -285,9 +870,53 @@ impl WarehouseCli {
                     println!("{:#}", json!(&m.definite));
                 }
             },
+            Sub::Cache(cache_sub) => {
+                let Some(cache) = self.archive_cache()? else {
+                    bail!("no cache directory, call with --cache-dir");
+                };
+                match cache_sub {
+                    CacheSub::Stats => {
+                        println!("{:#}", json!(&cache.stats()?));
+                    }
+                    CacheSub::Prune {
+                        older_than_days,
+                        max_size_mb,
+                    } => {
+                        if older_than_days.is_none() && max_size_mb.is_none() {
+                            bail!("nothing to do. Must enter --older-than-days or --max-size-mb")
+                        }
+                        let res = cache.prune(
+                            older_than_days.map(|d| Duration::from_secs(d * 24 * 60 * 60)),
+                            max_size_mb.map(|mb| mb * 1024 * 1024),
+                        )?;
+                        println!("{:#}", json!(&res));
+                    }
+                }
+            }
         };
         Ok(())
     }
+
+    fn archive_cache(&self) -> Result<Option<ArchiveCache>> {
+        self.cache_dir.as_deref().map(ArchiveCache::new).transpose()
+    }
+
+    fn trusted_waypoints(&self) -> Result<Vec<Waypoint>> {
+        verify_archive::parse_waypoints(&self.trusted_waypoint)
+    }
+}
+
+/// decode the account states of a snapshot archive, which may be gzipped
+async fn decode_snapshot_archive(archive_dir: &Path) -> Result<Vec<WarehouseAccState>> {
+    let (unzipped, temp) = unzip_temp::maybe_handle_gz(archive_dir)?;
+    let mut man = ManifestInfo::new(&unzipped);
+    man.set_info()?;
+    if man.contents != BundleContent::StateSnapshot {
+        bail!("not a state snapshot archive: {}", archive_dir.display());
+    }
+    let decoded = decode_archive(&man).await?;
+    drop(temp);
+    Ok(decoded.snaps)
 }
 
 pub async fn try_db_connection_pool(cli: &WarehouseCli) -> Result<Graph> {
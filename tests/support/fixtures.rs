@@ -5,6 +5,11 @@ pub fn v5_fixtures_path() -> PathBuf {
     p.join("tests/fixtures/v5")
 }
 
+pub fn v6_fixtures_path() -> PathBuf {
+    let p = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    p.join("tests/fixtures/v6")
+}
+
 pub fn v7_fixtures_path() -> PathBuf {
     let p = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     p.join("tests/fixtures/v7")
@@ -73,3 +78,14 @@ pub fn v6_tx_manifest_fixtures_path() -> PathBuf {
     );
     dir
 }
+
+pub fn v5_final_epoch_state_fixtures_path() -> PathBuf {
+    let p = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dir = p.join("tests/fixtures/v5_final_epoch/state_ver_141722729.0ab2");
+    assert!(
+        &dir.exists(),
+        "fixtures for backup archive cannot be found at path {}",
+        &dir.display()
+    );
+    dir
+}
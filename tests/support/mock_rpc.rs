@@ -0,0 +1,51 @@
+//! a minimal http server to serve recorded fullnode REST responses
+use std::sync::Arc;
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+/// content type and body for a request path (including the query string)
+pub type MockHandler = dyn Fn(&str) -> Option<(&'static str, Vec<u8>)> + Send + Sync;
+
+/// start serving on a random local port, returns the base url e.g. http://127.0.0.1:1234/v1
+/// Paths the handler does not know get a 404.
+pub async fn start_mock_rpc(handler: Arc<MockHandler>) -> anyhow::Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let mut req = vec![];
+                let mut buf = [0u8; 4096];
+                while !req.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => req.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let req = String::from_utf8_lossy(&req).to_string();
+                let path = req.split_whitespace().nth(1).unwrap_or("/");
+
+                let (status, content_type, body) = match handler(path) {
+                    Some((ct, body)) => ("200 OK", ct, body),
+                    None => ("404 Not Found", "text/plain", b"not found".to_vec()),
+                };
+                let header = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    content_type,
+                    body.len()
+                );
+                let _ = socket.write_all(header.as_bytes()).await;
+                let _ = socket.write_all(&body).await;
+                let _ = socket.shutdown().await;
+            });
+        }
+    });
+
+    Ok(format!("http://{}/v1", addr))
+}
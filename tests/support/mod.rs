@@ -1,3 +1,5 @@
 #[allow(dead_code)]
 pub mod fixtures;
+#[allow(dead_code)]
+pub mod mock_rpc;
 pub mod neo4j_testcontainer;
@@ -0,0 +1,51 @@
+mod support;
+
+use libra_forensic_db::{
+    archive_cache::{archive_content_hash, ArchiveCache},
+    load::decode_archive,
+    scan::ManifestInfo,
+    schema_transaction::WarehouseTxMaster,
+};
+
+#[tokio::test]
+async fn test_cache_roundtrip() -> anyhow::Result<()> {
+    let archive_path = support::fixtures::v6_tx_manifest_fixtures_path();
+    let mut man = ManifestInfo::new(&archive_path);
+    man.set_info()?;
+
+    let temp = diem_temppath::TempPath::new();
+    temp.create_as_dir()?;
+    let cache = ArchiveCache::new(temp.path())?;
+
+    let key = archive_content_hash(&archive_path)?;
+    // the key only depends on the files
+    assert!(key == archive_content_hash(&archive_path)?);
+    assert!(cache.get(&key).is_none());
+
+    let decoded = decode_archive(&man).await?;
+    assert!(decoded.txs.len() == 27);
+    cache.put(&key, &decoded)?;
+
+    let cached = cache.get(&key).expect("no cache entry");
+    assert!(cached.archive_id == decoded.archive_id);
+    assert!(cached.txs.len() == decoded.txs.len());
+    assert!(cached.txs[0].tx_hash == decoded.txs[0].tx_hash);
+    assert!(cached.txs[0].version == decoded.txs[0].version);
+    // event data is json, which is stored as a string in the binary entry
+    let events_json = |txs: &[WarehouseTxMaster]| serde_json::to_string(&txs[0].events);
+    assert!(events_json(&cached.txs)? == events_json(&decoded.txs)?);
+
+    let stats = cache.stats()?;
+    assert!(stats.entries == 1);
+    assert!(stats.total_bytes > 0);
+
+    // nothing is old enough to be removed
+    let res = cache.prune(Some(std::time::Duration::from_secs(3600)), None)?;
+    assert!(res.removed == 0);
+
+    let res = cache.prune(None, Some(0))?;
+    assert!(res.removed == 1);
+    assert!(cache.get(&key).is_none());
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+mod support;
+
+use libra_forensic_db::{
+    check_archive::{check_transaction_archive, deep_check_archive, CheckStatus},
+    scan::scan_dir_archive,
+};
+
+#[tokio::test]
+async fn test_check_tx_archive() -> anyhow::Result<()> {
+    let archive_path = support::fixtures::v6_tx_manifest_fixtures_path();
+    let (chunks, errors) = check_transaction_archive(&archive_path).await?;
+
+    assert!(chunks == 1);
+    assert!(errors.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_deep_check_report() -> anyhow::Result<()> {
+    let archive_path = support::fixtures::v6_tx_manifest_fixtures_path();
+    let archive = scan_dir_archive(&archive_path, None)?;
+    let (_, man) = archive.0.first_key_value().unwrap();
+
+    let report = deep_check_archive(man).await;
+    assert!(report.status == CheckStatus::Pass);
+    assert!(report.archive_id == "transaction_9900001-.e469");
+
+    // the report is serializable for other tools
+    let s = serde_json::to_string(&report)?;
+    assert!(s.contains("\"status\":\"Pass\""));
+
+    Ok(())
+}
@@ -0,0 +1,168 @@
+mod support;
+
+use diem_types::account_address::AccountAddress;
+use libra_forensic_db::{
+    classify_accounts::{classify_all, AccountClass, ExchangeWallet},
+    load_account_state::snapshot_batch,
+    neo4j_init::{get_neo4j_localhost_pool, maybe_create_indexes},
+    scan::FrameworkVersion,
+    schema_account_state::{DonorVoiceState, ValidatorState, WarehouseAccState},
+};
+use support::neo4j_testcontainer::start_neo4j_container;
+
+#[tokio::test]
+async fn test_classify_accounts() -> anyhow::Result<()> {
+    libra_forensic_db::log_setup();
+
+    let alice = AccountAddress::from_hex_literal("0xa11ce")?;
+    let bob = AccountAddress::from_hex_literal("0xb0b")?;
+    let carol = AccountAddress::from_hex_literal("0xca401")?;
+
+    let snap = |address: AccountAddress, framework_version: FrameworkVersion, version: u64| {
+        let mut s = WarehouseAccState::new(address);
+        s.time.framework_version = framework_version;
+        s.time.version = version;
+        s
+    };
+
+    // alice was a slow wallet at V5 version 900, and V6 versions 10 and 20, but not 30
+    let mut vec_snap = vec![];
+    for (framework_version, version, slow) in [
+        (FrameworkVersion::V5, 900, true),
+        (FrameworkVersion::V6, 10, true),
+        (FrameworkVersion::V6, 20, true),
+        (FrameworkVersion::V6, 30, false),
+    ] {
+        let mut s = snap(alice, framework_version, version);
+        s.slow_wallet_acc = slow;
+        vec_snap.push(s);
+    }
+    // bob is a validator operated by carol
+    let mut s = snap(bob, FrameworkVersion::V6, 20);
+    s.validator = Some(ValidatorState {
+        operator: Some(carol),
+        ..Default::default()
+    });
+    vec_snap.push(s);
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let graph = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&graph)
+        .await
+        .expect("could start index");
+
+    snapshot_batch(&vec_snap, &graph, 10, "test_classify").await?;
+
+    let exchange_wallets: Vec<ExchangeWallet> =
+        serde_json::from_str(r#"[{"address": "0xca401", "exchange": "an \"exchange\""}]"#)?;
+    let results = classify_all(&graph, &exchange_wallets).await?;
+    assert!(results.get(&AccountClass::SlowWallet) == Some(&1));
+    assert!(results.get(&AccountClass::Validator) == Some(&1));
+    assert!(results.get(&AccountClass::Operator) == Some(&1));
+    assert!(results.get(&AccountClass::ExchangeWallet) == Some(&1));
+    assert!(results.get(&AccountClass::Miner) == Some(&0));
+
+    let cypher_query = neo4rs::query(
+        "MATCH (a:SlowWallet)
+         RETURN a.address AS address,
+           a.slow_wallet_from_framework_version AS from_framework_version,
+           a.slow_wallet_from_version AS from,
+           a.slow_wallet_to_framework_version AS to_framework_version,
+           a.slow_wallet_to_version AS to",
+    );
+    let mut result = graph.execute(cypher_query).await?;
+    let row = result.next().await?.unwrap();
+    let address: String = row.get("address").unwrap();
+    let from_framework_version: String = row.get("from_framework_version").unwrap();
+    let from: i64 = row.get("from").unwrap();
+    let to_framework_version: String = row.get("to_framework_version").unwrap();
+    let to: i64 = row.get("to").unwrap();
+    assert!(address == alice.to_hex_literal());
+    // not the min and max of the versions, which mix the eras
+    assert!(from_framework_version == "V5");
+    assert!(from == 900);
+    assert!(to_framework_version == "V6");
+    assert!(to == 20);
+
+    let cypher_query = neo4rs::query(
+        "MATCH (a:Operator:ExchangeWallet)
+         RETURN a.address AS address, a.exchange AS exchange",
+    );
+    let mut result = graph.execute(cypher_query).await?;
+    let row = result.next().await?.unwrap();
+    let address: String = row.get("address").unwrap();
+    let exchange: String = row.get("exchange").unwrap();
+    assert!(address == carol.to_hex_literal());
+    assert!(exchange == "an \"exchange\"");
+
+    // the configuration changed, carol is not an exchange wallet any more
+    let exchange_wallets: Vec<ExchangeWallet> =
+        serde_json::from_str(r#"[{"address": "0xa11ce", "exchange": "another"}]"#)?;
+    let results = classify_all(&graph, &exchange_wallets).await?;
+    assert!(results.get(&AccountClass::ExchangeWallet) == Some(&1));
+
+    let cypher_query = neo4rs::query(
+        "MATCH (a:ExchangeWallet)
+         RETURN collect(a.address) AS addresses",
+    );
+    let mut result = graph.execute(cypher_query).await?;
+    let row = result.next().await?.unwrap();
+    let addresses: Vec<String> = row.get("addresses").unwrap();
+    assert!(addresses == vec![alice.to_hex_literal()]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_classify_keeps_loader_labels() -> anyhow::Result<()> {
+    libra_forensic_db::log_setup();
+
+    let wallet = AccountAddress::from_hex_literal("0xc0ffee")?;
+    let alice = AccountAddress::from_hex_literal("0xa11ce")?;
+
+    // the governance was found, but the snapshot is not flagged as donor voice
+    let mut dv = WarehouseAccState::new(wallet);
+    dv.time.framework_version = FrameworkVersion::V7;
+    dv.time.version = 10;
+    dv.donor_voice = Some(DonorVoiceState {
+        authorities: vec![alice],
+        threshold: 1,
+        ..Default::default()
+    });
+    let mut val = WarehouseAccState::new(alice);
+    val.time.framework_version = FrameworkVersion::V7;
+    val.time.version = 10;
+    val.validator = Some(ValidatorState::default());
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let graph = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&graph)
+        .await
+        .expect("could start index");
+
+    snapshot_batch(&[dv, val], &graph, 10, "test_classify_loader_labels").await?;
+
+    let results = classify_all(&graph, &[]).await?;
+    assert!(results.get(&AccountClass::DonorVoice) == Some(&1));
+    assert!(results.get(&AccountClass::Validator) == Some(&1));
+
+    let cypher_query = neo4rs::query(
+        "OPTIONAL MATCH (dv:DonorVoice {address: '0xc0ffee'})
+         OPTIONAL MATCH (v:Validator {address: '0xa11ce'})
+         RETURN dv.donor_voice_from_version AS from, v IS NOT NULL AS validator",
+    );
+    let mut result = graph.execute(cypher_query).await?;
+    let row = result.next().await?.unwrap();
+    let from: i64 = row.get("from").unwrap();
+    let validator: bool = row.get("validator").unwrap();
+    assert!(from == 10);
+    assert!(validator);
+
+    Ok(())
+}
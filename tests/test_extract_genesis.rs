@@ -0,0 +1,30 @@
+mod support;
+
+use libra_forensic_db::{extract_genesis::extract_genesis_recovery, scan::FrameworkVersion};
+
+#[test]
+fn test_extract_genesis_recovery() -> anyhow::Result<()> {
+    let path = support::fixtures::v6_fixtures_path().join("genesis_recovery_sample.json");
+    let snaps = extract_genesis_recovery(&path)?;
+
+    // the record without an account is skipped
+    assert!(snaps.len() == 2);
+    assert!(snaps
+        .iter()
+        .all(|s| s.time.version == 0 && s.time.framework_version == FrameworkVersion::V6));
+
+    let end_user = &snaps[0];
+    // balances are rebased from V5
+    assert!(end_user.balance == 35.0);
+    assert!(end_user.slow_wallet_acc);
+    assert!(end_user.slow_wallet_unlocked == Some(17.5));
+    assert!(end_user.validator.is_none());
+
+    let validator = &snaps[1];
+    assert!(validator.miner_height == Some(1234));
+    assert!(validator.donor_voice_acc);
+    // the role marks genesis validators
+    assert!(validator.validator.is_some());
+
+    Ok(())
+}
@@ -1,5 +1,7 @@
 mod support;
 
+use std::collections::HashSet;
+
 use anyhow::Result;
 use libra_forensic_db::extract_snapshot::{extract_current_snapshot, extract_v5_snapshot};
 use support::fixtures::{v5_state_manifest_fixtures_path, v7_state_manifest_fixtures_path};
@@ -31,3 +33,97 @@ async fn test_extract_v7_manifest() -> Result<()> {
     assert!(s.len() == 24607);
     Ok(())
 }
+
+#[tokio::test]
+async fn test_extract_v7_vouches() -> Result<()> {
+    let archive_dir = v7_state_manifest_fixtures_path();
+    let s = extract_current_snapshot(&archive_dir).await?;
+
+    // the vouch resources are decoded, not skipped
+    let receiving = s.iter().filter(|a| !a.vouches_received.is_empty()).count();
+    let giving = s.iter().filter(|a| !a.vouches_given.is_empty()).count();
+    assert!(receiving > 0);
+    assert!(giving > 0);
+
+    // a decoded layout gives sensible epochs, not the bytes of another field
+    let epoch = s.first().unwrap().time.epoch;
+    assert!(epoch == 116);
+    assert!(s
+        .iter()
+        .flat_map(|a| a.vouches_received.iter().chain(a.vouches_given.iter()))
+        .all(|v| v.epoch_vouched <= epoch));
+
+    // the vouches given by one account are received by the other
+    let received: HashSet<_> = s
+        .iter()
+        .flat_map(|r| {
+            r.vouches_received
+                .iter()
+                .map(move |v| (v.address, r.address))
+        })
+        .collect();
+    let matched = s
+        .iter()
+        .flat_map(|g| g.vouches_given.iter().map(move |v| (g.address, v.address)))
+        .filter(|pair| received.contains(pair))
+        .count();
+    assert!(matched > 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_extract_v7_donor_voice() -> Result<()> {
+    let archive_dir = v7_state_manifest_fixtures_path();
+    let s = extract_current_snapshot(&archive_dir).await?;
+
+    // every donor voice account has its state decoded
+    let dv: Vec<_> = s.iter().filter(|a| a.donor_voice_acc).collect();
+    assert!(!dv.is_empty());
+    assert!(dv.iter().all(|a| a.donor_voice.is_some()));
+
+    // the MultiActionGovernanceResource with the PropGovSigners type param
+    let with_authorities: Vec<_> = dv
+        .iter()
+        .filter_map(|a| a.donor_voice.as_ref())
+        .filter(|d| !d.authorities.is_empty())
+        .collect();
+    assert!(!with_authorities.is_empty());
+    assert!(with_authorities
+        .iter()
+        .all(|d| d.threshold > 0 && d.threshold <= d.authorities.len() as u64));
+
+    // the TxSchedule and its TimedTransfer payments
+    let payments: Vec<_> = dv
+        .iter()
+        .filter_map(|a| a.donor_voice.as_ref())
+        .flat_map(|d| d.payments.iter())
+        .collect();
+    assert!(!payments.is_empty());
+    // the deadline is an epoch, a decoded layout does not give bytes of another field
+    assert!(payments.iter().all(|p| p.deadline > 0 && p.value >= 0.0));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_extract_v7_validators() -> Result<()> {
+    let archive_dir = v7_state_manifest_fixtures_path();
+    let s = extract_current_snapshot(&archive_dir).await?;
+
+    let validators: Vec<_> = s.iter().filter_map(|a| a.validator.as_ref()).collect();
+    assert!(!validators.is_empty());
+
+    // the ValidatorConfig, StakePool and ValidatorUniverse resources are decoded
+    let indexed: Vec<_> = validators
+        .iter()
+        .filter(|v| v.in_universe && v.validator_index.is_some())
+        .collect();
+    assert!(!indexed.is_empty());
+    assert!(indexed.iter().all(|v| v.operator.is_some()));
+
+    // the ProofOfFeeAuction bids
+    assert!(validators.iter().any(|v| v.bid.is_some()));
+
+    Ok(())
+}
@@ -1,7 +1,9 @@
 mod support;
 
 use libra_forensic_db::{
-    extract_transactions::extract_current_transactions, scan::FrameworkVersion,
+    extract_transactions::{extract_blocks, extract_current_transactions},
+    load::decode_archive,
+    scan::{scan_dir_archive, BundleContent, FrameworkVersion},
 };
 
 #[tokio::test]
@@ -24,3 +26,46 @@ async fn test_extract_v6_tx_from_archive() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_decode_v6_archive_operators() -> anyhow::Result<()> {
+    let archive_path = support::fixtures::v6_tx_manifest_fixtures_path();
+    let map = scan_dir_archive(&archive_path, Some(BundleContent::Transaction))?;
+    let (_, man) = map.0.first_key_value().unwrap();
+    // V6 archives are labeled V7, the operators fall back to the V6 builder
+    assert!(man.version == FrameworkVersion::V7);
+
+    let decoded = decode_archive(man).await?;
+    assert!(decoded.txs.len() == 27);
+    assert!(!decoded.operators.is_empty());
+    assert!(decoded
+        .operators
+        .iter()
+        .all(|o| o.framework_version == man.version));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_extract_blocks() -> anyhow::Result<()> {
+    let archive_path = support::fixtures::v6_tx_manifest_fixtures_path();
+    let blocks = extract_blocks(&archive_path, &FrameworkVersion::V6).await?;
+    assert!(blocks
+        .iter()
+        .all(|b| b.framework_version == FrameworkVersion::V6));
+    assert!(!blocks.is_empty());
+
+    // blocks are sequential and don't overlap
+    for pair in blocks.windows(2) {
+        assert!(pair[0].height < pair[1].height);
+        assert!(pair[0].last_version < pair[1].version);
+    }
+    assert!(blocks.iter().all(|b| b.last_version >= b.version));
+
+    // every user transaction extracted is in a block
+    let (txs, _) = extract_current_transactions(&archive_path, &FrameworkVersion::V6).await?;
+    let user_txs: u64 = blocks.iter().map(|b| b.user_txs).sum();
+    assert!(user_txs >= txs.len() as u64);
+
+    Ok(())
+}
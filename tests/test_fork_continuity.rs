@@ -0,0 +1,56 @@
+mod support;
+
+use libra_forensic_db::{
+    analytics::fork_continuity::{compare_fork, ContinuityIssue, DEFAULT_TOLERANCE},
+    extract_genesis::extract_genesis_recovery,
+    extract_snapshot::extract_v5_snapshot,
+    scan::FrameworkVersion,
+};
+
+#[tokio::test]
+async fn test_fork_continuity_from_fixtures() -> anyhow::Result<()> {
+    let v5_path = support::fixtures::v5_final_epoch_state_fixtures_path();
+    // the first V6 state is the genesis, from the recovery file
+    let genesis_path = support::fixtures::v6_fixtures_path().join("genesis_recovery_sample.json");
+
+    let v5 = extract_v5_snapshot(&v5_path).await?;
+    let v6 = extract_genesis_recovery(&genesis_path)?;
+    assert!(!v5.is_empty());
+    assert!(v6.len() == 2);
+
+    let report = compare_fork(&v5, &v6, DEFAULT_TOLERANCE);
+    assert!(report.v5_accounts == v5.len() as u64);
+    assert!(report.v6_accounts == 2);
+    // both sample accounts were created before the fork
+    assert!(report.only_in_v6 == 0);
+    assert!(report.links.len() == 2);
+    assert!(report.links.iter().all(|l| l.v6_version == 0));
+    // links match the genesis snapshots by their era
+    assert!(report
+        .links
+        .iter()
+        .all(|l| l.v6_framework_version == FrameworkVersion::V6));
+
+    // the sample balances are not the final V5 balances
+    let mismatched = report
+        .issues
+        .iter()
+        .filter(|i| matches!(i, ContinuityIssue::BalanceMismatch { .. }))
+        .count();
+    assert!(mismatched == 2);
+    assert!(report.links.iter().filter(|l| !l.balance_ok).count() == 2);
+
+    // the accounts missing from the sample are all reported
+    let missing = report
+        .issues
+        .iter()
+        .filter(|i| matches!(i, ContinuityIssue::MissingInV6 { .. }))
+        .count();
+    let v5_with_balance = v5
+        .iter()
+        .filter(|s| s.balance > 0.0 && !v6.iter().any(|g| g.address == s.address))
+        .count();
+    assert!(missing == v5_with_balance);
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+mod support;
+
+use libra_forensic_db::{
+    extract_transactions::extract_current_transactions,
+    inspect_archive::{inspect_archive, write_ndjson, InspectFilter},
+    scan::FrameworkVersion,
+};
+
+#[tokio::test]
+async fn test_inspect_tx_ndjson() -> anyhow::Result<()> {
+    let archive_path = support::fixtures::v6_tx_manifest_fixtures_path();
+    let (txs, events) = extract_current_transactions(&archive_path, &FrameworkVersion::V6).await?;
+
+    let mut buf: Vec<u8> = vec![];
+    let count = write_ndjson(&txs, &events, &[], &InspectFilter::default(), &mut buf)?;
+    assert!(count == 79);
+
+    let out = String::from_utf8(buf)?;
+    assert!(out.lines().count() == 79);
+    // every line is a json object tagged with the record type
+    let first: serde_json::Value = serde_json::from_str(out.lines().next().unwrap())?;
+    assert!(first["record"] == "Tx");
+
+    // filter on one sender
+    let sender = txs.first().unwrap().sender;
+    let filter = InspectFilter {
+        address: Some(sender),
+        ..Default::default()
+    };
+    let mut buf: Vec<u8> = vec![];
+    let count = write_ndjson(&txs, &events, &[], &filter, &mut buf)?;
+    assert!(count > 0);
+    assert!(count < 79);
+
+    // a version range which excludes everything
+    let filter = InspectFilter {
+        end_version: Some(0),
+        ..Default::default()
+    };
+    let mut buf: Vec<u8> = vec![];
+    let count = write_ndjson(&txs, &events, &[], &filter, &mut buf)?;
+    assert!(count == 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_inspect_v5_json_file() -> anyhow::Result<()> {
+    let path = support::fixtures::v5_json_tx_path().join("example_create_user.json");
+
+    let mut buf: Vec<u8> = vec![];
+    let count = inspect_archive(&path, &InspectFilter::default(), &mut buf).await?;
+    assert!(count == 1);
+
+    let filter = InspectFilter {
+        function: Some("create_user_by_coin_tx".to_string()),
+        ..Default::default()
+    };
+    let mut buf: Vec<u8> = vec![];
+    let count = inspect_archive(&path, &filter, &mut buf).await?;
+    assert!(count == 1);
+
+    Ok(())
+}
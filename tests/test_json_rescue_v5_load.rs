@@ -1,10 +1,13 @@
 mod support;
 
+use diem_types::account_address::AccountAddress;
 use libra_forensic_db::{
     json_rescue_v5_extract::extract_v5_json_rescue,
     json_rescue_v5_load,
     load_tx_cypher::tx_batch,
     neo4j_init::{get_neo4j_localhost_pool, maybe_create_indexes},
+    v5_autopay::{impl_batch_autopay_insert, AutopayDisabled, AutopayInstruction, AutopayType},
+    v5_miner_history::load_v5_miner_history,
 };
 use support::{fixtures, neo4j_testcontainer::start_neo4j_container};
 
@@ -76,6 +79,105 @@ async fn test_load_queue() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_load_miner_history() -> anyhow::Result<()> {
+    libra_forensic_db::log_setup();
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let pool = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&pool)
+        .await
+        .expect("could start index");
+
+    let path = fixtures::v5_json_tx_path();
+
+    let merged = load_v5_miner_history(&path, &pool, 250).await?;
+    assert!(merged > 0);
+
+    // loading again gives the same counts
+    let merged_again = load_v5_miner_history(&path, &pool, 250).await?;
+    assert!(merged == merged_again);
+
+    let cypher_query = neo4rs::query(
+        "MATCH (:Account)-[:Mined]->(me:MinerEpoch)
+         RETURN count(me) AS epochs, sum(me.operator_proofs) AS operator_proofs, sum(me.proofs) AS proofs",
+    );
+    let mut result = pool.execute(cypher_query).await?;
+    let row = result.next().await?.unwrap();
+    let epochs: i64 = row.get("epochs").unwrap();
+    let operator_proofs: i64 = row.get("operator_proofs").unwrap();
+    let proofs: i64 = row.get("proofs").unwrap();
+    assert!(epochs == merged as i64);
+    // all the fixture proofs were submitted by operators
+    assert!(operator_proofs == proofs);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_load_autopay() -> anyhow::Result<()> {
+    libra_forensic_db::log_setup();
+
+    let alice = AccountAddress::from_hex_literal("0xa11ce")?;
+    let bob = AccountAddress::from_hex_literal("0xb0b")?;
+    let carol = AccountAddress::from_hex_literal("0xca401")?;
+    let instruction = |payee: AccountAddress, version: u64| AutopayInstruction {
+        sender: alice,
+        payee,
+        uid: 1,
+        in_type: AutopayType::PercentOfChange,
+        value: 1050,
+        end_epoch: 100,
+        version,
+    };
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let pool = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&pool)
+        .await
+        .expect("could start index");
+
+    // the archives are not loaded in order
+    let disabled = vec![AutopayDisabled {
+        sender: alice,
+        version: 20,
+    }];
+    let merged = impl_batch_autopay_insert(&pool, &[instruction(bob, 10)], &disabled).await?;
+    assert!(merged == 1);
+    // the uid is used again after autopay was disabled
+    let merged =
+        impl_batch_autopay_insert(&pool, &[instruction(carol, 5), instruction(carol, 30)], &[])
+            .await?;
+    assert!(merged == 2);
+
+    let cypher_query = neo4rs::query(
+        "MATCH (:Account)-[rel:Autopay]->(to:Account)
+         RETURN to.address AS payee, rel.version AS version, rel.pct AS pct, rel.cancelled_version AS cancelled
+         ORDER BY version",
+    );
+    let mut result = pool.execute(cypher_query).await?;
+    let mut rows = vec![];
+    while let Some(row) = result.next().await? {
+        let payee: String = row.get("payee").unwrap();
+        let version: u64 = row.get("version").unwrap();
+        let pct: f64 = row.get("pct").unwrap();
+        let cancelled: Option<u64> = row.get("cancelled").unwrap();
+        rows.push((payee, version, pct, cancelled));
+    }
+    assert!(rows.len() == 3);
+    assert!(rows[0] == (carol.to_hex_literal(), 5, 10.5, Some(20)));
+    assert!(rows[1] == (bob.to_hex_literal(), 10, 10.5, Some(20)));
+    assert!(rows[2] == (carol.to_hex_literal(), 30, 10.5, None));
+
+    Ok(())
+}
+
 #[ignore]
 // TODO: not a good test since we skip config tests in default mode
 #[tokio::test]
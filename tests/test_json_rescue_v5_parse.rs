@@ -8,8 +8,14 @@ use libra_backwards_compatibility::{
     },
 };
 use libra_forensic_db::{
-    json_rescue_v5_extract::{decompress_to_temppath, extract_v5_json_rescue},
+    json_rescue_v5_extract::{
+        decode_autopay_v5, decompress_to_temppath, extract_v5_json_rescue,
+        extract_v5_json_rescue_from_str, extract_v5_miner_proofs_from_str, for_each_json_in_tgz,
+        list_all_json_files,
+    },
     schema_transaction::EntryFunctionArgs,
+    v5_autopay::AutopayType,
+    v5_miner_history::aggregate_miner_epochs,
 };
 use support::fixtures;
 
@@ -64,6 +70,34 @@ fn test_rescue_v5_parse_miner_tx() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_rescue_v5_miner_proofs() -> anyhow::Result<()> {
+    let path = fixtures::v5_json_tx_path().join("0-999.json");
+    let json = std::fs::read_to_string(path)?;
+
+    let (segments, boundaries) = extract_v5_miner_proofs_from_str(&json)?;
+    assert!(boundaries.len() == 2);
+    assert!(boundaries[1].epoch == 2);
+    assert!(boundaries[1].version == 1);
+
+    // two operators submitting proofs
+    assert!(segments.len() == 2);
+    let total: u64 = segments.iter().map(|s| s.proofs).sum();
+    assert!(total == 11);
+    assert!(segments.iter().all(|s| s.operator.is_some()));
+    let first = segments.iter().find(|s| s.first_version == 176).unwrap();
+    assert!(first.last_version == 211);
+    assert!(first.proofs == 7);
+    assert!(first.operator.unwrap().to_hex_literal() == "0xc8336044cdf1878d9738ed0a041b235e");
+
+    let (miner_epochs, unresolved) = aggregate_miner_epochs(&segments, &boundaries);
+    assert!(unresolved == 0);
+    assert!(miner_epochs.iter().all(|m| m.epoch == 2));
+    assert!(miner_epochs.iter().map(|m| m.proofs).sum::<u64>() == 11);
+
+    Ok(())
+}
+
 #[test]
 fn test_json_format_example() -> anyhow::Result<()> {
     let p = fixtures::v5_json_tx_path().join("example_create_user.json");
@@ -108,3 +142,60 @@ fn decompress_and_read() {
 
     assert!(first.sender.to_hex_literal() == "0xb31bd7796bc113013a2bf6c3953305fd");
 }
+
+#[test]
+fn stream_json_from_tgz() -> anyhow::Result<()> {
+    let path = fixtures::v5_json_tx_path().join("0-99900.tgz");
+
+    let mut names = vec![];
+    let mut tx_count = 0;
+    for_each_json_in_tgz(&path, |file_name, json| {
+        if file_name == "10000-10999.json" {
+            let (tx, _, _) = extract_v5_json_rescue_from_str(&json)?;
+            tx_count = tx.len();
+        }
+        names.push(file_name);
+        Ok(())
+    })?;
+
+    assert!(tx_count == 4);
+
+    // same files as when decompressing to disk
+    let temp_dir = decompress_to_temppath(&path)?;
+    let on_disk = list_all_json_files(temp_dir.path())?;
+    assert!(names.len() == on_disk.len());
+
+    Ok(())
+}
+
+#[test]
+fn test_decode_autopay_v5() -> anyhow::Result<()> {
+    let path = fixtures::v5_json_tx_path().join("example_autopay_tx.json");
+    let json = std::fs::read_to_string(path)?;
+    let txs: Vec<TransactionViewV5> = serde_json::from_str(&json)?;
+
+    let (instructions, disabled) = decode_autopay_v5(&txs)?;
+    assert!(instructions.len() == 1);
+    let i = &instructions[0];
+    assert!(i.sender.to_hex_literal() == "0xbc25f79fef8a981be4636ac1a2d6f587");
+    assert!(i.payee.to_hex_literal() == "0xc0ffee");
+    assert!(i.uid == 1);
+    assert!(i.in_type == AutopayType::PercentOfBalance);
+    assert!(i.value == 1000);
+    assert!(i.end_epoch == 100);
+    assert!(i.version == 8900);
+
+    assert!(disabled.len() == 1);
+    assert!(disabled[0].sender == i.sender);
+    assert!(disabled[0].version == 8950);
+
+    // the other script functions are skipped
+    let path = fixtures::v5_json_tx_path().join("10000-10999.json");
+    let json = std::fs::read_to_string(path)?;
+    let txs: Vec<TransactionViewV5> = serde_json::from_str(&json)?;
+    let (instructions, disabled) = decode_autopay_v5(&txs)?;
+    assert!(instructions.is_empty());
+    assert!(disabled.is_empty());
+
+    Ok(())
+}
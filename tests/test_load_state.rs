@@ -1,10 +1,16 @@
 mod support;
 
+use diem_types::account_address::AccountAddress;
 use libra_forensic_db::{
+    analytics::ancestry::get_descendants,
     extract_snapshot::{extract_current_snapshot, extract_v5_snapshot},
     load_account_state::{impl_batch_snapshot_insert, snapshot_batch},
     neo4j_init::{get_neo4j_localhost_pool, maybe_create_indexes},
-    schema_account_state::WarehouseAccState,
+    scan::FrameworkVersion,
+    schema_account_state::{
+        DonorVoicePayment, DonorVoiceState, PaymentStatus, ValidatorState, VouchRecord,
+        WarehouseAccState,
+    },
 };
 use support::{
     fixtures::{v5_state_manifest_fixtures_path, v7_state_manifest_fixtures_path},
@@ -146,3 +152,441 @@ async fn test_snapshot_entrypoint() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_snapshot_chain_out_of_order() -> anyhow::Result<()> {
+    libra_forensic_db::log_setup();
+
+    let snap = |version: u64, balance: f64, sequence_num: u64| {
+        let mut s = WarehouseAccState::default();
+        s.time.version = version;
+        s.balance = balance;
+        s.sequence_num = sequence_num;
+        s
+    };
+    // loaded out of order, one per batch
+    let vec_snap = vec![snap(30, 3.0, 3), snap(10, 1.0, 1), snap(20, 2.5, 2)];
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let graph = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&graph)
+        .await
+        .expect("could start index");
+
+    snapshot_batch(&vec_snap, &graph, 1, "test_chain").await?;
+
+    let cypher_query = neo4rs::query(
+        "MATCH (a:Snapshot)-[r:NEXT]->(b:Snapshot)
+         RETURN a.version AS from, b.version AS to, r.delta_balance AS delta
+         ORDER BY from",
+    );
+    let mut result = graph.execute(cypher_query).await?;
+    let mut links = vec![];
+    while let Some(row) = result.next().await? {
+        let from: i64 = row.get("from").unwrap();
+        let to: i64 = row.get("to").unwrap();
+        let delta: f64 = row.get("delta").unwrap();
+        links.push((from, to, delta));
+    }
+    assert!(links == vec![(10, 20, 1.5), (20, 30, 0.5)]);
+
+    let cypher_query = neo4rs::query(
+        "MATCH (a:Account)
+         RETURN a.latest_version AS version, a.latest_balance AS balance",
+    );
+    let mut result = graph.execute(cypher_query).await?;
+    let row = result.next().await?.unwrap();
+    let version: i64 = row.get("version").unwrap();
+    let balance: f64 = row.get("balance").unwrap();
+    assert!(version == 30);
+    assert!(balance == 3.0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_snapshot_chain_across_eras() -> anyhow::Result<()> {
+    libra_forensic_db::log_setup();
+
+    let snap = |framework_version: FrameworkVersion, version: u64, balance: f64| {
+        let mut s = WarehouseAccState::default();
+        s.time.framework_version = framework_version;
+        s.time.version = version;
+        s.balance = balance;
+        s
+    };
+    // V7 versions restart after the fork, lower than the V5 versions
+    let vec_snap = vec![
+        snap(FrameworkVersion::V7, 5_000_000, 70.0),
+        snap(FrameworkVersion::V5, 120_000_000, 2.0),
+        snap(FrameworkVersion::V5, 100_000_000, 1.0),
+    ];
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let graph = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&graph)
+        .await
+        .expect("could start index");
+
+    snapshot_batch(&vec_snap, &graph, 1, "test_chain_eras").await?;
+
+    let cypher_query = neo4rs::query(
+        "MATCH (a:Snapshot)-[r:NEXT]->(b:Snapshot)
+         RETURN a.version AS from, b.version AS to, r.delta_balance AS delta_balance
+         ORDER BY from",
+    );
+    let mut result = graph.execute(cypher_query).await?;
+    let mut links = vec![];
+    while let Some(row) = result.next().await? {
+        let from: i64 = row.get("from").unwrap();
+        let to: i64 = row.get("to").unwrap();
+        let delta_balance: Option<f64> = row.get("delta_balance").unwrap();
+        links.push((from, to, delta_balance));
+    }
+    assert!(
+        links
+            == vec![
+                (100_000_000, 120_000_000, Some(1.0)),
+                // V5 coins are not comparable to V7 coins
+                (120_000_000, 5_000_000, None)
+            ]
+    );
+
+    let cypher_query = neo4rs::query(
+        "MATCH (a:Account)
+         RETURN a.latest_version AS version, a.latest_balance AS balance,
+           a.latest_framework_version AS framework_version",
+    );
+    let mut result = graph.execute(cypher_query).await?;
+    let row = result.next().await?.unwrap();
+    let version: i64 = row.get("version").unwrap();
+    let balance: f64 = row.get("balance").unwrap();
+    let framework_version: String = row.get("framework_version").unwrap();
+    assert!(version == 5_000_000);
+    assert!(balance == 70.0);
+    assert!(framework_version == "V7");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_snapshot_ancestry() -> anyhow::Result<()> {
+    libra_forensic_db::log_setup();
+
+    let root = AccountAddress::from_hex_literal("0x1a")?;
+    let child = AccountAddress::from_hex_literal("0x2b")?;
+    let grandchild = AccountAddress::from_hex_literal("0x3c")?;
+    let other = AccountAddress::from_hex_literal("0x4d")?;
+
+    let snap = |address: AccountAddress, ancestry: Vec<AccountAddress>| {
+        let mut s = WarehouseAccState::new(address);
+        s.ancestry = ancestry;
+        s
+    };
+    let vec_snap = vec![
+        snap(root, vec![]),
+        snap(child, vec![root]),
+        snap(grandchild, vec![root, child]),
+        snap(other, vec![]),
+    ];
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let graph = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&graph)
+        .await
+        .expect("could start index");
+
+    snapshot_batch(&vec_snap, &graph, 10, "test_ancestry").await?;
+
+    let descendants = get_descendants(&graph, root, None).await?;
+    assert!(descendants.len() == 2);
+    assert!(descendants[0].address == child.to_hex_literal());
+    assert!(descendants[0].depth == 1);
+    assert!(descendants[1].address == grandchild.to_hex_literal());
+    assert!(descendants[1].depth == 2);
+    assert!(descendants[1].parent == Some(child.to_hex_literal()));
+
+    let descendants = get_descendants(&graph, root, Some(1)).await?;
+    assert!(descendants.len() == 1);
+
+    let descendants = get_descendants(&graph, other, None).await?;
+    assert!(descendants.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_snapshot_vouch_state() -> anyhow::Result<()> {
+    libra_forensic_db::log_setup();
+
+    let alice = AccountAddress::from_hex_literal("0xa11ce")?;
+    let bob = AccountAddress::from_hex_literal("0xb0b")?;
+    let carol = AccountAddress::from_hex_literal("0xca401")?;
+
+    let snap = |address: AccountAddress,
+                epoch: u64,
+                received: Vec<VouchRecord>,
+                given: Vec<VouchRecord>| {
+        let mut s = WarehouseAccState::new(address);
+        s.time.epoch = epoch;
+        s.time.version = epoch * 100;
+        s.vouches_received = received;
+        s.vouches_given = given;
+        s
+    };
+
+    let vec_snap = vec![
+        // bob vouched for alice in epoch 1, listed by both accounts
+        snap(alice, 10, VouchRecord::from_lists(&[bob], &[1]), vec![]),
+        snap(bob, 10, vec![], VouchRecord::from_lists(&[alice], &[1])),
+        // later, carol vouches for alice and bob's vouch has expired
+        snap(
+            alice,
+            50,
+            VouchRecord::from_lists(&[bob, carol], &[1, 49]),
+            vec![],
+        ),
+    ];
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let graph = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&graph)
+        .await
+        .expect("could start index");
+
+    snapshot_batch(&vec_snap, &graph, 10, "test_vouch").await?;
+
+    let cypher_query = neo4rs::query(
+        "MATCH (from:Account)-[r:VouchState]->(to:Account {address: '0xa11ce'})
+         RETURN r.epoch AS epoch, from.address AS from, r.expired AS expired
+         ORDER BY epoch, from",
+    );
+    let mut result = graph.execute(cypher_query).await?;
+    let mut edges = vec![];
+    while let Some(row) = result.next().await? {
+        let epoch: i64 = row.get("epoch").unwrap();
+        let from: String = row.get("from").unwrap();
+        let expired: bool = row.get("expired").unwrap();
+        edges.push((epoch, from, expired));
+    }
+    assert!(
+        edges
+            == vec![
+                (10, "0xb0b".to_string(), false),
+                (50, "0xb0b".to_string(), true),
+                (50, "0xca401".to_string(), false),
+            ]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_snapshot_donor_voice() -> anyhow::Result<()> {
+    libra_forensic_db::log_setup();
+
+    let wallet = AccountAddress::from_hex_literal("0xc0ffee")?;
+    let alice = AccountAddress::from_hex_literal("0xa11ce")?;
+    let bob = AccountAddress::from_hex_literal("0xb0b")?;
+
+    let payment = |uid: u64, status: PaymentStatus| DonorVoicePayment {
+        uid,
+        payee: bob,
+        value: 10.0,
+        description: "a \"quoted\" grant".to_string(),
+        deadline: 12,
+        epoch_latest_veto_received: 0,
+        status,
+    };
+
+    let mut s = WarehouseAccState::new(wallet);
+    s.time.epoch = 10;
+    s.donor_voice_acc = true;
+    s.donor_voice = Some(DonorVoiceState {
+        authorities: vec![alice, bob],
+        threshold: 2,
+        depositors: vec![alice],
+        payments: vec![
+            payment(1, PaymentStatus::Paid),
+            payment(2, PaymentStatus::Pending),
+            payment(3, PaymentStatus::Vetoed),
+        ],
+    });
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let graph = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&graph)
+        .await
+        .expect("could start index");
+
+    snapshot_batch(&[s], &graph, 10, "test_donor_voice").await?;
+
+    // the governance is on the wallet Account, not a separate node
+    let cypher_query = neo4rs::query(
+        "MATCH (auth:Account)-[:Authority]->(dv:Account:DonorVoice {address: '0xc0ffee'})
+         RETURN count(auth) AS authorities, dv.threshold AS threshold",
+    );
+    let mut result = graph.execute(cypher_query).await?;
+    let row = result.next().await?.unwrap();
+    let authorities: i64 = row.get("authorities").unwrap();
+    let threshold: i64 = row.get("threshold").unwrap();
+    assert!(authorities == 2);
+    assert!(threshold == 2);
+
+    let cypher_query = neo4rs::query(
+        "MATCH (:DonorVoice)-[:Scheduled]->(p:Payment)-[:PaidTo]->(:Account {address: '0xb0b'})
+         RETURN p.status AS status, p.description AS description
+         ORDER BY p.uid",
+    );
+    let mut result = graph.execute(cypher_query).await?;
+    let mut statuses = vec![];
+    while let Some(row) = result.next().await? {
+        let status: String = row.get("status").unwrap();
+        let description: String = row.get("description").unwrap();
+        assert!(description == "a \"quoted\" grant");
+        statuses.push(status);
+    }
+    assert!(statuses == vec!["Paid", "Pending", "Vetoed"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_snapshot_donor_voice_across_eras() -> anyhow::Result<()> {
+    libra_forensic_db::log_setup();
+
+    let wallet = AccountAddress::from_hex_literal("0xc0ffee")?;
+    let alice = AccountAddress::from_hex_literal("0xa11ce")?;
+
+    let snap = |framework_version: FrameworkVersion, version: u64, threshold: u64| {
+        let mut s = WarehouseAccState::new(wallet);
+        s.time.framework_version = framework_version;
+        s.time.version = version;
+        s.donor_voice_acc = true;
+        s.donor_voice = Some(DonorVoiceState {
+            authorities: vec![alice],
+            threshold,
+            ..Default::default()
+        });
+        s
+    };
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let graph = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&graph)
+        .await
+        .expect("could start index");
+
+    // the V5 version is higher, but V7 versions restart after the fork
+    snapshot_batch(
+        &[snap(FrameworkVersion::V7, 5, 3)],
+        &graph,
+        10,
+        "test_dv_v7",
+    )
+    .await?;
+    snapshot_batch(
+        &[snap(FrameworkVersion::V5, 1_000_000, 2)],
+        &graph,
+        10,
+        "test_dv_v5",
+    )
+    .await?;
+
+    let cypher_query = neo4rs::query(
+        "MATCH (dv:Account:DonorVoice {address: '0xc0ffee'})
+         RETURN dv.threshold AS threshold, dv.donor_voice_version AS version,
+           dv.donor_voice_framework_version AS framework_version",
+    );
+    let mut result = graph.execute(cypher_query).await?;
+    let row = result.next().await?.unwrap();
+    let threshold: i64 = row.get("threshold").unwrap();
+    let version: i64 = row.get("version").unwrap();
+    let framework_version: String = row.get("framework_version").unwrap();
+    assert!(threshold == 3);
+    assert!(version == 5);
+    assert!(framework_version == "V7");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_snapshot_validator_state() -> anyhow::Result<()> {
+    libra_forensic_db::log_setup();
+
+    let validator = AccountAddress::from_hex_literal("0xa11ce")?;
+    let operator = AccountAddress::from_hex_literal("0x0b")?;
+
+    let snap = |epoch: u64, bid: u64, jailed: bool| {
+        let mut s = WarehouseAccState::new(validator);
+        s.time.epoch = epoch;
+        s.time.version = epoch * 100;
+        s.time.framework_version = FrameworkVersion::V7;
+        s.vouches_received = VouchRecord::from_lists(&[operator, operator], &[1, epoch]);
+        let mut v = ValidatorState {
+            in_universe: true,
+            operator: Some(operator),
+            bid: Some(bid),
+            bid_expiration_epoch: Some(epoch + 1),
+            jailed,
+            ..Default::default()
+        };
+        v.set_vouch_eligibility(&s.vouches_received, epoch);
+        s.validator = Some(v);
+        s
+    };
+    // the first vouch expires by epoch 50
+    let vec_snap = vec![snap(10, 50, false), snap(50, 80, true)];
+    assert!(vec_snap[0].validator.as_ref().unwrap().vouch_eligible);
+    assert!(!vec_snap[1].validator.as_ref().unwrap().vouch_eligible);
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let graph = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&graph)
+        .await
+        .expect("could start index");
+
+    snapshot_batch(&vec_snap, &graph, 10, "test_validator").await?;
+
+    let cypher_query = neo4rs::query(
+        "MATCH (:Validator {address: '0xa11ce'})-[:ValidatorState]->(vs:ValidatorState)-[:Operator]->(op:Account)
+         RETURN vs.epoch AS epoch, vs.bid AS bid, vs.jailed AS jailed, op.address AS operator, vs.framework_version AS framework_version
+         ORDER BY epoch",
+    );
+    let mut result = graph.execute(cypher_query).await?;
+    let mut states = vec![];
+    while let Some(row) = result.next().await? {
+        let epoch: i64 = row.get("epoch").unwrap();
+        let bid: i64 = row.get("bid").unwrap();
+        let jailed: bool = row.get("jailed").unwrap();
+        let operator: String = row.get("operator").unwrap();
+        let framework_version: String = row.get("framework_version").unwrap();
+        assert!(operator == "0xb");
+        assert!(framework_version == "V7");
+        states.push((epoch, bid, jailed));
+    }
+    assert!(states == vec![(10, 50, false), (50, 80, true)]);
+
+    Ok(())
+}
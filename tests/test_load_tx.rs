@@ -1,15 +1,20 @@
 mod support;
 use anyhow::Result;
 use diem_crypto::HashValue;
+use diem_types::account_address::AccountAddress;
 
 use libra_forensic_db::{
     cypher_templates::{write_batch_tx_string, write_batch_user_create},
-    extract_transactions::extract_current_transactions,
+    extract_transactions::{extract_blocks, extract_current_transactions},
     load::{ingest_all, try_load_one_archive},
+    load_blocks::{block_batch, load_blocks_from_dir},
     load_tx_cypher::tx_batch,
     neo4j_init::{get_neo4j_localhost_pool, maybe_create_indexes},
-    scan::{scan_dir_archive, FrameworkVersion},
-    schema_transaction::WarehouseTxMaster,
+    scan::{scan_dir_archive, BundleContent, FrameworkVersion},
+    schema_transaction::{RelationLabel, WarehouseTxMaster},
+    validator_operators::{
+        impl_batch_operator_insert, OperatorRelation, ValidatorHumanName, ValidatorRole,
+    },
 };
 use neo4rs::query;
 use support::{fixtures, neo4j_testcontainer::start_neo4j_container};
@@ -99,7 +104,7 @@ async fn test_load_entry_point_tx() -> anyhow::Result<()> {
         .await
         .expect("could start index");
 
-    let res = try_load_one_archive(man, &graph, 10).await?;
+    let res = try_load_one_archive(man, &graph, 10, None).await?;
 
     assert!(res.unique_accounts == 31);
     assert!(res.created_accounts == 25);
@@ -123,7 +128,7 @@ async fn test_gzip_archive_entry_point() -> Result<()> {
         .expect("could not get neo4j connection pool");
     maybe_create_indexes(&graph).await?;
 
-    ingest_all(&map, &graph, false, 250).await?;
+    ingest_all(&map, &graph, false, 250, None, None).await?;
     Ok(())
 }
 
@@ -283,3 +288,200 @@ async fn batch_users_create_unit() -> Result<()> {
 
 //     Ok(())
 // }
+
+#[tokio::test]
+async fn test_operator_relations() -> Result<()> {
+    libra_forensic_db::log_setup();
+
+    let val = AccountAddress::from_hex_literal("0xa11ce")?;
+    let op = AccountAddress::from_hex_literal("0xb0b")?;
+    let relation = |operator, version, name: &str| OperatorRelation {
+        operator,
+        validator: val,
+        version,
+        function: "0x::ValidatorScripts::register_validator_config".to_owned(),
+        framework_version: FrameworkVersion::V5,
+        operator_name: Some(name.to_owned()),
+        validator_name: None,
+        network_addresses: Some("00".to_owned()),
+        fullnode_addresses: Some("01".to_owned()),
+    };
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let graph = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&graph)
+        .await
+        .expect("could start index");
+
+    let names = vec![ValidatorHumanName {
+        address: op,
+        human_name: "bob \"the operator\"".to_owned(),
+        role: ValidatorRole::Operator,
+        version: 1,
+    }];
+    // the later relation is loaded first, and the validator configures itself
+    impl_batch_operator_insert(
+        &graph,
+        &[
+            relation(op, 20, "bob's new name"),
+            relation(val, 30, "alice"),
+        ],
+        &names,
+    )
+    .await?;
+    impl_batch_operator_insert(&graph, &[relation(op, 10, "bob")], &[]).await?;
+
+    let cypher_query = query(
+        "MATCH (op:Account)-[rel:Operates]->(val:Account)
+         RETURN op.address AS op, op.human_name AS name, rel.first_version AS first, rel.last_version AS last",
+    );
+    let mut res = graph.execute(cypher_query).await?;
+    let row = res.next().await?.unwrap();
+    let address: String = row.get("op").unwrap();
+    let name: String = row.get("name").unwrap();
+    let first: u64 = row.get("first").unwrap();
+    let last: u64 = row.get("last").unwrap();
+    assert!(address == op.to_hex_literal());
+    assert!(name == "bob's new name");
+    assert!(first == 10);
+    assert!(last == 20);
+    // no self relation
+    assert!(res.next().await?.is_none());
+
+    let cypher_string = format!(
+        r#"MATCH (val:Account {{address: "{}"}})
+         RETURN val.human_name AS name, val.validator_config_version AS config_version"#,
+        val.to_hex_literal()
+    );
+    let mut res = graph.execute(query(&cypher_string)).await?;
+    let row = res.next().await?.unwrap();
+    let name: String = row.get("name").unwrap();
+    let config_version: u64 = row.get("config_version").unwrap();
+    assert!(name == "alice");
+    assert!(config_version == 30);
+
+    // versions restart after V5, an earlier V6 version is another edge
+    let v6_relation = OperatorRelation {
+        framework_version: FrameworkVersion::V6,
+        operator_name: None,
+        network_addresses: None,
+        fullnode_addresses: None,
+        ..relation(op, 5, "bob")
+    };
+    impl_batch_operator_insert(&graph, &[v6_relation], &[]).await?;
+
+    let cypher_query = query(
+        "MATCH (:Account)-[rel:Operates]->(:Account)
+         RETURN rel.framework_version AS framework_version, rel.first_version AS first
+         ORDER BY framework_version",
+    );
+    let mut res = graph.execute(cypher_query).await?;
+    let mut edges = vec![];
+    while let Some(row) = res.next().await? {
+        let framework_version: String = row.get("framework_version").unwrap();
+        let first: u64 = row.get("first").unwrap();
+        edges.push((framework_version, first));
+    }
+    assert!(edges == vec![("V5".to_owned(), 10), ("V6".to_owned(), 5)]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_load_blocks() -> Result<()> {
+    libra_forensic_db::log_setup();
+    let archive_path = support::fixtures::v6_tx_manifest_fixtures_path();
+    let (txs, _events) = extract_current_transactions(&archive_path, &FrameworkVersion::V6).await?;
+    let blocks = extract_blocks(&archive_path, &FrameworkVersion::V6).await?;
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let graph = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&graph)
+        .await
+        .expect("could start index");
+
+    tx_batch(&txs, &graph, 100, "test_load_blocks").await?;
+    // versions restart after V5, a V5 transaction within a block's range
+    let v5_tx = WarehouseTxMaster {
+        tx_hash: HashValue::random(),
+        sender: AccountAddress::from_hex_literal("0xa11ce")?,
+        relation_label: RelationLabel::Transfer(AccountAddress::from_hex_literal("0xb0b")?, 1),
+        version: blocks[0].version,
+        framework_version: FrameworkVersion::V5,
+        ..Default::default()
+    };
+    tx_batch(&[v5_tx], &graph, 100, "test_load_blocks_v5").await?;
+
+    let (merged, linked) = block_batch(&blocks, &graph, 100).await?;
+    assert!(merged == blocks.len() as u64);
+    // transactions before the first block metadata are in the previous archive
+    assert!(linked > 0 && linked <= txs.len() as u64);
+
+    // merged by height, so loading again adds nothing
+    block_batch(&blocks, &graph, 100).await?;
+    let cypher_query = query(
+        "MATCH (b:Block)
+         OPTIONAL MATCH (p:Account)-[:Proposed]->(b)
+         RETURN count(DISTINCT b) AS blocks, count(p) + sum(CASE WHEN b.nil_block THEN 1 ELSE 0 END) AS proposed",
+    );
+    let mut res = graph.execute(cypher_query).await?;
+    let row = res.next().await?.unwrap();
+    let count: u64 = row.get("blocks").unwrap();
+    let proposed: u64 = row.get("proposed").unwrap();
+    assert!(count == blocks.len() as u64);
+    // every block has a proposer, unless it is a NIL block
+    assert!(proposed == count);
+
+    let cypher_query = query(
+        "MATCH ()-[t:Tx]->()
+         WHERE t.block_height IS NOT NULL AND t.framework_version = 'V5'
+         RETURN count(t) AS v5_linked",
+    );
+    let mut res = graph.execute(cypher_query).await?;
+    let row = res.next().await?.unwrap();
+    let v5_linked: u64 = row.get("v5_linked").unwrap();
+    assert!(v5_linked == 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_load_blocks_from_dir() -> Result<()> {
+    libra_forensic_db::log_setup();
+    let archive_path = support::fixtures::v6_tx_manifest_fixtures_path();
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let graph = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&graph)
+        .await
+        .expect("could start index");
+
+    // the transactions are loaded with the label the scan gives the archive
+    let map = scan_dir_archive(&archive_path, Some(BundleContent::Transaction))?;
+    ingest_all(&map, &graph, false, 250, None, None).await?;
+
+    // the V6 archive is not skipped as V5
+    let merged = load_blocks_from_dir(&archive_path, &graph, 100).await?;
+    assert!(merged > 0);
+
+    let cypher_query = query(
+        "MATCH ()-[t:Tx]->()
+         WHERE t.block_height IS NOT NULL
+         RETURN count(t) AS linked",
+    );
+    let mut res = graph.execute(cypher_query).await?;
+    let row = res.next().await?.unwrap();
+    let linked: u64 = row.get("linked").unwrap();
+    assert!(linked > 0);
+
+    Ok(())
+}
@@ -42,3 +42,60 @@ async fn test_queue() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_enqueue_if_new() -> Result<()> {
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let pool = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&pool).await?;
+
+    let archive_id = "transaction_38100001-.541f";
+    assert!(queue::enqueue_if_new(&pool, archive_id).await?);
+    assert!(queue::get_queued(&pool).await?.len() == 1);
+
+    // a completed archive is not reset when it is seen again
+    queue::update_task(&pool, archive_id, true, 0).await?;
+    assert!(!queue::enqueue_if_new(&pool, archive_id).await?);
+    assert!(queue::are_all_completed(&pool, archive_id).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_retry_clears_failed() -> Result<()> {
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let pool = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&pool).await?;
+
+    let archive_id = "transaction_38100001-.541f";
+    let failed_query = || {
+        neo4rs::query(
+            r#"MATCH (a:Queue {archive_id: $archive_id, batch: 0})
+            RETURN a.failed IS NOT NULL AS failed, a.failed_reason IS NOT NULL AS has_reason"#,
+        )
+        .param("archive_id", archive_id)
+    };
+
+    queue::update_task_failed(&pool, archive_id, "corrupt chunk").await?;
+    let mut res = pool.execute(failed_query()).await?;
+    let row = res.next().await?.unwrap();
+    assert!(row.get::<bool>("failed").unwrap());
+    assert!(row.get::<bool>("has_reason").unwrap());
+    assert!(queue::get_queued(&pool).await?.len() == 1);
+
+    // the retry succeeds
+    queue::update_task(&pool, archive_id, true, 0).await?;
+    let mut res = pool.execute(failed_query()).await?;
+    let row = res.next().await?.unwrap();
+    assert!(!row.get::<bool>("failed").unwrap());
+    assert!(!row.get::<bool>("has_reason").unwrap());
+    assert!(queue::are_all_completed(&pool, archive_id).await?);
+
+    Ok(())
+}
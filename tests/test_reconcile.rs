@@ -0,0 +1,164 @@
+mod support;
+
+use diem_crypto::HashValue;
+use diem_types::account_address::AccountAddress;
+use libra_forensic_db::{
+    analytics::reconcile::{reconcile, DEFAULT_TOLERANCE},
+    load_account_state::impl_batch_snapshot_insert,
+    load_tx_cypher::impl_batch_tx_insert,
+    neo4j_init::{get_neo4j_localhost_pool, maybe_create_indexes},
+    scan::FrameworkVersion,
+    schema_account_state::{WarehouseAccState, WarehouseTime},
+    schema_transaction::{RelationLabel, WarehouseTxMaster},
+    util::{COIN_DECIMAL_PRECISION, LEGACY_REBASE_MULTIPLIER},
+};
+use support::neo4j_testcontainer::start_neo4j_container;
+
+#[tokio::test]
+async fn test_reconcile_snapshots() -> anyhow::Result<()> {
+    libra_forensic_db::log_setup();
+
+    let alice = AccountAddress::from_hex_literal("0xa11ce")?;
+    let bob = AccountAddress::from_hex_literal("0xb0b")?;
+
+    let snap = |address: AccountAddress, version: u64, balance: f64| {
+        let mut s = WarehouseAccState::new(address);
+        s.time.framework_version = FrameworkVersion::V7;
+        s.time.version = version;
+        s.balance = balance;
+        s
+    };
+    let snaps = vec![
+        snap(alice, 10, 10.0),
+        snap(bob, 10, 20.0),
+        snap(alice, 20, 15.0),
+        // two coins are not accounted for
+        snap(bob, 20, 13.0),
+        // a V5 snapshot at the same version is not compared
+        WarehouseAccState {
+            time: WarehouseTime {
+                framework_version: FrameworkVersion::V5,
+                version: 20,
+                ..Default::default()
+            },
+            ..WarehouseAccState::new(alice)
+        },
+    ];
+
+    let transfer = |version: u64, framework_version: FrameworkVersion| WarehouseTxMaster {
+        tx_hash: HashValue::random(),
+        sender: bob,
+        relation_label: RelationLabel::Transfer(alice, 5 * COIN_DECIMAL_PRECISION),
+        version,
+        framework_version,
+        ..Default::default()
+    };
+    // only the V7 transfer in the range is counted
+    let txs = vec![
+        transfer(15, FrameworkVersion::V7),
+        transfer(25, FrameworkVersion::V7),
+        transfer(15, FrameworkVersion::V5),
+    ];
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let graph = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&graph)
+        .await
+        .expect("could start index");
+
+    impl_batch_snapshot_insert(&graph, &snaps).await?;
+    impl_batch_tx_insert(&graph, &txs).await?;
+
+    let report = reconcile(
+        &graph,
+        &FrameworkVersion::V7,
+        10,
+        20,
+        DEFAULT_TOLERANCE,
+        true,
+    )
+    .await?;
+    assert!(report.accounts == 2);
+    assert!(report.discrepancies.len() == 1);
+    let d = &report.discrepancies[0];
+    assert!(d.address == bob.to_hex_literal());
+    assert!(d.tx_net == -5.0);
+    assert!(d.unexplained == -2.0);
+
+    let cypher_query = neo4rs::query(
+        "MATCH (:Account)-[:HasDiscrepancy]->(d:Discrepancy)
+         RETURN count(d) AS discrepancies",
+    );
+    let mut result = graph.execute(cypher_query).await?;
+    let row = result.next().await?.unwrap();
+    let discrepancies: i64 = row.get("discrepancies").unwrap();
+    assert!(discrepancies == 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reconcile_v5_rebased_coins() -> anyhow::Result<()> {
+    libra_forensic_db::log_setup();
+
+    let alice = AccountAddress::from_hex_literal("0xa11ce")?;
+    let bob = AccountAddress::from_hex_literal("0xb0b")?;
+
+    // V5 balances are not rebased
+    let snap = |address: AccountAddress, version: u64, balance: f64| {
+        let mut s = WarehouseAccState::new(address);
+        s.time.framework_version = FrameworkVersion::V5;
+        s.time.version = version;
+        s.balance = balance;
+        s
+    };
+    let snaps = vec![
+        snap(alice, 10, 10.0),
+        snap(bob, 10, 20.0),
+        snap(alice, 20, 15.0),
+        snap(bob, 20, 15.0),
+    ];
+
+    // the coins of V5 transactions are rebased when they are extracted
+    let txs = vec![WarehouseTxMaster {
+        tx_hash: HashValue::random(),
+        sender: bob,
+        relation_label: RelationLabel::Transfer(
+            alice,
+            5 * COIN_DECIMAL_PRECISION * LEGACY_REBASE_MULTIPLIER,
+        ),
+        version: 15,
+        framework_version: FrameworkVersion::V5,
+        ..Default::default()
+    }];
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let graph = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&graph)
+        .await
+        .expect("could start index");
+
+    impl_batch_snapshot_insert(&graph, &snaps).await?;
+    impl_batch_tx_insert(&graph, &txs).await?;
+
+    // the transfer explains the balance changes exactly
+    let report = reconcile(
+        &graph,
+        &FrameworkVersion::V5,
+        10,
+        20,
+        DEFAULT_TOLERANCE,
+        false,
+    )
+    .await?;
+    assert!(report.accounts == 2);
+    assert!(report.discrepancies.is_empty());
+
+    Ok(())
+}
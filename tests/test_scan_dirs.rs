@@ -1,7 +1,12 @@
 mod support;
 
+use std::path::Path;
+
 use anyhow::Result;
-use libra_forensic_db::scan::{scan_dir_archive, BundleContent, FrameworkVersion};
+use libra_forensic_db::{
+    coverage::{coverage_all, coverage_report, CoverageIssue},
+    scan::{scan_dir_archive, ArchiveMap, BundleContent, FrameworkVersion, ManifestInfo},
+};
 use support::fixtures;
 
 #[test]
@@ -31,6 +36,85 @@ fn test_scan_dir_for_v7_manifests() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_scan_labels_current_tx_archives() -> Result<()> {
+    // V6 and V7 transaction archives are both labeled V7, like the snapshots
+    for start_here in [fixtures::v6_fixtures_path(), fixtures::v7_fixtures_path()] {
+        let s = scan_dir_archive(&start_here, Some(BundleContent::Transaction))?;
+        assert!(!s.0.is_empty());
+        assert!(s.0.values().all(|m| m.version == FrameworkVersion::V7));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_scan_reads_version_ranges() -> Result<()> {
+    let start_here = fixtures::v7_fixtures_path();
+
+    let s = scan_dir_archive(&start_here, None)?;
+
+    let tx =
+        s.0.values()
+            .find(|m| m.archive_id == "transaction_38100001-.541f")
+            .unwrap();
+    assert!(tx.first_version == Some(38100001));
+    assert!(tx.last_version == Some(38200000));
+
+    let snap =
+        s.0.values()
+            .find(|m| m.archive_id == "state_epoch_116_ver_38180075.05af")
+            .unwrap();
+    assert!(snap.first_version == Some(38180075));
+    assert!(snap.first_epoch == Some(116));
+
+    let epoch =
+        s.0.values()
+            .find(|m| m.contents == BundleContent::EpochEnding)
+            .unwrap();
+    assert!(epoch.first_epoch == Some(116));
+    assert!(epoch.last_version == Some(38180075));
+
+    let reports = coverage_all(&s);
+    assert!(!reports.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_coverage_separates_framework_versions() {
+    let archive = |dir: &str, version: FrameworkVersion, first: u64, last: u64| {
+        let mut man = ManifestInfo::new(Path::new(dir));
+        man.version = version;
+        man.contents = BundleContent::Transaction;
+        man.first_version = Some(first);
+        man.last_version = Some(last);
+        (man.archive_dir.clone(), man)
+    };
+
+    // versions restart at the fork, so these overlap only numerically
+    let map = ArchiveMap(
+        [
+            archive("/archives/v5_tx_1", FrameworkVersion::V5, 1, 100),
+            archive("/archives/v5_tx_101", FrameworkVersion::V5, 101, 200),
+            archive("/archives/v7_tx_1", FrameworkVersion::V7, 1, 150),
+            // a real overlap within V7
+            archive("/archives/v7_tx_101", FrameworkVersion::V7, 101, 300),
+        ]
+        .into_iter()
+        .collect(),
+    );
+
+    let report = coverage_report(&map, BundleContent::Transaction);
+    assert!(report.ranges.len() == 4);
+    assert!(report.issues.len() == 1);
+    assert!(matches!(
+        &report.issues[0],
+        CoverageIssue::Overlap { archive_a, archive_b, first: 101, last: 150 }
+            if archive_a == "v7_tx_1" && archive_b == "v7_tx_101"
+    ));
+}
+
 // TODO: check scan dirs
 #[ignore]
 #[test]
@@ -43,7 +127,7 @@ fn test_scan_dir_for_compressed_v7_manifests() -> Result<()> {
     // assert!(archives.0.iter().len() == 0);
 
     // // This time the scan should find readable files
-    // let (_, unzipped_dir) = test_helper_temp_unzipped(&start_here, false)?;
+    // let (_, unzipped_dir) = test_helper_temp_unzipped(&start_here)?;
 
     // let archives = scan_dir_archive(unzipped_dir.path(), None)?;
     // assert!(archives.0.iter().len() > 0);
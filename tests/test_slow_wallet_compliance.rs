@@ -0,0 +1,133 @@
+mod support;
+
+use diem_crypto::HashValue;
+use diem_types::account_address::AccountAddress;
+use libra_forensic_db::{
+    analytics::slow_wallet_compliance::{
+        slow_wallet_compliance, ComplianceIssue, DEFAULT_TOLERANCE,
+    },
+    load_account_state::impl_batch_snapshot_insert,
+    load_tx_cypher::impl_batch_tx_insert,
+    neo4j_init::{get_neo4j_localhost_pool, maybe_create_indexes},
+    scan::FrameworkVersion,
+    schema_account_state::WarehouseAccState,
+    schema_transaction::{RelationLabel, WarehouseTxMaster},
+    util::{COIN_DECIMAL_PRECISION, LEGACY_REBASE_MULTIPLIER},
+};
+use support::neo4j_testcontainer::start_neo4j_container;
+
+#[tokio::test]
+async fn test_slow_wallet_compliance() -> anyhow::Result<()> {
+    libra_forensic_db::log_setup();
+
+    let alice = AccountAddress::from_hex_literal("0xa11ce")?;
+    let bob = AccountAddress::from_hex_literal("0xb0b")?;
+    let carol = AccountAddress::from_hex_literal("0xca401")?;
+    let dave = AccountAddress::from_hex_literal("0xda4e")?;
+
+    let snap = |framework_version: FrameworkVersion,
+                version: u64,
+                epoch: u64,
+                unlocked: f64,
+                transferred: f64| {
+        let mut s = WarehouseAccState::new(alice);
+        s.time.framework_version = framework_version;
+        s.time.version = version;
+        s.time.epoch = epoch;
+        s.slow_wallet_acc = true;
+        s.slow_wallet_unlocked = Some(unlocked);
+        s.slow_wallet_transferred = Some(transferred);
+        s
+    };
+    let snaps = vec![
+        snap(FrameworkVersion::V6, 10, 1, 5.0, 0.0),
+        snap(FrameworkVersion::V6, 20, 2, 8.0, 8.0),
+        // versions restart after V5, this is long before the V6 snapshots
+        snap(FrameworkVersion::V5, 1000, 100, 1000.0, 0.0),
+        // V5 slow wallet amounts are not rebased
+        snap(FrameworkVersion::V5, 1100, 101, 1000.0, 100.0),
+    ];
+
+    let transfer = |framework_version: FrameworkVersion,
+                    from: AccountAddress,
+                    to: AccountAddress,
+                    coins: u64,
+                    version: u64| WarehouseTxMaster {
+        tx_hash: HashValue::random(),
+        sender: from,
+        relation_label: RelationLabel::Transfer(to, coins * COIN_DECIMAL_PRECISION / 10),
+        version,
+        framework_version,
+        ..Default::default()
+    };
+    let txs = vec![
+        // 10 coins sent, but only 8 were unlocked
+        transfer(FrameworkVersion::V6, alice, bob, 100, 15),
+        // most of it is sent on right away
+        transfer(FrameworkVersion::V6, bob, carol, 95, 16),
+        transfer(FrameworkVersion::V6, bob, dave, 10, 17),
+        // a V5 relay, within the version range of the V6 one
+        transfer(FrameworkVersion::V5, alice, bob, 500, 18),
+        transfer(FrameworkVersion::V5, bob, dave, 480, 19),
+        // 100 coins before the rebase, within the V5 unlocked amount
+        transfer(
+            FrameworkVersion::V5,
+            alice,
+            carol,
+            1000 * LEGACY_REBASE_MULTIPLIER,
+            1050,
+        ),
+    ];
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let graph = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&graph)
+        .await
+        .expect("could start index");
+
+    impl_batch_snapshot_insert(&graph, &snaps).await?;
+    impl_batch_tx_insert(&graph, &txs).await?;
+
+    let report = slow_wallet_compliance(&graph, DEFAULT_TOLERANCE, 0.9, 100).await?;
+    // one report per framework version
+    assert!(report.wallets.len() == 2);
+    let v5 = report
+        .wallets
+        .iter()
+        .find(|w| w.framework_version == "V5")
+        .unwrap();
+    assert!(v5.issues.is_empty());
+    assert!(v5.outbound_coins == 100.0);
+    let w = report
+        .wallets
+        .iter()
+        .find(|w| w.framework_version == "V6")
+        .unwrap();
+    assert!(w.address == alice.to_hex_literal());
+    assert!(w.unlock_per_epoch == Some(3.0));
+    assert!(
+        w.issues
+            == vec![ComplianceIssue::TxExceedsUnlocked {
+                version: 20,
+                unlocked: 8.0,
+                tx_total: 10.0
+            }]
+    );
+
+    // the V6 transfer to bob and the V5 transfer to dave are not a relay
+    assert!(report.relays.len() == 2);
+    let r = &report.relays[0];
+    assert!(r.framework_version == "V5");
+    assert!(r.destination == dave.to_hex_literal());
+    assert!(r.amount_out == 48.0);
+    let r = &report.relays[1];
+    assert!(r.framework_version == "V6");
+    assert!(r.intermediary == bob.to_hex_literal());
+    assert!(r.destination == carol.to_hex_literal());
+    assert!(r.amount_out == 9.5);
+
+    Ok(())
+}
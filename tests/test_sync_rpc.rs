@@ -0,0 +1,180 @@
+mod support;
+
+use std::{collections::HashMap, sync::Arc};
+
+use diem_crypto::HashValue;
+use diem_types::write_set::WriteSet;
+use libra_forensic_db::{
+    extract_transactions::extract_current_transactions,
+    load::ingest_all,
+    neo4j_init::{get_neo4j_localhost_pool, maybe_create_indexes},
+    scan::{scan_dir_archive, BundleContent, FrameworkVersion},
+    sync_rpc::{get_highest_version, sync_rpc, RpcClient, TransactionOnChainData},
+};
+use libra_storage::read_tx_chunk::{load_chunk, load_tx_chunk_manifest};
+use neo4rs::query;
+use serde_json::json;
+use support::{mock_rpc::start_mock_rpc, neo4j_testcontainer::start_neo4j_container};
+
+/// record the transactions of an archive in the shape the REST API returns them
+async fn recorded_transactions() -> anyhow::Result<Vec<TransactionOnChainData>> {
+    let archive_path = support::fixtures::v6_tx_manifest_fixtures_path();
+    let manifest = load_tx_chunk_manifest(&archive_path.join("transaction.manifest"))?;
+
+    let mut recorded = vec![];
+    for each_chunk_manifest in manifest.chunks {
+        let first_version = each_chunk_manifest.first_version;
+        let chunk = load_chunk(&archive_path, each_chunk_manifest).await?;
+        for (i, ((transaction, info), events)) in chunk
+            .txns
+            .into_iter()
+            .zip(chunk.txn_infos)
+            .zip(chunk.event_vecs)
+            .enumerate()
+        {
+            recorded.push(TransactionOnChainData {
+                version: first_version + i as u64,
+                transaction,
+                info,
+                events,
+                accumulator_root_hash: HashValue::zero(),
+                changes: WriteSet::default(),
+            });
+        }
+    }
+    Ok(recorded)
+}
+
+/// parse the query string of /v1/transactions?start=1&limit=2
+fn query_params(path: &str) -> HashMap<String, u64> {
+    path.split_once('?')
+        .map(|(_, q)| q)
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|kv| kv.split_once('='))
+        .filter_map(|(k, v)| Some((k.to_string(), v.parse().ok()?)))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_sync_from_mock_rpc() -> anyhow::Result<()> {
+    let recorded = Arc::new(recorded_transactions().await?);
+    let first = recorded.first().unwrap().version;
+    let last = recorded.last().unwrap().version;
+
+    let r = recorded.clone();
+    let url = start_mock_rpc(Arc::new(move |path: &str| {
+        if path == "/v1" {
+            let body = json!({ "ledger_version": last.to_string() });
+            return Some(("application/json", body.to_string().into_bytes()));
+        }
+        if let Some(v) = path.strip_prefix("/v1/blocks/by_version/") {
+            let v: u64 = v.parse().ok()?;
+            // the latest block metadata at or before the version
+            let block_start = r
+                .iter()
+                .filter(|t| t.version <= v && t.transaction.try_as_block_metadata().is_some())
+                .map(|t| t.version)
+                .last()
+                .unwrap_or(first);
+            let body = json!({ "first_version": block_start.to_string() });
+            return Some(("application/json", body.to_string().into_bytes()));
+        }
+        if path.starts_with("/v1/transactions") {
+            let q = query_params(path);
+            let start = *q.get("start")?;
+            let limit = *q.get("limit")?;
+            let page: Vec<_> = r
+                .iter()
+                .filter(|t| t.version >= start)
+                .take(limit as usize)
+                .cloned()
+                .collect();
+            return Some(("application/x-bcs", bcs::to_bytes(&page).ok()?));
+        }
+        None
+    }))
+    .await?;
+
+    let client = RpcClient::new(&url);
+    assert!(client.ledger_version().await? == last);
+    assert!(client.block_first_version(first).await? == first);
+
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let graph = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&graph).await?;
+
+    // a V5 transaction at a higher version, from before the hard fork,
+    // does not move the start of the sync
+    graph
+        .run(query(
+            r#"CREATE (:Account {address: "0xa"})-[:Tx {version: 100000000, framework_version: "V5"}]->(:Account {address: "0xb"})"#,
+        ))
+        .await?;
+    assert!(get_highest_version(&graph).await?.is_none());
+
+    let res = sync_rpc(&client, &graph, None, None, 10, &FrameworkVersion::V6).await?;
+
+    // the same records as extracting the archive
+    let archive_path = support::fixtures::v6_tx_manifest_fixtures_path();
+    let (from_archive, _) =
+        extract_current_transactions(&archive_path, &FrameworkVersion::V6).await?;
+    assert!(from_archive.len() == 27);
+    assert!(res.created_tx == from_archive.len() as u64);
+
+    let mut result = graph
+        .execute(query(
+            r#"MATCH ()-[r:Tx {framework_version: "V6"}]->()
+            RETURN r.tx_hash AS tx_hash ORDER BY r.version"#,
+        ))
+        .await?;
+    let mut synced_hashes = vec![];
+    while let Some(row) = result.next().await? {
+        synced_hashes.push(row.get::<String>("tx_hash")?);
+    }
+    let mut archive_txs: Vec<_> = from_archive.iter().collect();
+    archive_txs.sort_by_key(|t| t.version);
+    let archive_hashes: Vec<String> = archive_txs
+        .iter()
+        .map(|t| t.tx_hash.to_hex_literal())
+        .collect();
+    assert!(synced_hashes == archive_hashes);
+
+    let highest = get_highest_version(&graph).await?;
+    assert!(highest == archive_txs.last().map(|t| t.version));
+
+    // running again starts after the highest version, and finds nothing new
+    let res = sync_rpc(&client, &graph, None, None, 10, &FrameworkVersion::V6).await?;
+    assert!(res.created_tx == 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_highest_version_after_ingest() -> anyhow::Result<()> {
+    let c = start_neo4j_container();
+    let port = c.get_host_port_ipv4(7687);
+    let graph = get_neo4j_localhost_pool(port)
+        .await
+        .expect("could not get neo4j connection pool");
+    maybe_create_indexes(&graph).await?;
+
+    // the graph was filled from the archives, before syncing from a node
+    let map = scan_dir_archive(
+        &support::fixtures::v6_fixtures_path(),
+        Some(BundleContent::Transaction),
+    )?;
+    ingest_all(&map, &graph, false, 250, None, None).await?;
+
+    let archive_path = support::fixtures::v6_tx_manifest_fixtures_path();
+    let (from_archive, _) =
+        extract_current_transactions(&archive_path, &FrameworkVersion::V7).await?;
+    let highest = get_highest_version(&graph).await?;
+    assert!(highest.is_some());
+    assert!(highest == from_archive.iter().map(|t| t.version).max());
+
+    Ok(())
+}
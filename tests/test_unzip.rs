@@ -6,8 +6,7 @@ use libra_storage::read_tx_chunk::load_tx_chunk_manifest;
 #[test]
 fn test_unzip() {
     let archive_path = support::fixtures::v7_tx_manifest_fixtures_path();
-    let (_, temp_unzipped_dir) =
-        unzip_temp::test_helper_temp_unzipped(&archive_path, false).unwrap();
+    let (_, temp_unzipped_dir) = unzip_temp::test_helper_temp_unzipped(&archive_path).unwrap();
 
     assert!(temp_unzipped_dir.path().exists());
     assert!(temp_unzipped_dir
@@ -35,3 +34,58 @@ async fn test_extract_tx_with_gz_bug_from_archive() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_source_archive_is_not_modified() -> anyhow::Result<()> {
+    let fixture_path = support::fixtures::v7_tx_manifest_fixtures_path();
+
+    // make a copy of the archive, with a manifest which refers to .gz files
+    let temp = diem_temppath::TempPath::new();
+    temp.create_as_dir()?;
+    let source = temp.path().join("transaction_38100001-.541f");
+    std::fs::create_dir_all(&source)?;
+    let literal = std::fs::read_to_string(fixture_path.join("transaction.manifest"))?
+        .replace(".chunk", ".chunk.gz");
+    std::fs::write(source.join("transaction.manifest"), &literal)?;
+    std::fs::write(source.join("38100001-.chunk"), b"chunk")?;
+
+    let (archive_path, temppath_opt) = unzip_temp::maybe_handle_gz(&source)?;
+
+    // the fixed manifest is in a temp dir, the source is untouched
+    assert!(temppath_opt.is_some());
+    assert!(archive_path != source);
+    let fixed = std::fs::read_to_string(archive_path.join("transaction.manifest"))?;
+    assert!(!fixed.contains(".gz"));
+    assert!(archive_path.join("38100001-.chunk").exists());
+
+    let original = std::fs::read_to_string(source.join("transaction.manifest"))?;
+    assert!(original == literal);
+
+    Ok(())
+}
+
+#[test]
+fn test_corrupt_gz_is_reported() -> anyhow::Result<()> {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    let temp = diem_temppath::TempPath::new();
+    temp.create_as_dir()?;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(b"good chunk")?;
+    std::fs::write(temp.path().join("good.chunk.gz"), encoder.finish()?)?;
+    std::fs::write(temp.path().join("bad.chunk.gz"), b"not a gzip file")?;
+
+    let dst = diem_temppath::TempPath::new();
+    dst.create_as_dir()?;
+
+    let err = unzip_temp::decompress_all_gz(temp.path(), dst.path()).unwrap_err();
+    let msg = format!("{:#}", err);
+    assert!(msg.contains("1 of 2 files"));
+    assert!(msg.contains("bad.chunk.gz"));
+    // the good file was still decompressed
+    assert!(dst.path().join("good.chunk").exists());
+
+    Ok(())
+}
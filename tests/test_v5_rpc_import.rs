@@ -0,0 +1,56 @@
+mod support;
+
+use libra_forensic_db::{
+    scan::FrameworkVersion,
+    v5_rpc_to_raw::{import_v5_rpc, V5RpcEnvelope, V5RpcImport},
+};
+use serde_json::json;
+
+#[test]
+fn test_import_v5_rpc_responses() -> anyhow::Result<()> {
+    let path = support::fixtures::v5_fixtures_path().join("json-rpc");
+    let import = import_v5_rpc(&path)?;
+
+    assert!(import.txs.len() == 1);
+    let tx = import.txs.first().unwrap();
+    assert!(tx.version == 9768);
+    assert!(tx.function.contains("create_user_by_coin_tx"));
+    // the same events are in get_transactions and get_events, only linked once
+    assert!(tx.events.len() == 2);
+    assert!(tx.events.iter().any(|e| e.event_name == "receivedpayment"));
+
+    assert!(import.accounts.len() == 1);
+    let acc = import.accounts.first().unwrap();
+    assert!(acc.balance == 1.0);
+    assert!(acc.time.version == 9768);
+    assert!(acc.time.epoch == 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_v5_rpc_account_epoch_as_state_archive() -> anyhow::Result<()> {
+    let path = support::fixtures::v5_fixtures_path().join("json-rpc");
+    let mut import = V5RpcImport::default();
+    import.add_response_file(&path.join("get_account.json"))?;
+
+    let events: V5RpcEnvelope = serde_json::from_value(json!({
+        "diem_ledger_version": 9768,
+        "result": [{
+            "key": "0400000000000000000000000000000000000000000000000000000000000000",
+            "sequence_number": 3,
+            "transaction_version": 9000,
+            "data": { "type": "newepoch", "epoch": 4 }
+        }]
+    }))?;
+    import.add_response(events)?;
+
+    // V5 state archives have no epoch, so the account keeps epoch 0 and
+    // merges with the same state from an archive
+    let acc = import.accounts.first().unwrap();
+    assert!(acc.time.version == 9768);
+    assert!(acc.time.epoch == 0);
+    assert!(acc.time.framework_version == FrameworkVersion::V5);
+
+    Ok(())
+}